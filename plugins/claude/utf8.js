@@ -0,0 +1,89 @@
+// Decode UTF-8 bytes to a string, with U+FFFD for invalid sequences.
+export function utf8DecodeBytes(bytes) {
+  // Prefer native TextDecoder when available (QuickJS may not expose it).
+  if (typeof TextDecoder !== "undefined") {
+    try {
+      return new TextDecoder("utf-8", { fatal: false }).decode(new Uint8Array(bytes))
+    } catch {}
+  }
+
+  // Minimal UTF-8 decoder (replacement char on invalid sequences).
+  let out = ""
+  for (let i = 0; i < bytes.length; ) {
+    const b0 = bytes[i] & 0xff
+    if (b0 < 0x80) {
+      out += String.fromCharCode(b0)
+      i += 1
+      continue
+    }
+
+    // 2-byte
+    if (b0 >= 0xc2 && b0 <= 0xdf) {
+      if (i + 1 >= bytes.length) {
+        out += "\ufffd"
+        break
+      }
+      const b1 = bytes[i + 1] & 0xff
+      if ((b1 & 0xc0) !== 0x80) {
+        out += "\ufffd"
+        i += 1
+        continue
+      }
+      const cp = ((b0 & 0x1f) << 6) | (b1 & 0x3f)
+      out += String.fromCharCode(cp)
+      i += 2
+      continue
+    }
+
+    // 3-byte
+    if (b0 >= 0xe0 && b0 <= 0xef) {
+      if (i + 2 >= bytes.length) {
+        out += "\ufffd"
+        break
+      }
+      const b1 = bytes[i + 1] & 0xff
+      const b2 = bytes[i + 2] & 0xff
+      const validCont = (b1 & 0xc0) === 0x80 && (b2 & 0xc0) === 0x80
+      const notOverlong = !(b0 === 0xe0 && b1 < 0xa0)
+      const notSurrogate = !(b0 === 0xed && b1 >= 0xa0)
+      if (!validCont || !notOverlong || !notSurrogate) {
+        out += "\ufffd"
+        i += 1
+        continue
+      }
+      const cp = ((b0 & 0x0f) << 12) | ((b1 & 0x3f) << 6) | (b2 & 0x3f)
+      out += String.fromCharCode(cp)
+      i += 3
+      continue
+    }
+
+    // 4-byte
+    if (b0 >= 0xf0 && b0 <= 0xf4) {
+      if (i + 3 >= bytes.length) {
+        out += "\ufffd"
+        break
+      }
+      const b1 = bytes[i + 1] & 0xff
+      const b2 = bytes[i + 2] & 0xff
+      const b3 = bytes[i + 3] & 0xff
+      const validCont = (b1 & 0xc0) === 0x80 && (b2 & 0xc0) === 0x80 && (b3 & 0xc0) === 0x80
+      const notOverlong = !(b0 === 0xf0 && b1 < 0x90)
+      const notTooHigh = !(b0 === 0xf4 && b1 > 0x8f)
+      if (!validCont || !notOverlong || !notTooHigh) {
+        out += "\ufffd"
+        i += 1
+        continue
+      }
+      const cp =
+        ((b0 & 0x07) << 18) | ((b1 & 0x3f) << 12) | ((b2 & 0x3f) << 6) | (b3 & 0x3f)
+      const n = cp - 0x10000
+      out += String.fromCharCode(0xd800 + ((n >> 10) & 0x3ff), 0xdc00 + (n & 0x3ff))
+      i += 4
+      continue
+    }
+
+    out += "\ufffd"
+    i += 1
+  }
+  return out
+}
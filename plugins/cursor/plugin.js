@@ -11,9 +11,11 @@
 
   function readStateValue(ctx, key) {
     try {
-      const sql =
-        "SELECT value FROM ItemTable WHERE key = '" + key + "' LIMIT 1;"
-      const json = ctx.host.sqlite.query(STATE_DB, sql)
+      const json = ctx.host.sqlite.query(
+        STATE_DB,
+        "SELECT value FROM ItemTable WHERE key = ? LIMIT 1",
+        [key]
+      )
       const rows = ctx.util.tryParseJson(json)
       if (!Array.isArray(rows)) {
         throw new Error("sqlite returned invalid json")
@@ -29,15 +31,11 @@
 
   function writeStateValue(ctx, key, value) {
     try {
-      // Escape single quotes in value for SQL
-      const escaped = String(value).replace(/'/g, "''")
-      const sql =
-        "INSERT OR REPLACE INTO ItemTable (key, value) VALUES ('" +
-        key +
-        "', '" +
-        escaped +
-        "');"
-      ctx.host.sqlite.exec(STATE_DB, sql)
+      ctx.host.sqlite.exec(
+        STATE_DB,
+        "INSERT OR REPLACE INTO ItemTable (key, value) VALUES (?, ?)",
+        [key, String(value)]
+      )
       return true
     } catch (e) {
       ctx.host.log.warn("sqlite write failed for " + key + ": " + String(e))
@@ -130,7 +128,7 @@
   }
 
   function connectPost(ctx, url, token) {
-    return ctx.util.request({
+    return ctx.host.http.requestAsync({
       method: "POST",
       url: url,
       headers: {
@@ -143,7 +141,24 @@
     })
   }
 
-  function probe(ctx) {
+  // Never rejects: resolves to { resp } or { error } so one failure doesn't sink the others.
+  function settle(promise) {
+    return promise.then(
+      (resp) => ({ resp }),
+      (error) => ({ error })
+    )
+  }
+
+  // Usage, plan and credits are independent, so fetch them concurrently.
+  function fetchDashboard(ctx, token) {
+    return Promise.all([
+      settle(connectPost(ctx, USAGE_URL, token)),
+      settle(connectPost(ctx, PLAN_URL, token)),
+      settle(connectPost(ctx, CREDITS_URL, token)),
+    ])
+  }
+
+  async function probe(ctx) {
     let accessToken = readStateValue(ctx, "cursorAuth/accessToken")
     const refreshTokenValue = readStateValue(ctx, "cursorAuth/refreshToken")
 
@@ -175,34 +190,27 @@
       }
     }
 
-    let usageResp
+    let [usageResult, planResult, creditsResult] = await fetchDashboard(ctx, accessToken)
     let didRefresh = false
-    try {
-      usageResp = ctx.util.retryOnceOnAuth({
-        request: (token) => {
-          try {
-            return connectPost(ctx, USAGE_URL, token || accessToken)
-          } catch (e) {
-            ctx.host.log.error("usage request exception: " + String(e))
-            if (didRefresh) {
-              throw "Usage request failed after refresh. Try again."
-            }
-            throw "Usage request failed. Check your connection."
-          }
-        },
-        refresh: () => {
-          ctx.host.log.info("usage returned 401, attempting refresh")
-          didRefresh = true
-          const refreshed = refreshToken(ctx, refreshTokenValue)
-          if (refreshed) accessToken = refreshed
-          return refreshed
-        },
-      })
-    } catch (e) {
-      if (typeof e === "string") throw e
-      ctx.host.log.error("usage request failed: " + String(e))
+
+    if (usageResult.resp && ctx.util.isAuthStatus(usageResult.resp.status)) {
+      ctx.host.log.info("usage returned 401, attempting refresh")
+      didRefresh = true
+      const refreshed = refreshToken(ctx, refreshTokenValue)
+      if (refreshed) {
+        accessToken = refreshed
+        ;[usageResult, planResult, creditsResult] = await fetchDashboard(ctx, accessToken)
+      }
+    }
+
+    if (usageResult.error) {
+      ctx.host.log.error("usage request exception: " + String(usageResult.error))
+      if (didRefresh) {
+        throw "Usage request failed after refresh. Try again."
+      }
       throw "Usage request failed. Check your connection."
     }
+    const usageResp = usageResult.resp
 
     if (ctx.util.isAuthStatus(usageResp.status)) {
       ctx.host.log.error("usage returned auth error after all retries: status=" + usageResp.status)
@@ -226,26 +234,20 @@
     }
 
     let planName = ""
-    try {
-      const planResp = connectPost(ctx, PLAN_URL, accessToken)
-      if (planResp.status >= 200 && planResp.status < 300) {
-        const plan = ctx.util.tryParseJson(planResp.bodyText)
-        if (plan && plan.planInfo && plan.planInfo.planName) {
-          planName = plan.planInfo.planName
-        }
+    if (planResult.error) {
+      ctx.host.log.warn("plan info fetch failed: " + String(planResult.error))
+    } else if (planResult.resp.status >= 200 && planResult.resp.status < 300) {
+      const plan = ctx.util.tryParseJson(planResult.resp.bodyText)
+      if (plan && plan.planInfo && plan.planInfo.planName) {
+        planName = plan.planInfo.planName
       }
-    } catch (e) {
-      ctx.host.log.warn("plan info fetch failed: " + String(e))
     }
 
     let creditGrants = null
-    try {
-      const creditsResp = connectPost(ctx, CREDITS_URL, accessToken)
-      if (creditsResp.status >= 200 && creditsResp.status < 300) {
-        creditGrants = ctx.util.tryParseJson(creditsResp.bodyText)
-      }
-    } catch (e) {
-      ctx.host.log.warn("credit grants fetch failed: " + String(e))
+    if (creditsResult.error) {
+      ctx.host.log.warn("credit grants fetch failed: " + String(creditsResult.error))
+    } else if (creditsResult.resp.status >= 200 && creditsResult.resp.status < 300) {
+      creditGrants = ctx.util.tryParseJson(creditsResult.resp.bodyText)
     }
 
     let plan = null
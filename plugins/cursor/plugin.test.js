@@ -16,7 +16,7 @@ describe("cursor plugin", () => {
     const ctx = makeCtx()
     ctx.host.sqlite.query.mockReturnValue(JSON.stringify([]))
     const plugin = await loadPlugin()
-    expect(() => plugin.probe(ctx)).toThrow("Not logged in")
+    await expect(plugin.probe(ctx)).rejects.toThrow("Not logged in")
   })
 
   it("throws on sqlite errors when reading token", async () => {
@@ -25,7 +25,7 @@ describe("cursor plugin", () => {
       throw new Error("boom")
     })
     const plugin = await loadPlugin()
-    expect(() => plugin.probe(ctx)).toThrow("Not logged in")
+    await expect(plugin.probe(ctx)).rejects.toThrow("Not logged in")
     expect(ctx.host.log.warn).toHaveBeenCalled()
   })
 
@@ -37,7 +37,7 @@ describe("cursor plugin", () => {
       bodyText: JSON.stringify({ enabled: false }),
     })
     const plugin = await loadPlugin()
-    expect(() => plugin.probe(ctx)).toThrow("Usage tracking disabled")
+    await expect(plugin.probe(ctx)).rejects.toThrow("Usage tracking disabled")
   })
 
   it("throws on missing plan usage data", async () => {
@@ -48,7 +48,7 @@ describe("cursor plugin", () => {
       bodyText: JSON.stringify({ enabled: true }),
     })
     const plugin = await loadPlugin()
-    expect(() => plugin.probe(ctx)).toThrow("Usage tracking disabled")
+    await expect(plugin.probe(ctx)).rejects.toThrow("Usage tracking disabled")
   })
 
   it("throws on missing plan usage limit", async () => {
@@ -62,7 +62,7 @@ describe("cursor plugin", () => {
       }),
     })
     const plugin = await loadPlugin()
-    expect(() => plugin.probe(ctx)).toThrow("Plan usage limit missing")
+    await expect(plugin.probe(ctx)).rejects.toThrow("Plan usage limit missing")
   })
 
   it("calculates planUsed from limit - remaining when totalSpend missing", async () => {
@@ -76,7 +76,7 @@ describe("cursor plugin", () => {
       }),
     })
     const plugin = await loadPlugin()
-    const result = plugin.probe(ctx)
+    const result = await plugin.probe(ctx)
     const planLine = result.lines.find((l) => l.label === "Plan usage")
     expect(planLine).toBeTruthy()
     // used = limit - remaining = 2400 - 1200 = 1200
@@ -104,7 +104,7 @@ describe("cursor plugin", () => {
       }
     })
     const plugin = await loadPlugin()
-    const result = plugin.probe(ctx)
+    const result = await plugin.probe(ctx)
     expect(result.plan).toBeTruthy()
     expect(result.lines.find((line) => line.label === "Plan usage")).toBeTruthy()
   })
@@ -128,7 +128,7 @@ describe("cursor plugin", () => {
       }
     })
     const plugin = await loadPlugin()
-    const result = plugin.probe(ctx)
+    const result = await plugin.probe(ctx)
     expect(result.plan).toBeFalsy()
   })
 
@@ -152,7 +152,7 @@ describe("cursor plugin", () => {
       }
     })
     const plugin = await loadPlugin()
-    const result = plugin.probe(ctx)
+    const result = await plugin.probe(ctx)
     expect(result.lines.find((line) => line.label === "On-demand")).toBeTruthy()
   })
 
@@ -161,7 +161,7 @@ describe("cursor plugin", () => {
     ctx.host.sqlite.query.mockReturnValue(JSON.stringify([{ value: "token" }]))
     ctx.host.http.request.mockReturnValue({ status: 401, bodyText: "" })
     const plugin = await loadPlugin()
-    expect(() => plugin.probe(ctx)).toThrow("Token expired")
+    await expect(plugin.probe(ctx)).rejects.toThrow("Token expired")
   })
 
   it("throws on http errors", async () => {
@@ -169,7 +169,7 @@ describe("cursor plugin", () => {
     ctx.host.sqlite.query.mockReturnValue(JSON.stringify([{ value: "token" }]))
     ctx.host.http.request.mockReturnValue({ status: 500, bodyText: "" })
     const plugin = await loadPlugin()
-    expect(() => plugin.probe(ctx)).toThrow("HTTP 500")
+    await expect(plugin.probe(ctx)).rejects.toThrow("HTTP 500")
   })
 
   it("throws on usage request errors", async () => {
@@ -179,7 +179,7 @@ describe("cursor plugin", () => {
       throw new Error("boom")
     })
     const plugin = await loadPlugin()
-    expect(() => plugin.probe(ctx)).toThrow("Usage request failed")
+    await expect(plugin.probe(ctx)).rejects.toThrow("Usage request failed")
   })
 
   it("throws on parse errors", async () => {
@@ -190,7 +190,7 @@ describe("cursor plugin", () => {
       bodyText: "not-json",
     })
     const plugin = await loadPlugin()
-    expect(() => plugin.probe(ctx)).toThrow("Usage response invalid")
+    await expect(plugin.probe(ctx)).rejects.toThrow("Usage response invalid")
   })
 
   it("handles plan fetch failure gracefully", async () => {
@@ -210,7 +210,7 @@ describe("cursor plugin", () => {
       throw new Error("plan fail")
     })
     const plugin = await loadPlugin()
-    const result = plugin.probe(ctx)
+    const result = await plugin.probe(ctx)
     expect(result.lines.find((line) => line.label === "Plan usage")).toBeTruthy()
   })
 
@@ -241,7 +241,7 @@ describe("cursor plugin", () => {
       return { status: 200, bodyText: "{}" }
     })
     const plugin = await loadPlugin()
-    const result = plugin.probe(ctx)
+    const result = await plugin.probe(ctx)
     
     // Credits should be first in the lines array
     expect(result.lines[0].label).toBe("Credits")
@@ -273,7 +273,7 @@ describe("cursor plugin", () => {
       return { status: 200, bodyText: "{}" }
     })
     const plugin = await loadPlugin()
-    const result = plugin.probe(ctx)
+    const result = await plugin.probe(ctx)
     
     // Plan usage should be first when Credits not available
     expect(result.lines[0].label).toBe("Plan usage")
@@ -287,11 +287,11 @@ describe("cursor plugin", () => {
       .replace(/=+$/g, "")
     const accessToken = `a.${expiredPayload}.c`
 
-    ctx.host.sqlite.query.mockImplementation((db, sql) => {
-      if (String(sql).includes("cursorAuth/accessToken")) {
+    ctx.host.sqlite.query.mockImplementation((db, sql, params) => {
+      if (params[0] === "cursorAuth/accessToken") {
         return JSON.stringify([{ value: accessToken }])
       }
-      if (String(sql).includes("cursorAuth/refreshToken")) {
+      if (params[0] === "cursorAuth/refreshToken") {
         return JSON.stringify([{ value: "refresh" }])
       }
       return JSON.stringify([])
@@ -313,18 +313,22 @@ describe("cursor plugin", () => {
     })
 
     const plugin = await loadPlugin()
-    const result = plugin.probe(ctx)
+    const result = await plugin.probe(ctx)
     expect(result.lines.find((line) => line.label === "Plan usage")).toBeTruthy()
-    expect(ctx.host.sqlite.exec).toHaveBeenCalled()
+    expect(ctx.host.sqlite.exec).toHaveBeenCalledWith(
+      expect.any(String),
+      "INSERT OR REPLACE INTO ItemTable (key, value) VALUES (?, ?)",
+      ["cursorAuth/accessToken", newToken]
+    )
   })
 
   it("throws session expired when refresh requires logout and no access token exists", async () => {
     const ctx = makeCtx()
-    ctx.host.sqlite.query.mockImplementation((db, sql) => {
-      if (String(sql).includes("cursorAuth/accessToken")) {
+    ctx.host.sqlite.query.mockImplementation((db, sql, params) => {
+      if (params[0] === "cursorAuth/accessToken") {
         return JSON.stringify([])
       }
-      if (String(sql).includes("cursorAuth/refreshToken")) {
+      if (params[0] === "cursorAuth/refreshToken") {
         return JSON.stringify([{ value: "refresh" }])
       }
       return JSON.stringify([])
@@ -337,7 +341,7 @@ describe("cursor plugin", () => {
     })
 
     const plugin = await loadPlugin()
-    expect(() => plugin.probe(ctx)).toThrow("Session expired")
+    await expect(plugin.probe(ctx)).rejects.toThrow("Session expired")
   })
 
   it("continues with existing access token when refresh fails", async () => {
@@ -348,11 +352,11 @@ describe("cursor plugin", () => {
       .replace(/=+$/g, "")
     const accessToken = `a.${payload}.c`
 
-    ctx.host.sqlite.query.mockImplementation((db, sql) => {
-      if (String(sql).includes("cursorAuth/accessToken")) {
+    ctx.host.sqlite.query.mockImplementation((db, sql, params) => {
+      if (params[0] === "cursorAuth/accessToken") {
         return JSON.stringify([{ value: accessToken }])
       }
-      if (String(sql).includes("cursorAuth/refreshToken")) {
+      if (params[0] === "cursorAuth/refreshToken") {
         return JSON.stringify([{ value: "refresh" }])
       }
       return JSON.stringify([])
@@ -370,6 +374,20 @@ describe("cursor plugin", () => {
     })
 
     const plugin = await loadPlugin()
-    expect(() => plugin.probe(ctx)).not.toThrow()
+    await expect(plugin.probe(ctx)).resolves.toBeTruthy()
+  })
+
+  it("fetches usage, plan and credits concurrently", async () => {
+    const ctx = makeCtx()
+    ctx.host.sqlite.query.mockReturnValue(JSON.stringify([{ value: "token" }]))
+    ctx.host.http.request.mockReturnValue({
+      status: 200,
+      bodyText: JSON.stringify({ enabled: true, planUsage: { totalSpend: 0, limit: 100 } }),
+    })
+    const plugin = await loadPlugin()
+    const pending = plugin.probe(ctx)
+    // All three requests are in flight before the first one is awaited.
+    expect(ctx.host.http.requestAsync).toHaveBeenCalledTimes(3)
+    await pending
   })
 })
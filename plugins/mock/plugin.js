@@ -326,8 +326,8 @@
     }
 
     if (mode === "sqlite_throw") {
-      // Dot-commands are blocked by host -> uncaught -> host should report "probe() failed".
-      ctx.host.sqlite.query(ctx.app.appDataDir + "/does-not-matter.db", ".schema")
+      // Dot-commands are not SQL, so the query throws -> uncaught -> host should report "probe() failed".
+      ctx.host.sqlite.query(ctx.app.pluginDataDir + "/does-not-matter.db", ".schema")
       return { plan: plan, lines: hintLines }
     }
 
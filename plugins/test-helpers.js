@@ -24,10 +24,12 @@ export const makeCtx = () => {
       },
       sqlite: {
         query: vi.fn(() => "[]"),
-        exec: vi.fn(),
+        exec: vi.fn(() => 0),
       },
       http: {
         request: vi.fn(),
+        // Delegates to the `request` mock so tests can stub both with one implementation.
+        requestAsync: vi.fn((opts) => new Promise((resolve) => resolve(ctx.host.http.request(opts)))),
       },
       log: {
         trace: vi.fn(),
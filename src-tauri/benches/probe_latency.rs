@@ -0,0 +1,117 @@
+//! Entry evaluation (including the entry's imports) and full probe latency for the
+//! bundled plugins, compiling from source vs. loading cached bytecode. Full probes only
+//! run the mock plugin (pinned to `ok`) so the numbers don't depend on credentials or
+//! the network.
+//!
+//! Run with `cargo bench --bench probe_latency`.
+
+use openusage_lib::plugin_engine::bytecode::BytecodeCache;
+use openusage_lib::plugin_engine::limits::ProbeConfig;
+use openusage_lib::plugin_engine::manifest::{self, EntryType};
+use openusage_lib::plugin_engine::modules::PluginModules;
+use openusage_lib::plugin_engine::runtime::run_probe;
+use rquickjs::{Context, Runtime};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const ROUNDS: usize = 20;
+
+fn median(mut samples: Vec<Duration>) -> Duration {
+    samples.sort();
+    samples[samples.len() / 2]
+}
+
+fn main() {
+    let plugins_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../plugins");
+    let plugins = manifest::load_plugins_from_dir(&plugins_dir);
+    assert!(
+        !plugins.is_empty(),
+        "no plugins in {}",
+        plugins_dir.display()
+    );
+
+    println!("startup (runtime + context + entry and import eval):");
+    for plugin in &plugins {
+        let cache: &'static BytecodeCache = Box::leak(Box::new(BytecodeCache::new()));
+        let file_name = match plugin.manifest.entry_type {
+            EntryType::Script => plugin.manifest.entry.clone(),
+            EntryType::Module => PluginModules::new(&plugin.plugin_dir)
+                .module_name(&plugin.manifest.entry)
+                .unwrap(),
+        };
+        let startup = || {
+            let start = Instant::now();
+            let rt = Runtime::new().unwrap();
+            let modules =
+                PluginModules::new(&plugin.plugin_dir).with_cache(&plugin.manifest.id, cache);
+            rt.set_loader(modules.clone(), modules);
+            let ctx = Context::full(&rt).unwrap();
+            ctx.with(|ctx| {
+                cache
+                    .eval_entry(
+                        &ctx,
+                        &plugin.manifest.id,
+                        plugin.manifest.entry_type,
+                        &file_name,
+                        &plugin.entry_script,
+                    )
+                    .map(|_| ())
+                    .unwrap();
+            });
+            start.elapsed()
+        };
+        let (mut cold, mut cached) = (Vec::new(), Vec::new());
+        for _ in 0..ROUNDS {
+            cache.invalidate(&plugin.manifest.id);
+            cold.push(startup());
+            cached.push(startup());
+        }
+        println!(
+            "  {:<10} source {:>10.2?}  bytecode {:>10.2?}",
+            plugin.manifest.id,
+            median(cold),
+            median(cached)
+        );
+    }
+
+    let mock = plugins
+        .iter()
+        .find(|plugin| plugin.manifest.id == "mock")
+        .unwrap();
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let app_dir = std::env::temp_dir().join(format!("openusage-bench-{}", nanos));
+    let data_dir = app_dir.join("plugins_data").join("mock");
+    std::fs::create_dir_all(&data_dir).unwrap();
+    std::fs::write(
+        data_dir.join("config.json"),
+        r#"{"mode":"ok","pinned":true}"#,
+    )
+    .unwrap();
+    let config = ProbeConfig::default();
+    let probe = || {
+        let start = Instant::now();
+        let output = run_probe(mock, &app_dir, "0.0.0", &config);
+        assert!(
+            output.error.is_none(),
+            "mock probe failed: {:?}",
+            output.error
+        );
+        start.elapsed()
+    };
+    let (mut cold, mut cached) = (Vec::new(), Vec::new());
+    for _ in 0..ROUNDS {
+        BytecodeCache::shared().invalidate(&mock.manifest.id);
+        cold.push(probe());
+        cached.push(probe());
+    }
+    println!(
+        "full probe:\n  {:<10} source {:>10.2?}  bytecode {:>10.2?}",
+        "mock",
+        median(cold),
+        median(cached)
+    );
+    let _ = std::fs::remove_dir_all(&app_dir);
+}
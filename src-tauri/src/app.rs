@@ -0,0 +1,618 @@
+//! The desktop app: Tauri setup, the commands the webview calls and the tray panel,
+//! around the same plugin engine the headless binaries use.
+
+#[cfg(target_os = "macos")]
+use crate::{app_nap, webkit_config};
+use crate::plugin_engine::manifest::PluginMeta;
+use crate::plugin_engine::scheduler::{ProbeBatchStarted, ProbeResult};
+use crate::{http_api, panel, plugin_engine, tray};
+use tauri_plugin_aptabase::EventTracker;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::Emitter;
+use tauri_plugin_log::{Target, TargetKind};
+use uuid::Uuid;
+
+const SETTINGS_STORE_PATH: &str = "settings.json";
+const PROBE_TIMEOUT_KEY: &str = "probeTimeoutMs";
+const MAX_CONCURRENT_PROBES_KEY: &str = "maxConcurrentProbes";
+const ALLOW_PRIVATE_NETWORK_KEY: &str = "allowPrivateNetwork";
+const SECRET_STORE_KEY: &str = "secretStore";
+const ALERT_RULES_KEY: &str = "alertRules";
+const HTTP_API_ENABLED_KEY: &str = "httpApiEnabled";
+const HTTP_API_PORT_KEY: &str = "httpApiPort";
+const OTLP_KEY: &str = "otlp";
+const SINKS_KEY: &str = "sinks";
+
+pub struct AppState {
+    pub plugins: Vec<plugin_engine::manifest::LoadedPlugin>,
+    pub app_data_dir: PathBuf,
+    pub app_version: String,
+    pub probe_config: plugin_engine::limits::ProbeConfig,
+    pub scheduler: Arc<plugin_engine::scheduler::ProbeScheduler>,
+    pub probe_states: Arc<Mutex<plugin_engine::state::ProbeStates>>,
+    pub results: plugin_engine::pipeline::ResultPipeline,
+    /// Running loopback API, when enabled in settings.
+    pub http_api: Option<http_api::HttpApiHandle>,
+    /// Keeps `openusaged` from writing to the same data dir while the app runs. None
+    /// when another process held it at startup and results are kept in memory only.
+    _data_dir_lock: Option<plugin_engine::pipeline::DataDirLock>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProbeBatchComplete {
+    pub batch_id: String,
+    pub cancelled: bool,
+}
+
+#[tauri::command]
+fn init_panel(app_handle: tauri::AppHandle) {
+    panel::init(&app_handle).expect("Failed to initialize panel");
+}
+
+#[tauri::command]
+fn hide_panel(app_handle: tauri::AppHandle) {
+    use tauri_nspanel::ManagerExt;
+    if let Ok(panel) = app_handle.get_webview_panel("main") {
+        panel.hide();
+    }
+}
+
+#[tauri::command]
+async fn start_probe_batch(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, Mutex<AppState>>,
+    batch_id: Option<String>,
+    plugin_ids: Option<Vec<String>>,
+) -> Result<ProbeBatchStarted, String> {
+    let batch_id = batch_id
+        .and_then(|id| {
+            let trimmed = id.trim().to_string();
+            if trimmed.is_empty() {
+                None
+            } else {
+                Some(trimmed)
+            }
+        })
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let (plugins, scheduler, alerts) = {
+        let locked = state.lock().map_err(|e| e.to_string())?;
+        (
+            locked.plugins.clone(),
+            Arc::clone(&locked.scheduler),
+            Arc::clone(&locked.results.alerts),
+        )
+    };
+    // Pick up alert rule edits from the settings without a restart.
+    if let Ok(mut alerts) = alerts.lock() {
+        alerts.set_rules(load_alert_rules(&app_handle));
+    }
+
+    scheduler.start_batch(&batch_id, plugins, plugin_ids)
+}
+
+#[tauri::command]
+fn get_probe_states(
+    state: tauri::State<'_, Mutex<AppState>>,
+) -> Result<Vec<plugin_engine::state::ProbeState>, String> {
+    let probe_states = {
+        let locked = state.lock().map_err(|e| e.to_string())?;
+        Arc::clone(&locked.probe_states)
+    };
+    let snapshot = probe_states.lock().map_err(|e| e.to_string())?.snapshot();
+    Ok(snapshot)
+}
+
+/// Last successful output of each loaded plugin, for showing data before probes finish.
+#[tauri::command]
+fn get_cached_outputs(
+    state: tauri::State<'_, Mutex<AppState>>,
+) -> Result<Vec<plugin_engine::runtime::PluginOutput>, String> {
+    let (plugin_ids, output_cache) = {
+        let locked = state.lock().map_err(|e| e.to_string())?;
+        let ids: Vec<String> = locked.plugins.iter().map(|plugin| plugin.manifest.id.clone()).collect();
+        (ids, Arc::clone(&locked.results.output_cache))
+    };
+    let cache = output_cache.lock().map_err(|e| e.to_string())?;
+    Ok(cache.outputs_for(plugin_ids.iter().map(String::as_str)))
+}
+
+/// Recorded samples of one progress line, oldest first, for usage charts.
+#[tauri::command]
+fn query_usage_history(
+    state: tauri::State<'_, Mutex<AppState>>,
+    plugin_id: String,
+    label: String,
+    range: Option<plugin_engine::history::HistoryRange>,
+) -> Result<Vec<plugin_engine::history::UsageSample>, String> {
+    let usage_history = {
+        let locked = state.lock().map_err(|e| e.to_string())?;
+        Arc::clone(&locked.results.usage_history)
+    };
+    let mut history = usage_history.lock().map_err(|e| e.to_string())?;
+    Ok(history.query(plugin_id.trim(), &label, range.unwrap_or_default()))
+}
+
+/// Host calls of the most recent probes, newest first, for debugging a provider.
+#[tauri::command]
+fn query_host_audit(
+    state: tauri::State<'_, Mutex<AppState>>,
+    plugin_id: Option<String>,
+) -> Result<Vec<plugin_engine::audit::ProbeAudit>, String> {
+    let host_audit = {
+        let locked = state.lock().map_err(|e| e.to_string())?;
+        Arc::clone(&locked.results.host_audit)
+    };
+    let audit = host_audit.lock().map_err(|e| e.to_string())?;
+    Ok(audit.query(plugin_id.as_deref().map(str::trim)))
+}
+
+/// Write the HTTP calls of the most recent probes to a HAR file under `audit/` in the
+/// app data dir and return its path.
+#[tauri::command]
+fn export_host_audit_har(
+    state: tauri::State<'_, Mutex<AppState>>,
+    plugin_id: Option<String>,
+) -> Result<String, String> {
+    let (host_audit, app_data_dir) = {
+        let locked = state.lock().map_err(|e| e.to_string())?;
+        (Arc::clone(&locked.results.host_audit), locked.app_data_dir.clone())
+    };
+    let plugin_id = plugin_id.as_deref().map(str::trim);
+    let har = {
+        let audit = host_audit.lock().map_err(|e| e.to_string())?;
+        plugin_engine::audit::to_har(&audit.query(plugin_id))
+    };
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let dir = app_data_dir.join("audit");
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let label: String = plugin_id
+        .unwrap_or("all")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .collect();
+    let path = dir.join(format!("openusage-{}-{}.har", label, seconds));
+    let body = serde_json::to_string_pretty(&har).map_err(|e| e.to_string())?;
+    std::fs::write(&path, body).map_err(|e| e.to_string())?;
+    log::info!("exported host audit to {}", path.display());
+    if let Err(err) =
+        plugin_engine::audit::prune_har_exports(&dir, plugin_engine::audit::MAX_HAR_EXPORTS)
+    {
+        log::warn!("failed to prune old audit exports in {}: {}", dir.display(), err);
+    }
+    Ok(path.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+fn cancel_probe_batch(state: tauri::State<'_, Mutex<AppState>>, batch_id: String) -> Result<bool, String> {
+    let scheduler = {
+        let locked = state.lock().map_err(|e| e.to_string())?;
+        Arc::clone(&locked.scheduler)
+    };
+    Ok(scheduler.cancel(batch_id.trim()))
+}
+
+fn create_scheduler(
+    app_handle: &tauri::AppHandle,
+    app_data_dir: PathBuf,
+    app_version: String,
+    probe_config: plugin_engine::limits::ProbeConfig,
+    probe_states: Arc<Mutex<plugin_engine::state::ProbeStates>>,
+    results: plugin_engine::pipeline::ResultPipeline,
+    sinks: plugin_engine::sinks::SinkDispatcher,
+) -> plugin_engine::scheduler::ProbeScheduler {
+    use plugin_engine::scheduler::ProbeEvent;
+
+    let state_handle = app_handle.clone();
+    let publish = move |update: &dyn Fn(&mut plugin_engine::state::ProbeStates) -> plugin_engine::state::ProbeState| {
+        let Ok(mut states) = probe_states.lock() else {
+            return;
+        };
+        let _ = state_handle.emit("probe:state", update(&mut states));
+    };
+
+    let max_workers = probe_config.max_concurrent_probes;
+    let handle = app_handle.clone();
+    plugin_engine::scheduler::ProbeScheduler::new(
+        max_workers,
+        Arc::new(move |plugin: &plugin_engine::manifest::LoadedPlugin| {
+            results.probe(plugin, &app_data_dir, &app_version, &probe_config)
+        }),
+        Arc::new(move |event: ProbeEvent| match event {
+            ProbeEvent::Queued { plugin_id } => publish(&|states| states.queued(&plugin_id)),
+            ProbeEvent::Started { plugin_id } => publish(&|states| states.running(&plugin_id)),
+            ProbeEvent::Dropped { plugin_id } => publish(&|states| states.dropped(&plugin_id)),
+            ProbeEvent::Finished {
+                plugin_id,
+                output,
+                duration,
+                panicked,
+            } => publish(&|states| states.finished(&plugin_id, &output, duration, panicked)),
+            ProbeEvent::Result { batch_id, output } => {
+                let result = ProbeResult { batch_id, output };
+                sinks.dispatch(&result);
+                let _ = handle.emit("probe:result", result);
+            }
+            ProbeEvent::BatchComplete { batch_id, cancelled } => {
+                log::info!("probe batch {} complete", batch_id);
+                let _ = handle.emit("probe:batch-complete", ProbeBatchComplete { batch_id, cancelled });
+            }
+        }),
+    )
+}
+
+#[tauri::command]
+fn get_log_path(app_handle: tauri::AppHandle) -> Result<String, String> {
+    // macOS log directory: ~/Library/Logs/{bundleIdentifier}
+    let home = dirs::home_dir().ok_or("no home dir")?;
+    let bundle_id = app_handle.config().identifier.clone();
+    let log_dir = home.join("Library").join("Logs").join(&bundle_id);
+    let log_file = log_dir.join(format!("{}.log", app_handle.package_info().name));
+    Ok(log_file.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+fn list_plugins(state: tauri::State<'_, Mutex<AppState>>) -> Vec<PluginMeta> {
+    let plugins = {
+        let locked = state.lock().expect("plugin state poisoned");
+        locked.plugins.clone()
+    };
+    log::debug!("list_plugins: {} plugins", plugins.len());
+
+    plugins.iter().map(PluginMeta::from).collect()
+}
+
+/// Shows fired alerts as desktop notifications and forwards them to the UI.
+struct DesktopNotificationSink {
+    app_handle: tauri::AppHandle,
+}
+
+impl plugin_engine::alerts::NotificationSink for DesktopNotificationSink {
+    fn notify(&self, notification: &plugin_engine::alerts::AlertNotification) {
+        use tauri_plugin_notification::NotificationExt;
+
+        // Alerts fire on a probe worker while the pipeline holds its locks; showing the
+        // notification can block, so hand it to the main thread.
+        let app_handle = self.app_handle.clone();
+        let (title, body) = (notification.title.clone(), notification.body.clone());
+        let shown = self.app_handle.run_on_main_thread(move || {
+            if let Err(err) = app_handle
+                .notification()
+                .builder()
+                .title(&title)
+                .body(&body)
+                .show()
+            {
+                log::warn!("failed to show notification: {}", err);
+            }
+        });
+        if let Err(err) = shown {
+            log::warn!("failed to queue notification: {}", err);
+        }
+        let _ = self.app_handle.emit("alert:fired", notification);
+    }
+}
+
+fn load_alert_rules(app_handle: &tauri::AppHandle) -> plugin_engine::alerts::AlertRules {
+    use tauri_plugin_store::StoreExt;
+
+    let Ok(store) = app_handle.store(SETTINGS_STORE_PATH) else {
+        return plugin_engine::alerts::AlertRules::default();
+    };
+    match store.get(ALERT_RULES_KEY) {
+        Some(value) => serde_json::from_value(value).unwrap_or_else(|err| {
+            log::warn!("ignoring invalid {}: {}", ALERT_RULES_KEY, err);
+            plugin_engine::alerts::AlertRules::default()
+        }),
+        None => plugin_engine::alerts::AlertRules::default(),
+    }
+}
+
+/// Serves the app's plugins and results over the HTTP API. Refreshes go through the
+/// shared scheduler, so their results also reach the panel.
+struct AppUsageSource {
+    plugins: Vec<plugin_engine::manifest::LoadedPlugin>,
+    scheduler: Arc<plugin_engine::scheduler::ProbeScheduler>,
+    results: plugin_engine::pipeline::ResultPipeline,
+}
+
+impl http_api::UsageSource for AppUsageSource {
+    fn plugins(&self) -> Vec<PluginMeta> {
+        self.plugins.iter().map(PluginMeta::from).collect()
+    }
+
+    fn usage(&self) -> Vec<plugin_engine::runtime::PluginOutput> {
+        self.results
+            .current(self.plugins.iter().map(|plugin| plugin.manifest.id.as_str()))
+    }
+
+    fn metrics(&self) -> String {
+        self.results
+            .metrics(self.plugins.iter().map(|plugin| plugin.manifest.id.as_str()))
+    }
+
+    fn refresh(&self, plugin_ids: Option<Vec<String>>) -> Result<ProbeBatchStarted, String> {
+        // The panel shows results of `api-` batches even though it didn't start them.
+        let batch_id = format!("api-{}", Uuid::new_v4());
+        self.scheduler.start_batch(&batch_id, self.plugins.clone(), plugin_ids)
+    }
+
+    fn wait(&self, batch_id: &str, timeout: Duration) -> bool {
+        self.scheduler.wait_batch(batch_id, timeout)
+    }
+}
+
+fn load_http_api_config(app_handle: &tauri::AppHandle) -> http_api::HttpApiConfig {
+    use tauri_plugin_store::StoreExt;
+
+    let mut config = http_api::HttpApiConfig::default();
+    if let Ok(store) = app_handle.store(SETTINGS_STORE_PATH) {
+        config.enabled = store
+            .get(HTTP_API_ENABLED_KEY)
+            .and_then(|value| value.as_bool())
+            .unwrap_or(false);
+        if let Some(port) = store
+            .get(HTTP_API_PORT_KEY)
+            .and_then(|value| value.as_u64())
+            .and_then(|port| u16::try_from(port).ok())
+            .filter(|port| *port > 0)
+        {
+            config.port = port;
+        }
+    }
+    config
+}
+
+fn start_http_api(
+    config: &http_api::HttpApiConfig,
+    app_data_dir: &std::path::Path,
+    source: AppUsageSource,
+) -> Option<http_api::HttpApiHandle> {
+    if !config.enabled {
+        return None;
+    }
+    let started = http_api::resolve_token(config, app_data_dir)
+        .and_then(|token| http_api::start(config.port, token, Arc::new(source)));
+    match started {
+        Ok(handle) => Some(handle),
+        Err(err) => {
+            log::error!("failed to start http api on port {}: {}", config.port, err);
+            None
+        }
+    }
+}
+
+fn load_otlp_config(app_handle: &tauri::AppHandle) -> plugin_engine::telemetry::OtlpConfig {
+    use tauri_plugin_store::StoreExt;
+
+    let Ok(store) = app_handle.store(SETTINGS_STORE_PATH) else {
+        return plugin_engine::telemetry::OtlpConfig::default();
+    };
+    match store.get(OTLP_KEY) {
+        Some(value) => serde_json::from_value(value).unwrap_or_else(|err| {
+            log::warn!("ignoring invalid {}: {}", OTLP_KEY, err);
+            plugin_engine::telemetry::OtlpConfig::default()
+        }),
+        None => plugin_engine::telemetry::OtlpConfig::default(),
+    }
+}
+
+fn load_sinks_config(app_handle: &tauri::AppHandle) -> plugin_engine::sinks::SinksConfig {
+    use tauri_plugin_store::StoreExt;
+
+    let Ok(store) = app_handle.store(SETTINGS_STORE_PATH) else {
+        return plugin_engine::sinks::SinksConfig::default();
+    };
+    match store.get(SINKS_KEY) {
+        Some(value) => serde_json::from_value(value).unwrap_or_else(|err| {
+            log::warn!("ignoring invalid {}: {}", SINKS_KEY, err);
+            plugin_engine::sinks::SinksConfig::default()
+        }),
+        None => plugin_engine::sinks::SinksConfig::default(),
+    }
+}
+
+fn load_probe_config(app_handle: &tauri::AppHandle) -> plugin_engine::limits::ProbeConfig {
+    use tauri_plugin_store::StoreExt;
+
+    let mut config = plugin_engine::limits::ProbeConfig::default();
+    match app_handle.store(SETTINGS_STORE_PATH) {
+        Ok(store) => {
+            if let Some(timeout_ms) = store
+                .get(PROBE_TIMEOUT_KEY)
+                .and_then(|value| value.as_u64())
+                .filter(|ms| *ms > 0)
+            {
+                config.default_timeout_ms = timeout_ms;
+            }
+            if let Some(max) = store
+                .get(MAX_CONCURRENT_PROBES_KEY)
+                .and_then(|value| value.as_u64())
+                .filter(|max| *max > 0)
+            {
+                config.max_concurrent_probes = max as usize;
+            }
+            config.allow_private_network = store
+                .get(ALLOW_PRIVATE_NETWORK_KEY)
+                .and_then(|value| value.as_bool())
+                .unwrap_or(false);
+            if config.allow_private_network {
+                log::warn!("plugins may reach private network addresses ({})", ALLOW_PRIVATE_NETWORK_KEY);
+            }
+            if let Some(value) = store.get(SECRET_STORE_KEY) {
+                match serde_json::from_value(value) {
+                    Ok(kind) => config.secret_store = kind,
+                    Err(err) => log::warn!("invalid {} setting, using auto: {}", SECRET_STORE_KEY, err),
+                }
+            }
+        }
+        Err(err) => log::warn!("failed to open settings store: {}", err),
+    }
+    config
+}
+
+/// Tell the user why usage history, forecasts and alert state won't be kept.
+fn notify_read_only(app_handle: &tauri::AppHandle) {
+    use tauri_plugin_notification::NotificationExt;
+
+    if let Err(err) = app_handle
+        .notification()
+        .builder()
+        .title("OpenUsage can't save results")
+        .body("Its data dir is in use by another OpenUsage app or openusaged. Quit it and restart OpenUsage to keep usage history.")
+        .show()
+    {
+        log::warn!("failed to show notification: {}", err);
+    }
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
+    let _guard = runtime.enter();
+    // Share one runtime between Tauri and the plugin host API's async calls.
+    tauri::async_runtime::set(runtime.handle().clone());
+
+    tauri::Builder::default()
+        .plugin(tauri_plugin_aptabase::Builder::new("A-US-6435241436").build())
+        .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_store::Builder::default().build())
+        .plugin(tauri_nspanel::init())
+        .plugin(
+            tauri_plugin_log::Builder::new()
+                .targets([
+                    Target::new(TargetKind::Stdout),
+                    Target::new(TargetKind::LogDir { file_name: None }),
+                ])
+                .max_file_size(10_000_000) // 10 MB
+                .level(log::LevelFilter::Trace) // Allow all levels; runtime filter via tray menu
+                .level_for("hyper", log::LevelFilter::Warn)
+                .level_for("reqwest", log::LevelFilter::Warn)
+                .level_for("tao", log::LevelFilter::Info)
+                .level_for("tauri_plugin_updater", log::LevelFilter::Info)
+                .build(),
+        )
+        .plugin(tauri_plugin_process::init())
+        .plugin(tauri_plugin_notification::init())
+        .invoke_handler(tauri::generate_handler![
+            init_panel,
+            hide_panel,
+            start_probe_batch,
+            cancel_probe_batch,
+            get_probe_states,
+            get_cached_outputs,
+            query_usage_history,
+            query_host_audit,
+            export_host_audit_har,
+            list_plugins,
+            get_log_path
+        ])
+        .setup(|app| {
+            #[cfg(target_os = "macos")]
+            app.set_activation_policy(tauri::ActivationPolicy::Accessory);
+
+            #[cfg(target_os = "macos")]
+            {
+                app_nap::disable_app_nap();
+                webkit_config::disable_webview_suspension(app.handle());
+            }
+
+            use tauri::Manager;
+
+            let version = app.package_info().version.to_string();
+            log::info!("OpenUsage v{} starting", version);
+
+            let _ = app.track_event("app_started", None);
+
+            let app_data_dir = app.path().app_data_dir().expect("no app data dir");
+            let resource_dir = app.path().resource_dir().expect("no resource dir");
+            log::debug!("app_data_dir: {:?}", app_data_dir);
+
+            let (_, plugins) = plugin_engine::initialize_plugins(&app_data_dir, &resource_dir);
+            let probe_config = load_probe_config(app.handle());
+            log::debug!(
+                "probe timeout: {}ms, max concurrent probes: {}",
+                probe_config.default_timeout_ms,
+                probe_config.max_concurrent_probes
+            );
+            let app_version = app.package_info().version.to_string();
+            let probe_states = Arc::new(Mutex::new(plugin_engine::state::ProbeStates::new(
+                plugins.iter().map(|plugin| plugin.manifest.id.clone()),
+            )));
+            let data_dir_lock = match plugin_engine::pipeline::DataDirLock::acquire(&app_data_dir) {
+                Ok(lock) => Some(lock),
+                Err(message) => {
+                    log::error!("{}; results won't be saved this session", message);
+                    notify_read_only(app.handle());
+                    None
+                }
+            };
+            let alert_rules = load_alert_rules(app.handle());
+            let sink = Arc::new(DesktopNotificationSink {
+                app_handle: app.handle().clone(),
+            });
+            let mut results = if data_dir_lock.is_some() {
+                plugin_engine::pipeline::ResultPipeline::open(&app_data_dir, alert_rules, sink)
+            } else {
+                plugin_engine::pipeline::ResultPipeline::open_read_only(
+                    &app_data_dir,
+                    alert_rules,
+                    sink,
+                )
+            };
+            let otlp_config = load_otlp_config(app.handle());
+            if otlp_config.enabled {
+                match plugin_engine::telemetry::OtlpExporter::start(&otlp_config) {
+                    Ok(exporter) => results = results.with_telemetry(exporter),
+                    Err(err) => log::error!("failed to start otlp export: {}", err),
+                }
+            }
+            let scheduler = create_scheduler(
+                app.handle(),
+                app_data_dir.clone(),
+                app_version.clone(),
+                probe_config.clone(),
+                Arc::clone(&probe_states),
+                results.clone(),
+                plugin_engine::sinks::SinkDispatcher::from_config(&load_sinks_config(app.handle())),
+            );
+            let scheduler = Arc::new(scheduler);
+            let http_api = start_http_api(
+                &load_http_api_config(app.handle()),
+                &app_data_dir,
+                AppUsageSource {
+                    plugins: plugins.clone(),
+                    scheduler: Arc::clone(&scheduler),
+                    results: results.clone(),
+                },
+            );
+            app.manage(Mutex::new(AppState {
+                plugins,
+                app_data_dir,
+                app_version,
+                probe_config,
+                scheduler,
+                probe_states,
+                results,
+                http_api,
+                _data_dir_lock: data_dir_lock,
+            }));
+
+            tray::create(app.handle())?;
+
+            app.handle().plugin(tauri_plugin_updater::Builder::new().build())?;
+
+            Ok(())
+        })
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|_, _| {});
+}
@@ -0,0 +1,522 @@
+//! Headless `openusage-cli`: loads plugins like the app does, probes them without a
+//! webview and prints the outputs.
+
+use crate::plugin_engine;
+use crate::plugin_engine::limits::ProbeConfig;
+use crate::plugin_engine::manifest::LoadedPlugin;
+use crate::plugin_engine::runtime::{MetricLine, PluginOutput, ProgressFormat};
+use crate::plugin_engine::scheduler::{ProbeEvent, ProbeScheduler};
+use crate::plugin_engine::secrets::SecretStoreKind;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+
+/// Bundle identifier from tauri.conf.json; Tauri puts the app data dir under it.
+pub const APP_IDENTIFIER: &str = "com.sunstory.openusage";
+
+pub const EXIT_OK: i32 = 0;
+/// At least one probe returned an error.
+pub const EXIT_PROBE_ERROR: i32 = 1;
+/// Bad arguments or nothing to probe.
+pub const EXIT_USAGE: i32 = 2;
+
+const USAGE: &str = "\
+Usage: openusage-cli probe [options]
+       openusage-cli cached [options]
+
+Commands:
+  probe   Run the plugins now and print their outputs
+  cached  Print the last good outputs saved by the app or openusaged
+
+Options:
+  --plugin <id>        Only this plugin (repeatable)
+  --json               Print outputs as JSON
+  --table              Print outputs as a table (default)
+  --data-dir <dir>     App data dir (default: the app's)
+  --plugins-dir <dir>  Load plugins from this directory (probe)
+  --timeout-ms <ms>    Per-probe timeout (probe)
+  --allow-private-network
+                       Let plugins reach loopback and private addresses (probe)
+  --secret-store <kind>
+                       auto, keychain, secret-service or file (probe)
+  -h, --help           Show this help
+
+probe exits with 1 if any probe fails.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Table,
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProbeArgs {
+    pub plugin_ids: Vec<String>,
+    pub format: OutputFormat,
+    pub data_dir: Option<PathBuf>,
+    pub plugins_dir: Option<PathBuf>,
+    pub timeout_ms: Option<u64>,
+    pub allow_private_network: bool,
+    pub secret_store: SecretStoreKind,
+}
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Probe(ProbeArgs),
+    Cached(ProbeArgs),
+    Help,
+}
+
+/// Run the CLI with the arguments after the program name and return the exit code.
+pub fn run(args: Vec<String>) -> i32 {
+    let args = match parse_args(&args) {
+        Ok(Command::Probe(args)) => args,
+        Ok(Command::Cached(args)) => return print_cached(&args),
+        Ok(Command::Help) => {
+            println!("{}", USAGE);
+            return EXIT_OK;
+        }
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+
+    let app_data_dir = args.data_dir.clone().unwrap_or_else(default_app_data_dir);
+    let plugins = match &args.plugins_dir {
+        Some(dir) => plugin_engine::manifest::load_plugins_from_dir(dir),
+        None => plugin_engine::initialize_plugins(&app_data_dir, &default_resource_dir()).1,
+    };
+    let plugins = match select_plugins(plugins, &args.plugin_ids) {
+        Ok(plugins) => plugins,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            return EXIT_USAGE;
+        }
+    };
+
+    let mut config = ProbeConfig::default();
+    if let Some(timeout_ms) = args.timeout_ms {
+        config.default_timeout_ms = timeout_ms;
+    }
+    config.allow_private_network = args.allow_private_network;
+    config.secret_store = args.secret_store;
+
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            eprintln!("error: failed to start runtime: {}", err);
+            return EXIT_USAGE;
+        }
+    };
+    let _guard = runtime.enter();
+    let mut outputs = probe_all(plugins, app_data_dir.clone(), config);
+    // Fit to the samples the app or openusaged keeps, but leave saving them to it.
+    let mut samples = plugin_engine::forecast::ForecastSamples::load(&app_data_dir);
+    let now_ms = plugin_engine::history::now_ms();
+    for output in &mut outputs {
+        plugin_engine::forecast::attach_forecasts(output, &mut samples, now_ms);
+    }
+    print_outputs(&outputs, args.format);
+    exit_code(&outputs)
+}
+
+fn print_cached(args: &ProbeArgs) -> i32 {
+    let app_data_dir = args.data_dir.clone().unwrap_or_else(default_app_data_dir);
+    let cache = plugin_engine::output_cache::OutputCache::load(&app_data_dir);
+    let outputs = if args.plugin_ids.is_empty() {
+        cache.all()
+    } else {
+        cache.outputs_for(args.plugin_ids.iter().map(String::as_str))
+    };
+    if outputs.is_empty() {
+        eprintln!("error: no cached outputs in {}", app_data_dir.display());
+        return EXIT_USAGE;
+    }
+    print_outputs(&outputs, args.format);
+    EXIT_OK
+}
+
+fn print_outputs(outputs: &[PluginOutput], format: OutputFormat) {
+    let rendered = match format {
+        OutputFormat::Json => match serde_json::to_string_pretty(&outputs) {
+            Ok(json) => json + "\n",
+            Err(err) => {
+                eprintln!("error: failed to serialize outputs: {}", err);
+                String::new()
+            }
+        },
+        OutputFormat::Table => format_table(outputs),
+    };
+    // A closed pipe (e.g. `| head`) is not worth a panic.
+    let _ = std::io::stdout().lock().write_all(rendered.as_bytes());
+}
+
+/// Where the app keeps its data, so the CLI shares plugin data and caches with it.
+pub fn default_app_data_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join(APP_IDENTIFIER)
+}
+
+/// Best guess at the app bundle's resource dir, relative to the running binary.
+pub fn default_resource_dir() -> PathBuf {
+    let exe_dir = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+        .unwrap_or_default();
+    if cfg!(target_os = "macos") {
+        exe_dir.join("../Resources")
+    } else {
+        exe_dir
+    }
+}
+
+/// Probe every plugin through the scheduler and return the outputs in plugin order.
+pub fn probe_all(
+    plugins: Vec<LoadedPlugin>,
+    app_data_dir: PathBuf,
+    config: ProbeConfig,
+) -> Vec<PluginOutput> {
+    let order: Vec<String> = plugins
+        .iter()
+        .map(|plugin| plugin.manifest.id.clone())
+        .collect();
+    let (tx, rx) = mpsc::channel();
+    let scheduler = ProbeScheduler::new(
+        config.max_concurrent_probes,
+        Arc::new(move |plugin: &LoadedPlugin| {
+            plugin_engine::runtime::run_probe(
+                plugin,
+                &app_data_dir,
+                env!("CARGO_PKG_VERSION"),
+                &config,
+            )
+        }),
+        Arc::new(move |event: ProbeEvent| match event {
+            ProbeEvent::Result { output, .. } => {
+                let _ = tx.send(Some(output));
+            }
+            ProbeEvent::BatchComplete { .. } => {
+                let _ = tx.send(None);
+            }
+            _ => {}
+        }),
+    );
+    if let Err(err) = scheduler.submit("cli", plugins) {
+        eprintln!("error: {}", err);
+        return Vec::new();
+    }
+
+    let mut outputs: Vec<PluginOutput> = rx.iter().map_while(|output| output).collect();
+    outputs.sort_by_key(|output| order.iter().position(|id| *id == output.provider_id));
+    outputs
+}
+
+pub fn exit_code(outputs: &[PluginOutput]) -> i32 {
+    if outputs.iter().any(|output| output.error.is_some()) {
+        EXIT_PROBE_ERROR
+    } else {
+        EXIT_OK
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Command, String> {
+    let mut iter = args.iter();
+    let cached = match iter.next().map(String::as_str) {
+        Some("probe") => false,
+        Some("cached") => true,
+        Some("-h") | Some("--help") | Some("help") => return Ok(Command::Help),
+        Some(other) => return Err(format!("unknown command: {}", other)),
+        None => return Err("missing command".to_string()),
+    };
+
+    let mut probe = ProbeArgs {
+        plugin_ids: Vec::new(),
+        format: OutputFormat::Table,
+        data_dir: None,
+        plugins_dir: None,
+        timeout_ms: None,
+        allow_private_network: false,
+        secret_store: SecretStoreKind::Auto,
+    };
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| {
+            iter.next()
+                .cloned()
+                .ok_or_else(|| format!("{} needs a value", name))
+        };
+        match arg.as_str() {
+            "--plugin" => probe.plugin_ids.push(value("--plugin")?),
+            "--json" => probe.format = OutputFormat::Json,
+            "--table" => probe.format = OutputFormat::Table,
+            "--data-dir" => probe.data_dir = Some(PathBuf::from(value("--data-dir")?)),
+            "--plugins-dir" => probe.plugins_dir = Some(PathBuf::from(value("--plugins-dir")?)),
+            "--timeout-ms" => {
+                let raw = value("--timeout-ms")?;
+                let ms = raw
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|ms| *ms > 0)
+                    .ok_or_else(|| format!("invalid --timeout-ms: {}", raw))?;
+                probe.timeout_ms = Some(ms);
+            }
+            "--allow-private-network" => probe.allow_private_network = true,
+            "--secret-store" => probe.secret_store = value("--secret-store")?.parse()?,
+            "-h" | "--help" => return Ok(Command::Help),
+            other => return Err(format!("unknown option: {}", other)),
+        }
+    }
+    Ok(if cached {
+        Command::Cached(probe)
+    } else {
+        Command::Probe(probe)
+    })
+}
+
+fn select_plugins(plugins: Vec<LoadedPlugin>, ids: &[String]) -> Result<Vec<LoadedPlugin>, String> {
+    if plugins.is_empty() {
+        return Err("no plugins found".to_string());
+    }
+    if ids.is_empty() {
+        return Ok(plugins);
+    }
+    if let Some(unknown) = ids
+        .iter()
+        .find(|id| !plugins.iter().any(|plugin| plugin.manifest.id == **id))
+    {
+        let known: Vec<&str> = plugins
+            .iter()
+            .map(|plugin| plugin.manifest.id.as_str())
+            .collect();
+        return Err(format!(
+            "unknown plugin {} (available: {})",
+            unknown,
+            known.join(", ")
+        ));
+    }
+    Ok(plugins
+        .into_iter()
+        .filter(|plugin| ids.contains(&plugin.manifest.id))
+        .collect())
+}
+
+/// One row per line (or per error), with aligned columns.
+fn format_table(outputs: &[PluginOutput]) -> String {
+    let mut rows: Vec<[String; 4]> = vec![[
+        "PLUGIN".to_string(),
+        "METRIC".to_string(),
+        "VALUE".to_string(),
+        "RESETS".to_string(),
+    ]];
+    for output in outputs {
+        let plugin = output.provider_id.clone();
+        if let Some(error) = &output.error {
+            rows.push([
+                plugin.clone(),
+                "error".to_string(),
+                error.to_string(),
+                String::new(),
+            ]);
+        }
+        for line in &output.lines {
+            rows.push(match line {
+                MetricLine::Text { label, value, .. } => {
+                    [plugin.clone(), label.clone(), value.clone(), String::new()]
+                }
+                MetricLine::Badge { label, text, .. } => {
+                    [plugin.clone(), label.clone(), text.clone(), String::new()]
+                }
+                MetricLine::Progress {
+                    label,
+                    used,
+                    limit,
+                    format,
+                    resets_at,
+                    ..
+                } => [
+                    plugin.clone(),
+                    label.clone(),
+                    format_progress(*used, *limit, format),
+                    resets_at.clone().unwrap_or_default(),
+                ],
+            });
+        }
+    }
+
+    let mut widths = [0usize; 4];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut out = String::new();
+    for row in &rows {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+fn format_progress(used: f64, limit: f64, format: &ProgressFormat) -> String {
+    match format {
+        ProgressFormat::Percent => format!("{}%", trim_number(used)),
+        ProgressFormat::Dollars => format!("${:.2} / ${:.2}", used, limit),
+        ProgressFormat::Count { suffix } => {
+            format!("{} / {} {}", trim_number(used), trim_number(limit), suffix)
+        }
+    }
+}
+
+fn trim_number(value: f64) -> String {
+    let rounded = format!("{:.1}", value);
+    rounded
+        .strip_suffix(".0")
+        .map(str::to_string)
+        .unwrap_or(rounded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::error::{ProbeError, ProbeErrorKind};
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn output(id: &str, lines: Vec<MetricLine>, error: Option<ProbeError>) -> PluginOutput {
+        PluginOutput {
+            provider_id: id.to_string(),
+            display_name: id.to_string(),
+            plan: None,
+            lines,
+            icon_url: String::new(),
+            error,
+            fetched_at: None,
+            stale: false,
+        }
+    }
+
+    #[test]
+    fn parses_probe_options() {
+        let parsed = parse_args(&args(&[
+            "probe",
+            "--plugin",
+            "claude",
+            "--plugin",
+            "codex",
+            "--json",
+            "--timeout-ms",
+            "5000",
+            "--allow-private-network",
+            "--secret-store",
+            "file",
+        ]))
+        .unwrap();
+        assert_eq!(
+            parsed,
+            Command::Probe(ProbeArgs {
+                plugin_ids: vec!["claude".to_string(), "codex".to_string()],
+                format: OutputFormat::Json,
+                data_dir: None,
+                plugins_dir: None,
+                timeout_ms: Some(5000),
+                allow_private_network: true,
+                secret_store: SecretStoreKind::File,
+            })
+        );
+        match parse_args(&args(&["cached", "--data-dir", "/tmp/ou"])).unwrap() {
+            Command::Cached(cached) => assert_eq!(cached.data_dir, Some(PathBuf::from("/tmp/ou"))),
+            other => panic!("expected cached, got {:?}", other),
+        }
+        assert_eq!(parse_args(&args(&["--help"])).unwrap(), Command::Help);
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        assert!(parse_args(&args(&[])).is_err());
+        assert!(parse_args(&args(&["status"])).is_err());
+        assert!(parse_args(&args(&["probe", "--plugin"])).is_err());
+        assert!(parse_args(&args(&["probe", "--timeout-ms", "0"])).is_err());
+        assert!(parse_args(&args(&["probe", "--verbose"])).is_err());
+        assert!(parse_args(&args(&["probe", "--secret-store", "vault"])).is_err());
+    }
+
+    #[test]
+    fn formats_table_rows_and_errors() {
+        let outputs = vec![
+            output(
+                "claude",
+                vec![
+                    MetricLine::Progress {
+                        label: "Session".to_string(),
+                        used: 42.0,
+                        limit: 100.0,
+                        format: ProgressFormat::Percent,
+                        resets_at: Some("2026-02-09T00:00:00Z".to_string()),
+                        period_duration_ms: None,
+                        color: None,
+                        forecast: None,
+                    },
+                    MetricLine::Text {
+                        label: "Plan".to_string(),
+                        value: "Max".to_string(),
+                        color: None,
+                        subtitle: None,
+                    },
+                ],
+                None,
+            ),
+            output(
+                "cursor",
+                vec![],
+                Some(ProbeError::new(ProbeErrorKind::Auth, "not logged in")),
+            ),
+        ];
+        let table = format_table(&outputs);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], "PLUGIN  METRIC   VALUE                RESETS");
+        assert_eq!(
+            lines[1],
+            "claude  Session  42%                  2026-02-09T00:00:00Z"
+        );
+        assert_eq!(lines[2], "claude  Plan     Max");
+        assert!(lines[3].starts_with("cursor  error    "));
+        assert!(lines[3].contains("not logged in"));
+    }
+
+    #[test]
+    fn formats_progress_values() {
+        assert_eq!(
+            format_progress(12.5, 100.0, &ProgressFormat::Dollars),
+            "$12.50 / $100.00"
+        );
+        let count = ProgressFormat::Count {
+            suffix: "requests".to_string(),
+        };
+        assert_eq!(format_progress(120.0, 500.0, &count), "120 / 500 requests");
+        assert_eq!(
+            format_progress(33.33, 100.0, &ProgressFormat::Percent),
+            "33.3%"
+        );
+    }
+
+    #[test]
+    fn exit_code_reflects_probe_errors() {
+        let ok = output("claude", vec![], None);
+        let failed = output(
+            "codex",
+            vec![],
+            Some(ProbeError::new(ProbeErrorKind::Network, "offline")),
+        );
+        assert_eq!(exit_code(&[ok, failed]), EXIT_PROBE_ERROR);
+        assert_eq!(exit_code(&[output("claude", vec![], None)]), EXIT_OK);
+    }
+}
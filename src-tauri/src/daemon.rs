@@ -0,0 +1,915 @@
+//! `openusaged`: probes plugins on its own interval without a webview, for machines
+//! without a desktop. Results go through the same pipeline as the app (last-good
+//! cache, usage history, forecasts, alerts) in the data dir, where `openusage-cli
+//! cached --data-dir` can read them. The data dir is locked while the daemon runs, so
+//! by default it is a dir of its own rather than the app's. With `httpApi.enabled` the
+//! same loopback HTTP API as the app is served. With `--mcp` it speaks the Model
+//! Context Protocol on stdin/stdout instead of probing on an interval, for coding agents
+//! that launch it as a local MCP server: it serves the results cached in the data dir
+//! and holds the lock only while a refresh probes, so the app or a daemon can own the
+//! dir meanwhile.
+
+use crate::cli;
+use crate::http_api::{self, HttpApiConfig, UsageSource};
+use crate::mcp::McpServer;
+use crate::plugin_engine;
+use crate::plugin_engine::alerts::{AlertNotification, AlertRules, NotificationSink};
+use crate::plugin_engine::limits::ProbeConfig;
+use crate::plugin_engine::manifest::{LoadedPlugin, PluginMeta};
+use crate::plugin_engine::metrics;
+use crate::plugin_engine::output_cache::OutputCache;
+use crate::plugin_engine::pipeline::{DataDirLock, ResultPipeline};
+use crate::plugin_engine::runtime::PluginOutput;
+use crate::plugin_engine::scheduler::{
+    ProbeBatchStarted, ProbeEvent, ProbeFn, ProbeResult, ProbeScheduler,
+};
+use crate::plugin_engine::secrets::SecretStoreKind;
+use crate::plugin_engine::sinks::{SinkDispatcher, SinksConfig};
+use crate::plugin_engine::telemetry::{OtlpConfig, OtlpExporter};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub const CONFIG_FILE: &str = "openusaged.json";
+pub const DEFAULT_INTERVAL_SECS: u64 = 300;
+/// Shorter intervals would hammer provider APIs.
+pub const MIN_INTERVAL_SECS: u64 = 30;
+const ROUND_BATCH_PREFIX: &str = "round-";
+
+const USAGE: &str = "\
+Usage: openusaged [options]
+
+Options:
+  --config <path>  Config file (default: <config dir>/openusage/openusaged.json)
+  --once           Probe once and exit; exits with 1 if any probe fails
+  --mcp            Serve MCP on stdin/stdout instead of probing on an interval;
+                   exits when stdin closes
+  -h, --help       Show this help";
+
+/// Daemon settings, read from a JSON file. Every field is optional.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct DaemonConfig {
+    /// Seconds between the starts of two probe rounds.
+    pub interval_secs: u64,
+    /// Plugin ids to probe; empty probes every plugin.
+    pub plugins: Vec<String>,
+    /// Load plugins from here instead of the app's plugin dirs.
+    pub plugins_dir: Option<PathBuf>,
+    /// Where results, history and plugin data live; defaults to [`default_data_dir`].
+    pub data_dir: Option<PathBuf>,
+    pub probe_timeout_ms: Option<u64>,
+    pub max_concurrent_probes: Option<usize>,
+    /// Let plugins reach loopback and private addresses; for plugin development.
+    pub allow_private_network: bool,
+    /// Where plugin secrets are kept: `auto`, `keychain`, `secret-service` or `file`.
+    pub secret_store: SecretStoreKind,
+    pub alert_rules: AlertRules,
+    /// `error`, `warn`, `info`, `debug` or `trace`.
+    pub log_level: String,
+    pub http_api: HttpApiConfig,
+    pub otlp: OtlpConfig,
+    pub sinks: SinksConfig,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: DEFAULT_INTERVAL_SECS,
+            plugins: Vec::new(),
+            plugins_dir: None,
+            data_dir: None,
+            probe_timeout_ms: None,
+            max_concurrent_probes: None,
+            allow_private_network: false,
+            secret_store: SecretStoreKind::Auto,
+            alert_rules: AlertRules::default(),
+            log_level: "info".to_string(),
+            http_api: HttpApiConfig::default(),
+            otlp: OtlpConfig::default(),
+            sinks: SinksConfig::default(),
+        }
+    }
+}
+
+impl DaemonConfig {
+    /// Read the config file; a missing file means all defaults.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let config: Self = match std::fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map_err(|err| format!("invalid config {}: {}", path.display(), err))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(err) => return Err(format!("failed to read config {}: {}", path.display(), err)),
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.interval_secs < MIN_INTERVAL_SECS {
+            return Err(format!(
+                "intervalSecs must be at least {}",
+                MIN_INTERVAL_SECS
+            ));
+        }
+        if self.probe_timeout_ms == Some(0) || self.max_concurrent_probes == Some(0) {
+            return Err("probeTimeoutMs and maxConcurrentProbes must be positive".to_string());
+        }
+        self.log_filter()?;
+        Ok(())
+    }
+
+    fn log_filter(&self) -> Result<log::LevelFilter, String> {
+        self.log_level
+            .parse()
+            .map_err(|_| format!("invalid logLevel: {}", self.log_level))
+    }
+
+    pub fn probe_config(&self) -> ProbeConfig {
+        let mut config = ProbeConfig::default();
+        if let Some(timeout_ms) = self.probe_timeout_ms {
+            config.default_timeout_ms = timeout_ms;
+        }
+        if let Some(max) = self.max_concurrent_probes {
+            config.max_concurrent_probes = max;
+        }
+        config.allow_private_network = self.allow_private_network;
+        config.secret_store = self.secret_store;
+        config
+    }
+
+    pub fn data_dir(&self) -> PathBuf {
+        self.data_dir.clone().unwrap_or_else(default_data_dir)
+    }
+}
+
+/// `openusaged` next to the app's data dir, so the daemon doesn't lock the app out of
+/// its own dir when both run on one machine.
+pub fn default_data_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("openusaged")
+}
+
+pub fn default_config_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("openusage")
+        .join(CONFIG_FILE)
+}
+
+/// Run the daemon with the arguments after the program name and return the exit code.
+pub fn run(args: Vec<String>) -> i32 {
+    let mut config_path = None;
+    let mut once = false;
+    let mut mcp = false;
+    let mut iter = args.into_iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--config" => match iter.next() {
+                Some(path) => config_path = Some(PathBuf::from(path)),
+                None => {
+                    eprintln!("error: --config needs a value\n\n{}", USAGE);
+                    return cli::EXIT_USAGE;
+                }
+            },
+            "--once" => once = true,
+            "--mcp" => mcp = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return cli::EXIT_OK;
+            }
+            other => {
+                eprintln!("error: unknown option: {}\n\n{}", other, USAGE);
+                return cli::EXIT_USAGE;
+            }
+        }
+    }
+
+    if once && mcp {
+        eprintln!("error: --once and --mcp can't be combined\n\n{}", USAGE);
+        return cli::EXIT_USAGE;
+    }
+
+    let config_path = config_path.unwrap_or_else(default_config_path);
+    let config = match DaemonConfig::load(&config_path) {
+        Ok(config) => config,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            return cli::EXIT_USAGE;
+        }
+    };
+    init_logging(config.log_filter().unwrap_or(log::LevelFilter::Info));
+    log::info!(
+        "openusaged v{} starting, config {}",
+        env!("CARGO_PKG_VERSION"),
+        config_path.display()
+    );
+
+    let data_dir = config.data_dir();
+    if mcp {
+        return run_mcp(&config, data_dir);
+    }
+    // Held until exit, so a second writer can't clobber this one's stores.
+    let _lock = match DataDirLock::acquire(&data_dir) {
+        Ok(lock) => lock,
+        Err(message) => {
+            log::error!("{}", message);
+            return cli::EXIT_USAGE;
+        }
+    };
+    let plugins = match &config.plugins_dir {
+        Some(dir) => plugin_engine::manifest::load_plugins_from_dir(dir),
+        None => plugin_engine::initialize_plugins(&data_dir, &cli::default_resource_dir()).1,
+    };
+    let plugins = select_plugins(plugins, &config.plugins);
+    if plugins.is_empty() {
+        log::error!("no plugins to probe");
+        return cli::EXIT_USAGE;
+    }
+    log::info!(
+        "probing {} every {}s",
+        plugin_list(&plugins),
+        config.interval_secs
+    );
+
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            log::error!("failed to start runtime: {}", err);
+            return cli::EXIT_USAGE;
+        }
+    };
+    let _guard = runtime.enter();
+
+    let mut results =
+        ResultPipeline::open(&data_dir, config.alert_rules.clone(), Arc::new(LogSink));
+    if config.otlp.enabled {
+        match OtlpExporter::start(&config.otlp) {
+            Ok(exporter) => results = results.with_telemetry(exporter),
+            Err(err) => {
+                log::error!("failed to start otlp export: {}", err);
+                return cli::EXIT_USAGE;
+            }
+        }
+    }
+    let probe_config = config.probe_config();
+    let max_workers = probe_config.max_concurrent_probes;
+    let probe_results = results.clone();
+    let probe_dir = data_dir.clone();
+    let probe: ProbeFn = Arc::new(move |plugin: &LoadedPlugin| {
+        probe_results.probe(plugin, &probe_dir, env!("CARGO_PKG_VERSION"), &probe_config)
+    });
+    let mut daemon = Daemon::new(
+        plugins,
+        max_workers,
+        probe,
+        SinkDispatcher::from_config(&config.sinks),
+    );
+    let source = Arc::new(DaemonSource {
+        plugins: daemon.plugins.clone(),
+        scheduler: Arc::clone(&daemon.scheduler),
+        results,
+    });
+
+    // Kept alive for the life of the loop below.
+    let _http_api = if config.http_api.enabled {
+        let started = http_api::resolve_token(&config.http_api, &data_dir)
+            .and_then(|token| http_api::start(config.http_api.port, token, source.clone()));
+        match started {
+            Ok(handle) => Some(handle),
+            Err(err) => {
+                log::error!(
+                    "failed to start http api on port {}: {}",
+                    config.http_api.port,
+                    err
+                );
+                return cli::EXIT_USAGE;
+            }
+        }
+    } else {
+        None
+    };
+
+    let interval = Duration::from_secs(config.interval_secs);
+    loop {
+        let started = Instant::now();
+        let outputs = daemon.logged_round();
+        if once {
+            return cli::exit_code(&outputs);
+        }
+        std::thread::sleep(interval.saturating_sub(started.elapsed()));
+    }
+}
+
+/// `--mcp`: answer MCP requests on stdin/stdout until stdin closes. Every agent session
+/// launches its own server, often while the app or a daemon owns the data dir, so the
+/// data dir is only locked while a `refresh` probes. Logs stay on stderr.
+fn run_mcp(config: &DaemonConfig, data_dir: PathBuf) -> i32 {
+    let plugins = match &config.plugins_dir {
+        Some(dir) => plugin_engine::manifest::load_plugins_from_dir(dir),
+        // Installing the bundled plugins writes to the data dir; its owner does that.
+        None => match DataDirLock::acquire(&data_dir) {
+            Ok(_lock) => {
+                plugin_engine::initialize_plugins(&data_dir, &cli::default_resource_dir()).1
+            }
+            Err(_) => plugin_engine::load_installed_plugins(&data_dir).1,
+        },
+    };
+    let plugins = select_plugins(plugins, &config.plugins);
+    if plugins.is_empty() {
+        log::error!("no plugins to serve");
+        return cli::EXIT_USAGE;
+    }
+    log::info!("serving {} over mcp", plugin_list(&plugins));
+
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            log::error!("failed to start runtime: {}", err);
+            return cli::EXIT_USAGE;
+        }
+    };
+    let _guard = runtime.enter();
+
+    let probe_config = config.probe_config();
+    let max_workers = probe_config.max_concurrent_probes;
+    let probe_dir = data_dir.clone();
+    let source = McpSource::new(
+        plugins,
+        data_dir,
+        config.alert_rules.clone(),
+        max_workers,
+        Arc::new(move |results: &ResultPipeline, plugin: &LoadedPlugin| {
+            results.probe(plugin, &probe_dir, env!("CARGO_PKG_VERSION"), &probe_config)
+        }),
+        SinkDispatcher::from_config(&config.sinks),
+    );
+    match McpServer::new(Arc::new(source)).serve_stdio(std::io::stdin().lock(), std::io::stdout()) {
+        Ok(()) => cli::EXIT_OK,
+        Err(err) => {
+            log::error!("mcp stdio failed: {}", err);
+            cli::EXIT_USAGE
+        }
+    }
+}
+
+fn plugin_list(plugins: &[LoadedPlugin]) -> String {
+    plugins
+        .iter()
+        .map(|plugin| plugin.manifest.id.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Plugins named in the config, or all of them; unknown ids are logged and skipped.
+fn select_plugins(plugins: Vec<LoadedPlugin>, ids: &[String]) -> Vec<LoadedPlugin> {
+    for id in ids {
+        if !plugins.iter().any(|plugin| plugin.manifest.id == *id) {
+            log::warn!("configured plugin {} not found", id);
+        }
+    }
+    plugins
+        .into_iter()
+        .filter(|plugin| ids.is_empty() || ids.contains(&plugin.manifest.id))
+        .collect()
+}
+
+/// Probe rounds over a fixed set of plugins.
+struct Daemon {
+    scheduler: Arc<ProbeScheduler>,
+    results: mpsc::Receiver<Option<PluginOutput>>,
+    plugins: Vec<LoadedPlugin>,
+    rounds: u64,
+}
+
+impl Daemon {
+    fn new(
+        plugins: Vec<LoadedPlugin>,
+        max_workers: usize,
+        probe: ProbeFn,
+        sinks: SinkDispatcher,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let scheduler = ProbeScheduler::new(
+            max_workers,
+            probe,
+            // Batches started through the HTTP API don't belong to a round.
+            Arc::new(move |event: ProbeEvent| match event {
+                ProbeEvent::Result { batch_id, output } => {
+                    let result = ProbeResult { batch_id, output };
+                    sinks.dispatch(&result);
+                    if result.batch_id.starts_with(ROUND_BATCH_PREFIX) {
+                        let _ = tx.send(Some(result.output));
+                    }
+                }
+                ProbeEvent::BatchComplete { batch_id, .. }
+                    if batch_id.starts_with(ROUND_BATCH_PREFIX) =>
+                {
+                    let _ = tx.send(None);
+                }
+                _ => {}
+            }),
+        );
+        Self {
+            scheduler: Arc::new(scheduler),
+            results: rx,
+            plugins,
+            rounds: 0,
+        }
+    }
+
+    /// Probe every plugin once and wait for all results.
+    fn round(&mut self) -> Vec<PluginOutput> {
+        self.rounds += 1;
+        let batch_id = format!("{}{}", ROUND_BATCH_PREFIX, self.rounds);
+        if let Err(err) = self.scheduler.submit(&batch_id, self.plugins.clone()) {
+            log::error!("failed to start {}: {}", batch_id, err);
+            return Vec::new();
+        }
+        self.results.iter().map_while(|output| output).collect()
+    }
+
+    fn logged_round(&mut self) -> Vec<PluginOutput> {
+        let outputs = self.round();
+        let failed = outputs
+            .iter()
+            .filter(|output| output.error.is_some())
+            .count();
+        log::info!(
+            "round {} done: {} ok, {} failed",
+            self.rounds,
+            outputs.len() - failed,
+            failed
+        );
+        outputs
+    }
+}
+
+/// Serves the daemon's plugins and results over the HTTP API.
+struct DaemonSource {
+    plugins: Vec<LoadedPlugin>,
+    scheduler: Arc<ProbeScheduler>,
+    results: ResultPipeline,
+}
+
+impl UsageSource for DaemonSource {
+    fn plugins(&self) -> Vec<PluginMeta> {
+        self.plugins.iter().map(PluginMeta::from).collect()
+    }
+
+    fn usage(&self) -> Vec<PluginOutput> {
+        self.results.current(
+            self.plugins
+                .iter()
+                .map(|plugin| plugin.manifest.id.as_str()),
+        )
+    }
+
+    fn metrics(&self) -> String {
+        self.results.metrics(
+            self.plugins
+                .iter()
+                .map(|plugin| plugin.manifest.id.as_str()),
+        )
+    }
+
+    fn refresh(&self, plugin_ids: Option<Vec<String>>) -> Result<ProbeBatchStarted, String> {
+        let batch_id = format!("api-{}", uuid::Uuid::new_v4());
+        self.scheduler
+            .start_batch(&batch_id, self.plugins.clone(), plugin_ids)
+    }
+
+    fn wait(&self, batch_id: &str, timeout: Duration) -> bool {
+        self.scheduler.wait_batch(batch_id, timeout)
+    }
+}
+
+/// Probes one plugin through the pipeline of the refresh it belongs to.
+type McpProbeFn = Arc<dyn Fn(&ResultPipeline, &LoadedPlugin) -> PluginOutput + Send + Sync>;
+
+/// Serves `--mcp`. Usage is read from the data dir's output cache on every call, so it
+/// follows whichever process owns the dir. A refresh takes the data dir lock and
+/// reopens the stores, and both are let go once its probes are done; while another
+/// process holds the lock, refreshing is left to it.
+struct McpSource {
+    plugins: Vec<LoadedPlugin>,
+    data_dir: PathBuf,
+    scheduler: ProbeScheduler,
+    writer: Arc<Mutex<Option<McpWriter>>>,
+    /// Results of this server's own refreshes, failures included.
+    latest: Arc<Mutex<HashMap<String, PluginOutput>>>,
+    alert_rules: AlertRules,
+}
+
+/// The data dir lock and stores while refreshes are running.
+struct McpWriter {
+    _lock: DataDirLock,
+    results: ResultPipeline,
+    batches: usize,
+}
+
+impl McpSource {
+    fn new(
+        plugins: Vec<LoadedPlugin>,
+        data_dir: PathBuf,
+        alert_rules: AlertRules,
+        max_workers: usize,
+        probe: McpProbeFn,
+        sinks: SinkDispatcher,
+    ) -> Self {
+        let writer: Arc<Mutex<Option<McpWriter>>> = Arc::new(Mutex::new(None));
+        let latest = Arc::new(Mutex::new(HashMap::new()));
+        let probe_writer = Arc::clone(&writer);
+        let probe_dir = data_dir.clone();
+        let probe_rules = alert_rules.clone();
+        let event_writer = Arc::clone(&writer);
+        let event_latest = Arc::clone(&latest);
+        let scheduler = ProbeScheduler::new(
+            max_workers,
+            Arc::new(move |plugin: &LoadedPlugin| {
+                let results = probe_writer
+                    .lock()
+                    .ok()
+                    .and_then(|writer| writer.as_ref().map(|writer| writer.results.clone()))
+                    .unwrap_or_else(|| {
+                        ResultPipeline::open_read_only(
+                            &probe_dir,
+                            probe_rules.clone(),
+                            Arc::new(LogSink),
+                        )
+                    });
+                probe(&results, plugin)
+            }),
+            Arc::new(move |event: ProbeEvent| match event {
+                ProbeEvent::Result { batch_id, output } => {
+                    let result = ProbeResult { batch_id, output };
+                    sinks.dispatch(&result);
+                    if let Ok(mut latest) = event_latest.lock() {
+                        latest.insert(result.output.provider_id.clone(), result.output);
+                    }
+                }
+                ProbeEvent::BatchComplete { .. } => {
+                    if let Ok(mut writer) = event_writer.lock() {
+                        release_batch(&mut writer);
+                    }
+                }
+                _ => {}
+            }),
+        );
+        Self {
+            plugins,
+            data_dir,
+            scheduler,
+            writer,
+            latest,
+            alert_rules,
+        }
+    }
+}
+
+/// Count a finished refresh; the last one gives the data dir back.
+fn release_batch(writer: &mut Option<McpWriter>) {
+    if let Some(held) = writer.as_mut() {
+        held.batches = held.batches.saturating_sub(1);
+        if held.batches == 0 {
+            *writer = None;
+        }
+    }
+}
+
+impl UsageSource for McpSource {
+    fn plugins(&self) -> Vec<PluginMeta> {
+        self.plugins.iter().map(PluginMeta::from).collect()
+    }
+
+    fn usage(&self) -> Vec<PluginOutput> {
+        let cache = OutputCache::load(&self.data_dir);
+        let latest = self.latest.lock().ok();
+        self.plugins
+            .iter()
+            .filter_map(|plugin| {
+                let id = plugin.manifest.id.as_str();
+                let cached = cache.get(id);
+                let own = latest.as_ref().and_then(|latest| latest.get(id));
+                match own {
+                    // A failed refresh is reported until a newer result reaches the cache.
+                    Some(own)
+                        if cached.is_none_or(|cached| cached.fetched_at == own.fetched_at) =>
+                    {
+                        Some(own.clone())
+                    }
+                    _ => cached.cloned(),
+                }
+            })
+            .collect()
+    }
+
+    fn metrics(&self) -> String {
+        metrics::render(&self.usage(), &BTreeMap::new())
+    }
+
+    fn refresh(&self, plugin_ids: Option<Vec<String>>) -> Result<ProbeBatchStarted, String> {
+        {
+            let mut writer = self
+                .writer
+                .lock()
+                .map_err(|_| "refresh state is poisoned".to_string())?;
+            match writer.as_mut() {
+                Some(held) => held.batches += 1,
+                None => {
+                    let lock = DataDirLock::acquire(&self.data_dir).map_err(|message| {
+                        format!(
+                            "{}; it probes on its own and get_usage returns its latest results",
+                            message
+                        )
+                    })?;
+                    *writer = Some(McpWriter {
+                        _lock: lock,
+                        results: ResultPipeline::open(
+                            &self.data_dir,
+                            self.alert_rules.clone(),
+                            Arc::new(LogSink),
+                        ),
+                        batches: 1,
+                    });
+                }
+            }
+        }
+        // Outside the writer lock: an empty batch completes within `start_batch`.
+        let batch_id = format!("mcp-{}", uuid::Uuid::new_v4());
+        let started = self
+            .scheduler
+            .start_batch(&batch_id, self.plugins.clone(), plugin_ids);
+        if started.is_err() {
+            if let Ok(mut writer) = self.writer.lock() {
+                release_batch(&mut writer);
+            }
+        }
+        started
+    }
+
+    fn wait(&self, batch_id: &str, timeout: Duration) -> bool {
+        self.scheduler.wait_batch(batch_id, timeout)
+    }
+}
+
+/// Alerts end up in the log (and so the journal) when there is no desktop.
+struct LogSink;
+
+impl NotificationSink for LogSink {
+    fn notify(&self, notification: &AlertNotification) {
+        log::warn!("{}: {}", notification.title, notification.body);
+    }
+}
+
+struct StderrLogger;
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!(
+                "{:<5} {}: {}",
+                record.level(),
+                record.target(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+/// Log to stderr without timestamps; journald adds its own.
+fn init_logging(level: log::LevelFilter) {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::error::{ProbeError, ProbeErrorKind};
+    use crate::plugin_engine::manifest::PluginManifest;
+    use crate::plugin_engine::temp_dir;
+
+    fn plugin(id: &str) -> LoadedPlugin {
+        let manifest: PluginManifest = serde_json::from_value(serde_json::json!({
+            "schemaVersion": 1,
+            "id": id,
+            "name": id,
+            "version": "0.0.1",
+            "entry": "plugin.js",
+            "icon": "icon.svg",
+            "lines": []
+        }))
+        .expect("manifest");
+        LoadedPlugin {
+            manifest,
+            plugin_dir: PathBuf::new(),
+            entry_script: String::new(),
+            icon_data_url: String::new(),
+        }
+    }
+
+    #[test]
+    fn missing_config_file_uses_defaults() {
+        let config = DaemonConfig::load(&temp_dir("missing").join(CONFIG_FILE)).unwrap();
+        assert_eq!(config, DaemonConfig::default());
+        assert_eq!(
+            config.probe_config().max_concurrent_probes,
+            ProbeConfig::default().max_concurrent_probes
+        );
+        assert_ne!(config.data_dir(), cli::default_app_data_dir());
+    }
+
+    #[test]
+    fn reads_and_validates_config_file() {
+        let dir = temp_dir("config");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(CONFIG_FILE);
+        std::fs::write(
+            &path,
+            r#"{ "intervalSecs": 120, "plugins": ["claude"], "probeTimeoutMs": 5000,
+                 "alertRules": { "thresholds": [90] }, "logLevel": "debug", "allowPrivateNetwork": true,
+                 "secretStore": "file", "httpApi": { "enabled": true },
+                 "otlp": { "enabled": true, "endpoint": "http://collector:4318" } }"#,
+        )
+        .unwrap();
+        let config = DaemonConfig::load(&path).unwrap();
+        assert_eq!(config.interval_secs, 120);
+        assert_eq!(config.plugins, vec!["claude".to_string()]);
+        assert_eq!(config.probe_config().default_timeout_ms, 5000);
+        assert!(config.probe_config().allow_private_network);
+        assert_eq!(config.probe_config().secret_store, SecretStoreKind::File);
+        assert_eq!(config.alert_rules.thresholds, vec![90.0]);
+        assert!(config.http_api.enabled);
+        assert_eq!(config.http_api.port, http_api::DEFAULT_PORT);
+        assert_eq!(config.otlp.endpoint, "http://collector:4318");
+        assert_eq!(config.otlp.service_name, "openusage");
+
+        std::fs::write(&path, r#"{ "intervalSecs": 5 }"#).unwrap();
+        assert!(DaemonConfig::load(&path)
+            .unwrap_err()
+            .contains("intervalSecs"));
+        std::fs::write(&path, r#"{ "intervalSec": 600 }"#).unwrap();
+        assert!(DaemonConfig::load(&path).is_err(), "typos are rejected");
+        std::fs::write(&path, r#"{ "logLevel": "loud" }"#).unwrap();
+        assert!(DaemonConfig::load(&path).is_err());
+    }
+
+    #[test]
+    fn rounds_probe_selected_plugins_and_persist_results() {
+        let dir = temp_dir("round");
+        let results = ResultPipeline::open(&dir, AlertRules::default(), Arc::new(LogSink));
+        let probe: ProbeFn = Arc::new(move |plugin: &LoadedPlugin| {
+            results.process(PluginOutput {
+                provider_id: plugin.manifest.id.clone(),
+                display_name: plugin.manifest.name.clone(),
+                plan: None,
+                lines: vec![],
+                icon_url: String::new(),
+                error: None,
+                fetched_at: None,
+                stale: false,
+            })
+        });
+        let plugins = select_plugins(
+            vec![plugin("claude"), plugin("codex"), plugin("cursor")],
+            &[
+                "codex".to_string(),
+                "claude".to_string(),
+                "nope".to_string(),
+            ],
+        );
+        let mut daemon = Daemon::new(plugins, 2, probe, SinkDispatcher::default());
+
+        for round in 1..=2 {
+            let mut ids: Vec<String> = daemon
+                .round()
+                .into_iter()
+                .map(|output| output.provider_id)
+                .collect();
+            ids.sort();
+            assert_eq!(ids, vec!["claude".to_string(), "codex".to_string()]);
+            assert_eq!(daemon.rounds, round);
+        }
+
+        let cache = plugin_engine::output_cache::OutputCache::load(&dir);
+        assert!(cache.get("claude").is_some() && cache.get("codex").is_some());
+        assert!(cache.get("cursor").is_none());
+    }
+
+    #[test]
+    fn api_refresh_reports_through_the_pipeline_without_ending_a_round() {
+        let dir = temp_dir("refresh");
+        let results = ResultPipeline::open(&dir, AlertRules::default(), Arc::new(LogSink));
+        let probe_results = results.clone();
+        let probe: ProbeFn = Arc::new(move |plugin: &LoadedPlugin| {
+            probe_results.process(PluginOutput {
+                provider_id: plugin.manifest.id.clone(),
+                display_name: plugin.manifest.name.clone(),
+                plan: None,
+                lines: vec![],
+                icon_url: String::new(),
+                error: None,
+                fetched_at: None,
+                stale: false,
+            })
+        });
+        let mut daemon = Daemon::new(
+            vec![plugin("claude"), plugin("codex")],
+            2,
+            probe,
+            SinkDispatcher::default(),
+        );
+        let source = DaemonSource {
+            plugins: daemon.plugins.clone(),
+            scheduler: Arc::clone(&daemon.scheduler),
+            results,
+        };
+        assert_eq!(source.plugins().len(), 2);
+
+        let started = source.refresh(Some(vec!["codex".to_string()])).unwrap();
+        assert!(started.batch_id.starts_with("api-"));
+        assert_eq!(started.plugin_ids, vec!["codex".to_string()]);
+        assert_eq!(daemon.round().len(), 2);
+        assert_eq!(daemon.round().len(), 2);
+        assert_eq!(source.usage().len(), 2);
+    }
+
+    #[test]
+    fn mcp_source_locks_the_data_dir_only_while_refreshing() {
+        let dir = temp_dir("mcp");
+        let output = |id: &str, error: Option<ProbeError>| PluginOutput {
+            provider_id: id.to_string(),
+            display_name: id.to_string(),
+            plan: None,
+            lines: vec![],
+            icon_url: String::new(),
+            error,
+            fetched_at: None,
+            stale: false,
+        };
+        let failing = Arc::new(Mutex::new(false));
+        let probe_failing = Arc::clone(&failing);
+        let source = McpSource::new(
+            vec![plugin("claude"), plugin("codex")],
+            dir.clone(),
+            AlertRules::default(),
+            2,
+            Arc::new(move |results: &ResultPipeline, plugin: &LoadedPlugin| {
+                let error = (*probe_failing.lock().unwrap())
+                    .then(|| ProbeError::new(ProbeErrorKind::Network, "offline"));
+                results.process(output(&plugin.manifest.id, error))
+            }),
+            SinkDispatcher::default(),
+        );
+        assert!(source.usage().is_empty());
+
+        // Another process owns the dir: its cache is served, refreshing is its job.
+        let owner_lock = DataDirLock::acquire(&dir).unwrap();
+        let owner = ResultPipeline::open(&dir, AlertRules::default(), Arc::new(LogSink));
+        for id in ["claude", "cursor"] {
+            owner.process(output(id, None));
+        }
+        let usage = source.usage();
+        assert_eq!(usage.len(), 1);
+        assert_eq!(usage[0].provider_id, "claude");
+        assert!(source
+            .refresh(None)
+            .unwrap_err()
+            .contains("another OpenUsage app"));
+        drop(owner_lock);
+
+        let started = source.refresh(Some(vec!["codex".to_string()])).unwrap();
+        assert!(source.wait(&started.batch_id, Duration::from_secs(10)));
+        assert_eq!(source.usage().len(), 2);
+        let relocked = DataDirLock::acquire(&dir).expect("released after the refresh");
+        drop(relocked);
+
+        *failing.lock().unwrap() = true;
+        let started = source.refresh(Some(vec!["codex".to_string()])).unwrap();
+        assert!(source.wait(&started.batch_id, Duration::from_secs(10)));
+        let usage = source.usage();
+        let codex = usage
+            .iter()
+            .find(|output| output.provider_id == "codex")
+            .unwrap();
+        assert!(codex.stale && codex.error.is_some());
+        assert!(OutputCache::load(&dir)
+            .get("codex")
+            .unwrap()
+            .error
+            .is_none());
+    }
+}
@@ -0,0 +1,651 @@
+//! Optional loopback HTTP API so editor extensions, tmux and scripts can read the
+//! numbers the panel shows. Listens on 127.0.0.1 only and requires a bearer token.
+//!
+//! - `GET /v1/plugins` - same shape as `list_plugins`
+//! - `GET /v1/usage` - current `PluginOutput` of every plugin
+//! - `GET /v1/usage/{id}` - one plugin's `PluginOutput`
+//! - `POST /v1/refresh` - start a probe batch, optional body `{ "pluginIds": [...] }`
+//! - `GET /metrics` - progress lines and probe timings for Prometheus
+//! - `POST /mcp` - Model Context Protocol endpoint, see [`crate::mcp`]
+
+use crate::mcp::McpServer;
+use crate::plugin_engine::manifest::PluginMeta;
+use crate::plugin_engine::metrics;
+use crate::plugin_engine::runtime::PluginOutput;
+use crate::plugin_engine::scheduler::ProbeBatchStarted;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+pub const DEFAULT_PORT: u16 = 6736;
+/// Generated token, readable by local clients; only the owner may read it.
+pub const TOKEN_FILE: &str = "api_token";
+
+const MAX_HEADER_BYTES: usize = 16 * 1024;
+const MAX_BODY_BYTES: usize = 64 * 1024;
+const MAX_CONNECTIONS: usize = 16;
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HttpApiConfig {
+    pub enabled: bool,
+    pub port: u16,
+    /// Fixed token; when absent one is generated and kept in `api_token` in the data dir.
+    pub token: Option<String>,
+}
+
+impl Default for HttpApiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: DEFAULT_PORT,
+            token: None,
+        }
+    }
+}
+
+/// What the API serves; implemented by the app and by `openusaged`.
+pub trait UsageSource: Send + Sync {
+    fn plugins(&self) -> Vec<PluginMeta>;
+    /// Current output of every loaded plugin that has reported one.
+    fn usage(&self) -> Vec<PluginOutput>;
+    /// Prometheus text exposition of the current usage.
+    fn metrics(&self) -> String;
+    fn refresh(&self, plugin_ids: Option<Vec<String>>) -> Result<ProbeBatchStarted, String>;
+    /// Block until a batch from `refresh` has reported every plugin; false on timeout.
+    fn wait(&self, batch_id: &str, timeout: Duration) -> bool;
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RefreshRequest {
+    plugin_ids: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorBody<'a> {
+    error: &'a str,
+}
+
+/// The token from the config, or the one in `api_token`, created on first use.
+pub fn resolve_token(config: &HttpApiConfig, data_dir: &Path) -> std::io::Result<String> {
+    if let Some(token) = config
+        .token
+        .as_deref()
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+    {
+        return Ok(token.to_string());
+    }
+    let path = data_dir.join(TOKEN_FILE);
+    if let Ok(existing) = std::fs::read_to_string(&path) {
+        let existing = existing.trim();
+        if !existing.is_empty() {
+            return Ok(existing.to_string());
+        }
+    }
+    std::fs::create_dir_all(data_dir)?;
+    let token = format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    );
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(&path)?.write_all(token.as_bytes())?;
+    Ok(token)
+}
+
+/// A running server; stops when dropped.
+pub struct HttpApiHandle {
+    addr: SocketAddr,
+    stopped: Arc<AtomicBool>,
+}
+
+impl HttpApiHandle {
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for HttpApiHandle {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // Wake the accept loop so it sees the flag.
+        let _ = TcpStream::connect_timeout(&self.addr, Duration::from_millis(200));
+    }
+}
+
+/// Bind 127.0.0.1:`port` (0 picks a free port) and serve on a background thread.
+pub fn start(
+    port: u16,
+    token: String,
+    source: Arc<dyn UsageSource>,
+) -> std::io::Result<HttpApiHandle> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+    let addr = listener.local_addr()?;
+    let stopped = Arc::new(AtomicBool::new(false));
+    let server = Arc::new(Server {
+        token,
+        port: addr.port(),
+        mcp: McpServer::new(Arc::clone(&source)),
+        source,
+        connections: AtomicUsize::new(0),
+    });
+
+    let stop_flag = Arc::clone(&stopped);
+    thread::Builder::new()
+        .name("http-api".to_string())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                if stop_flag.load(Ordering::SeqCst) {
+                    break;
+                }
+                match stream {
+                    Ok(stream) => Server::dispatch(&server, stream),
+                    Err(err) => log::debug!("http api accept failed: {}", err),
+                }
+            }
+        })?;
+    log::info!("http api listening on http://{}", addr);
+    Ok(HttpApiHandle { addr, stopped })
+}
+
+struct Server {
+    token: String,
+    port: u16,
+    source: Arc<dyn UsageSource>,
+    mcp: McpServer,
+    connections: AtomicUsize,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn json<T: Serialize>(status: u16, value: &T) -> Self {
+        match serde_json::to_string(value) {
+            Ok(body) => Self {
+                status,
+                content_type: "application/json",
+                body,
+            },
+            Err(err) => Self::error(500, &err.to_string()),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: serde_json::to_string(&ErrorBody { error: message }).unwrap_or_default(),
+        }
+    }
+}
+
+impl Server {
+    fn dispatch(server: &Arc<Server>, mut stream: TcpStream) {
+        if server.connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            server.connections.fetch_sub(1, Ordering::SeqCst);
+            write_response(&mut stream, &Response::error(503, "too many connections"));
+            return;
+        }
+        let server = Arc::clone(server);
+        let spawned = thread::Builder::new()
+            .name("http-api-conn".to_string())
+            .spawn(move || {
+                let _ = stream.set_read_timeout(Some(IO_TIMEOUT));
+                let _ = stream.set_write_timeout(Some(IO_TIMEOUT));
+                let response = match read_request(&mut stream) {
+                    Ok(request) => server.handle(&request),
+                    Err(response) => response,
+                };
+                write_response(&mut stream, &response);
+                server.connections.fetch_sub(1, Ordering::SeqCst);
+            });
+        if let Err(err) = spawned {
+            log::warn!("http api failed to spawn connection thread: {}", err);
+        }
+    }
+
+    fn handle(&self, request: &Request) -> Response {
+        // Reject other host names so a web page can't reach us through DNS rebinding.
+        let host_ok = request.header("host").is_some_and(|host| {
+            host == format!("127.0.0.1:{}", self.port) || host == format!("localhost:{}", self.port)
+        });
+        if !host_ok {
+            return Response::error(403, "invalid host");
+        }
+        if !self.authorized(request) {
+            return Response::error(401, "missing or invalid bearer token");
+        }
+
+        let path = request
+            .path
+            .split('?')
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches('/');
+        match (request.method.as_str(), path) {
+            ("GET", "/v1/plugins") => Response::json(200, &self.source.plugins()),
+            ("GET", "/v1/usage") => Response::json(200, &self.source.usage()),
+            ("GET", "/metrics") => Response {
+                status: 200,
+                content_type: metrics::CONTENT_TYPE,
+                body: self.source.metrics(),
+            },
+            ("GET", _) if path.starts_with("/v1/usage/") => {
+                let id = &path["/v1/usage/".len()..];
+                match self
+                    .source
+                    .usage()
+                    .into_iter()
+                    .find(|output| output.provider_id == id)
+                {
+                    Some(output) => Response::json(200, &output),
+                    None => Response::error(404, &format!("no usage for plugin {}", id)),
+                }
+            }
+            ("POST", "/v1/refresh") => {
+                let body: RefreshRequest = if request.body.iter().all(u8::is_ascii_whitespace) {
+                    RefreshRequest::default()
+                } else {
+                    match serde_json::from_slice(&request.body) {
+                        Ok(body) => body,
+                        Err(err) => return Response::error(400, &format!("invalid body: {}", err)),
+                    }
+                };
+                match self.source.refresh(body.plugin_ids) {
+                    Ok(started) => Response::json(202, &started),
+                    Err(err) => Response::error(409, &err),
+                }
+            }
+            ("POST", "/mcp") => match std::str::from_utf8(&request.body) {
+                Ok(message) => match self.mcp.handle(message) {
+                    Some(body) => Response {
+                        status: 200,
+                        content_type: "application/json",
+                        body,
+                    },
+                    None => Response {
+                        status: 202,
+                        content_type: "application/json",
+                        body: String::new(),
+                    },
+                },
+                Err(_) => Response::error(400, "body is not utf-8"),
+            },
+            (_, "/v1/plugins" | "/v1/usage" | "/v1/refresh" | "/metrics" | "/mcp") => {
+                Response::error(405, "method not allowed")
+            }
+            _ if path.starts_with("/v1/usage/") => Response::error(405, "method not allowed"),
+            _ => Response::error(404, "not found"),
+        }
+    }
+
+    fn authorized(&self, request: &Request) -> bool {
+        let Some(token) = request
+            .header("authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+        else {
+            return false;
+        };
+        constant_time_eq(token.trim().as_bytes(), self.token.as_bytes())
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn read_request(stream: &mut TcpStream) -> Result<Request, Response> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let header_end = loop {
+        if let Some(index) = buf.windows(4).position(|window| window == b"\r\n\r\n") {
+            break index;
+        }
+        if buf.len() > MAX_HEADER_BYTES {
+            return Err(Response::error(431, "headers too large"));
+        }
+        match stream.read(&mut chunk) {
+            Ok(0) => return Err(Response::error(400, "incomplete request")),
+            Ok(read) => buf.extend_from_slice(&chunk[..read]),
+            Err(_) => return Err(Response::error(408, "request timeout")),
+        }
+    };
+
+    let head = std::str::from_utf8(&buf[..header_end])
+        .map_err(|_| Response::error(400, "invalid request"))?;
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let (Some(method), Some(path)) = (request_line.next(), request_line.next()) else {
+        return Err(Response::error(400, "invalid request line"));
+    };
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect();
+
+    let content_length = headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case("content-length"))
+        .map(|(_, value)| value.parse::<usize>())
+        .transpose()
+        .map_err(|_| Response::error(400, "invalid content-length"))?
+        .unwrap_or(0);
+    if content_length > MAX_BODY_BYTES {
+        return Err(Response::error(413, "body too large"));
+    }
+    let mut body = buf[header_end + 4..].to_vec();
+    while body.len() < content_length {
+        match stream.read(&mut chunk) {
+            Ok(0) => return Err(Response::error(400, "incomplete body")),
+            Ok(read) => body.extend_from_slice(&chunk[..read]),
+            Err(_) => return Err(Response::error(408, "request timeout")),
+        }
+    }
+    body.truncate(content_length);
+
+    Ok(Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        headers,
+        body,
+    })
+}
+
+fn write_response(stream: &mut TcpStream, response: &Response) {
+    let reason = match response.status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        431 => "Request Header Fields Too Large",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    };
+    let auth_header = if response.status == 401 {
+        "WWW-Authenticate: Bearer\r\n"
+    } else {
+        ""
+    };
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Cache-Control: no-store\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason,
+        response.content_type,
+        response.body.len(),
+        auth_header
+    );
+    let _ = stream.write_all(head.as_bytes());
+    let _ = stream.write_all(response.body.as_bytes());
+    let _ = stream.flush();
+    let _ = stream.shutdown(Shutdown::Both);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::temp_dir;
+    use std::sync::Mutex;
+
+    const TOKEN: &str = "test-token";
+
+    #[derive(Default)]
+    struct FakeSource {
+        refreshed: Mutex<Vec<Option<Vec<String>>>>,
+    }
+
+    impl UsageSource for FakeSource {
+        fn plugins(&self) -> Vec<PluginMeta> {
+            vec![PluginMeta {
+                id: "claude".to_string(),
+                name: "Claude".to_string(),
+                icon_url: String::new(),
+                brand_color: None,
+                lines: vec![],
+                primary_candidates: vec![],
+            }]
+        }
+
+        fn usage(&self) -> Vec<PluginOutput> {
+            vec![PluginOutput {
+                provider_id: "claude".to_string(),
+                display_name: "Claude".to_string(),
+                plan: Some("Max".to_string()),
+                lines: vec![],
+                icon_url: String::new(),
+                error: None,
+                fetched_at: Some("2026-02-01T00:00:00Z".to_string()),
+                stale: false,
+            }]
+        }
+
+        fn metrics(&self) -> String {
+            metrics::render(&self.usage(), &Default::default())
+        }
+
+        fn refresh(&self, plugin_ids: Option<Vec<String>>) -> Result<ProbeBatchStarted, String> {
+            self.refreshed.lock().unwrap().push(plugin_ids.clone());
+            Ok(ProbeBatchStarted {
+                batch_id: "b1".to_string(),
+                plugin_ids: plugin_ids.unwrap_or_else(|| vec!["claude".to_string()]),
+            })
+        }
+
+        fn wait(&self, _batch_id: &str, _timeout: Duration) -> bool {
+            true
+        }
+    }
+
+    fn server() -> (HttpApiHandle, Arc<FakeSource>) {
+        let source = Arc::new(FakeSource::default());
+        let handle = start(0, TOKEN.to_string(), source.clone()).expect("start");
+        (handle, source)
+    }
+
+    /// Send a raw request and return (status, body).
+    fn send(handle: &HttpApiHandle, request: &str) -> (u16, String) {
+        let mut stream = TcpStream::connect(handle.local_addr()).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response[9..12].parse().unwrap();
+        let body = response.split_once("\r\n\r\n").unwrap().1.to_string();
+        (status, body)
+    }
+
+    fn call(
+        handle: &HttpApiHandle,
+        method: &str,
+        path: &str,
+        token: Option<&str>,
+        body: &str,
+    ) -> (u16, String) {
+        let auth = token
+            .map(|token| format!("Authorization: Bearer {}\r\n", token))
+            .unwrap_or_default();
+        send(
+            handle,
+            &format!(
+                "{} {} HTTP/1.1\r\nHost: 127.0.0.1:{}\r\n{}Content-Length: {}\r\n\r\n{}",
+                method,
+                path,
+                handle.local_addr().port(),
+                auth,
+                body.len(),
+                body
+            ),
+        )
+    }
+
+    #[test]
+    fn requires_bearer_token_and_local_host() {
+        let (handle, _) = server();
+        assert_eq!(call(&handle, "GET", "/v1/usage", None, "").0, 401);
+        assert_eq!(call(&handle, "GET", "/v1/usage", Some("wrong"), "").0, 401);
+        let rebinding = format!(
+            "GET /v1/usage HTTP/1.1\r\nHost: evil.example:{}\r\nAuthorization: Bearer {}\r\n\r\n",
+            handle.local_addr().port(),
+            TOKEN
+        );
+        assert_eq!(send(&handle, &rebinding).0, 403);
+        assert!(handle.local_addr().ip().is_loopback());
+    }
+
+    #[test]
+    fn serves_plugins_and_usage() {
+        let (handle, _) = server();
+        let (status, body) = call(&handle, "GET", "/v1/plugins", Some(TOKEN), "");
+        assert_eq!(status, 200);
+        let plugins: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(plugins[0]["id"], "claude");
+        assert!(plugins[0].get("primaryCandidates").is_some());
+
+        let (status, body) = call(&handle, "GET", "/v1/usage", Some(TOKEN), "");
+        assert_eq!(status, 200);
+        let usage: Vec<PluginOutput> = serde_json::from_str(&body).unwrap();
+        assert_eq!(usage[0].plan.as_deref(), Some("Max"));
+
+        let (status, body) = call(&handle, "GET", "/v1/usage/claude", Some(TOKEN), "");
+        assert_eq!(status, 200);
+        assert!(body.contains("\"providerId\":\"claude\""));
+        assert_eq!(
+            call(&handle, "GET", "/v1/usage/cursor", Some(TOKEN), "").0,
+            404
+        );
+        assert_eq!(call(&handle, "GET", "/v2/usage", Some(TOKEN), "").0, 404);
+        assert_eq!(call(&handle, "DELETE", "/v1/usage", Some(TOKEN), "").0, 405);
+    }
+
+    #[test]
+    fn serves_prometheus_metrics() {
+        let (handle, _) = server();
+        let mut stream = TcpStream::connect(handle.local_addr()).unwrap();
+        let request = format!(
+            "GET /metrics HTTP/1.1\r\nHost: localhost:{}\r\nAuthorization: Bearer {}\r\n\r\n",
+            handle.local_addr().port(),
+            TOKEN
+        );
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains(&format!("Content-Type: {}\r\n", metrics::CONTENT_TYPE)));
+        assert_eq!(call(&handle, "GET", "/metrics", None, "").0, 401);
+        assert_eq!(call(&handle, "POST", "/metrics", Some(TOKEN), "").0, 405);
+    }
+
+    #[test]
+    fn refresh_starts_a_batch() {
+        let (handle, source) = server();
+        let (status, body) = call(&handle, "POST", "/v1/refresh", Some(TOKEN), "");
+        assert_eq!(status, 202);
+        assert!(body.contains("\"batchId\":\"b1\""));
+
+        let (status, _) = call(
+            &handle,
+            "POST",
+            "/v1/refresh",
+            Some(TOKEN),
+            r#"{"pluginIds":["codex"]}"#,
+        );
+        assert_eq!(status, 202);
+        assert_eq!(
+            call(&handle, "POST", "/v1/refresh", Some(TOKEN), "{oops").0,
+            400
+        );
+        assert_eq!(call(&handle, "GET", "/v1/refresh", Some(TOKEN), "").0, 405);
+        assert_eq!(
+            *source.refreshed.lock().unwrap(),
+            vec![None, Some(vec!["codex".to_string()])]
+        );
+    }
+
+    #[test]
+    fn serves_mcp_over_post() {
+        let (handle, source) = server();
+        let (status, body) = call(
+            &handle,
+            "POST",
+            "/mcp",
+            Some(TOKEN),
+            r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":"refresh"}}"#,
+        );
+        assert_eq!(status, 200);
+        let response: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            response["result"]["structuredContent"]["usage"][0]["providerId"],
+            "claude"
+        );
+        assert_eq!(*source.refreshed.lock().unwrap(), vec![None]);
+
+        let notification = r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#;
+        assert_eq!(
+            call(&handle, "POST", "/mcp", Some(TOKEN), notification),
+            (202, String::new())
+        );
+        assert_eq!(call(&handle, "POST", "/mcp", None, notification).0, 401);
+        assert_eq!(call(&handle, "GET", "/mcp", Some(TOKEN), "").0, 405);
+    }
+
+    #[test]
+    fn generated_token_is_persisted_privately() {
+        let dir = temp_dir("http-token");
+        let config = HttpApiConfig::default();
+        let first = resolve_token(&config, &dir).unwrap();
+        assert_eq!(first.len(), 64);
+        assert_eq!(resolve_token(&config, &dir).unwrap(), first);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(dir.join(TOKEN_FILE))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let fixed = HttpApiConfig {
+            token: Some("mine".to_string()),
+            ..HttpApiConfig::default()
+        };
+        assert_eq!(resolve_token(&fixed, &dir).unwrap(), "mine");
+    }
+}
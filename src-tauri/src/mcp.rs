@@ -0,0 +1,452 @@
+//! Model Context Protocol server, so coding agents can check their own quota and pace
+//! themselves. Speaks JSON-RPC 2.0 over stdio (`openusaged --mcp`) or over the local
+//! HTTP API (`POST /mcp`), backed by the same [`UsageSource`] as the HTTP API.
+//!
+//! Tools:
+//! - `list_providers` - loaded plugins and the lines they report
+//! - `get_usage` - latest usage of one or all providers, answered from memory
+//! - `refresh` - probe providers now and return their fresh usage
+
+use crate::http_api::UsageSource;
+use crate::plugin_engine::runtime::PluginOutput;
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Protocol revisions this server can speak, newest first.
+const PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+const DEFAULT_REFRESH_TIMEOUT: Duration = Duration::from_secs(60);
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+pub struct McpServer {
+    source: Arc<dyn UsageSource>,
+    refresh_timeout: Duration,
+}
+
+impl McpServer {
+    pub fn new(source: Arc<dyn UsageSource>) -> Self {
+        Self {
+            source,
+            refresh_timeout: DEFAULT_REFRESH_TIMEOUT,
+        }
+    }
+
+    /// Handle one JSON-RPC message; `None` for notifications, which get no response.
+    pub fn handle(&self, message: &str) -> Option<String> {
+        let response = match serde_json::from_str::<Value>(message) {
+            Ok(request) => self.handle_value(&request)?,
+            Err(err) => error_response(Value::Null, PARSE_ERROR, &format!("parse error: {}", err)),
+        };
+        Some(response.to_string())
+    }
+
+    /// Serve newline-delimited messages until `input` is closed.
+    pub fn serve_stdio(&self, input: impl BufRead, mut output: impl Write) -> std::io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = self.handle(&line) {
+                output.write_all(response.as_bytes())?;
+                output.write_all(b"\n")?;
+                output.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_value(&self, request: &Value) -> Option<Value> {
+        let id = request.get("id").cloned();
+        let Some(method) = request.get("method").and_then(Value::as_str) else {
+            return Some(error_response(
+                id.unwrap_or(Value::Null),
+                INVALID_REQUEST,
+                "invalid request",
+            ));
+        };
+        // Notifications (no id) never get a response, not even an error.
+        let id = id?;
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+        let result = match method {
+            "initialize" => Ok(self.initialize(&params)),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": tool_definitions() })),
+            "tools/call" => self.call_tool(&params),
+            _ => Err((METHOD_NOT_FOUND, format!("method not found: {}", method))),
+        };
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => error_response(id, code, &message),
+        })
+    }
+
+    fn initialize(&self, params: &Value) -> Value {
+        let requested = params.get("protocolVersion").and_then(Value::as_str);
+        let version = requested
+            .filter(|version| PROTOCOL_VERSIONS.contains(version))
+            .unwrap_or(PROTOCOL_VERSIONS[0]);
+        json!({
+            "protocolVersion": version,
+            "capabilities": { "tools": { "listChanged": false } },
+            "serverInfo": { "name": "openusage", "version": env!("CARGO_PKG_VERSION") },
+            "instructions": "Reports how much of each AI provider's usage limits is used. \
+                Check get_usage before long or expensive work and slow down when a limit is nearly used up."
+        })
+    }
+
+    fn call_tool(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let name = params
+            .get("name")
+            .and_then(Value::as_str)
+            .ok_or((INVALID_PARAMS, "missing tool name".to_string()))?;
+        let arguments = params
+            .get("arguments")
+            .cloned()
+            .unwrap_or_else(|| json!({}));
+        let outcome = match name {
+            "list_providers" => Ok(self.list_providers()),
+            "get_usage" => self.get_usage(&arguments),
+            "refresh" => self.refresh(&arguments),
+            _ => return Err((INVALID_PARAMS, format!("unknown tool: {}", name))),
+        };
+        // Tool failures are results the agent can read, not protocol errors.
+        Ok(match outcome {
+            Ok(value) => json!({
+                "content": [{ "type": "text", "text": value.to_string() }],
+                "structuredContent": value,
+                "isError": false
+            }),
+            Err(message) => json!({
+                "content": [{ "type": "text", "text": message }],
+                "isError": true
+            }),
+        })
+    }
+
+    fn list_providers(&self) -> Value {
+        let providers: Vec<Value> = self
+            .source
+            .plugins()
+            .into_iter()
+            .map(|plugin| {
+                json!({
+                    "id": plugin.id,
+                    "name": plugin.name,
+                    "lines": plugin.lines.iter().map(|line| &line.label).collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+        json!({ "providers": providers })
+    }
+
+    fn get_usage(&self, arguments: &Value) -> Result<Value, String> {
+        let usage = self.source.usage();
+        match arguments.get("provider").and_then(Value::as_str) {
+            Some(provider) => match usage.iter().find(|output| output.provider_id == provider) {
+                Some(output) => Ok(json!({ "usage": [compact(output)] })),
+                None => Err(self.unknown_provider(provider)),
+            },
+            None => Ok(json!({ "usage": usage.iter().map(compact).collect::<Vec<_>>() })),
+        }
+    }
+
+    fn refresh(&self, arguments: &Value) -> Result<Value, String> {
+        let providers: Option<Vec<String>> = match arguments.get("providers") {
+            None | Some(Value::Null) => None,
+            Some(value) => Some(
+                serde_json::from_value(value.clone())
+                    .map_err(|_| "providers must be a list of ids".to_string())?,
+            ),
+        };
+        if let Some(unknown) = providers.as_ref().and_then(|ids| {
+            let known: Vec<String> = self
+                .source
+                .plugins()
+                .into_iter()
+                .map(|plugin| plugin.id)
+                .collect();
+            ids.iter().find(|id| !known.contains(id)).cloned()
+        }) {
+            return Err(self.unknown_provider(&unknown));
+        }
+
+        let started = self.source.refresh(providers)?;
+        let complete = self.source.wait(&started.batch_id, self.refresh_timeout);
+        let usage: Vec<Value> = self
+            .source
+            .usage()
+            .iter()
+            .filter(|output| started.plugin_ids.contains(&output.provider_id))
+            .map(compact)
+            .collect();
+        Ok(json!({ "complete": complete, "usage": usage }))
+    }
+
+    fn unknown_provider(&self, provider: &str) -> String {
+        let known: Vec<String> = self
+            .source
+            .plugins()
+            .into_iter()
+            .map(|plugin| plugin.id)
+            .collect();
+        format!(
+            "no usage for provider {}; known providers: {}",
+            provider,
+            known.join(", ")
+        )
+    }
+}
+
+/// A `PluginOutput` without the icon, which is a large data URL agents don't need.
+fn compact(output: &PluginOutput) -> Value {
+    let mut value = serde_json::to_value(output).unwrap_or(Value::Null);
+    if let Some(object) = value.as_object_mut() {
+        object.remove("iconUrl");
+    }
+    value
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+fn tool_definitions() -> Value {
+    json!([
+        {
+            "name": "list_providers",
+            "description": "List the AI providers OpenUsage tracks and the usage lines each reports.",
+            "inputSchema": { "type": "object", "properties": {} }
+        },
+        {
+            "name": "get_usage",
+            "description": "Latest usage of one provider, or of all when no provider is given. \
+                Progress lines carry used, limit, resetsAt and, when enough history exists, a forecast.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "provider": { "type": "string", "description": "Provider id from list_providers, e.g. \"claude\"" }
+                }
+            }
+        },
+        {
+            "name": "refresh",
+            "description": "Probe providers now and return their fresh usage. Takes a few seconds.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "providers": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Provider ids to probe; all when omitted"
+                    }
+                }
+            }
+        }
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::manifest::{ManifestLineDto, PluginMeta};
+    use crate::plugin_engine::scheduler::ProbeBatchStarted;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct FakeSource {
+        refreshed: Mutex<Vec<Option<Vec<String>>>>,
+    }
+
+    impl UsageSource for FakeSource {
+        fn plugins(&self) -> Vec<PluginMeta> {
+            ["claude", "codex"]
+                .iter()
+                .map(|id| PluginMeta {
+                    id: id.to_string(),
+                    name: id.to_string(),
+                    icon_url: "data:image/svg+xml;base64,AAAA".to_string(),
+                    brand_color: None,
+                    lines: vec![ManifestLineDto {
+                        line_type: "progress".to_string(),
+                        label: "Session".to_string(),
+                        scope: "overview".to_string(),
+                    }],
+                    primary_candidates: vec![],
+                })
+                .collect()
+        }
+
+        fn usage(&self) -> Vec<PluginOutput> {
+            vec![PluginOutput {
+                provider_id: "claude".to_string(),
+                display_name: "Claude".to_string(),
+                plan: Some("Max".to_string()),
+                lines: vec![],
+                icon_url: "data:image/svg+xml;base64,AAAA".to_string(),
+                error: None,
+                fetched_at: None,
+                stale: false,
+            }]
+        }
+
+        fn metrics(&self) -> String {
+            String::new()
+        }
+
+        fn refresh(&self, plugin_ids: Option<Vec<String>>) -> Result<ProbeBatchStarted, String> {
+            self.refreshed.lock().unwrap().push(plugin_ids.clone());
+            Ok(ProbeBatchStarted {
+                batch_id: "b1".to_string(),
+                plugin_ids: plugin_ids
+                    .unwrap_or_else(|| vec!["claude".to_string(), "codex".to_string()]),
+            })
+        }
+
+        fn wait(&self, _batch_id: &str, _timeout: Duration) -> bool {
+            true
+        }
+    }
+
+    fn request(server: &McpServer, message: Value) -> Value {
+        serde_json::from_str(&server.handle(&message.to_string()).expect("response")).unwrap()
+    }
+
+    fn call(server: &McpServer, name: &str, arguments: Value) -> Value {
+        request(
+            server,
+            json!({ "jsonrpc": "2.0", "id": 7, "method": "tools/call", "params": { "name": name, "arguments": arguments } }),
+        )["result"]
+            .clone()
+    }
+
+    #[test]
+    fn initializes_and_lists_tools() {
+        let server = McpServer::new(Arc::new(FakeSource::default()));
+        let response = request(
+            &server,
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "protocolVersion": "2025-03-26" } }),
+        );
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"]["protocolVersion"], "2025-03-26");
+        assert!(response["result"]["capabilities"]["tools"].is_object());
+
+        let response = request(
+            &server,
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "protocolVersion": "1999-01-01" } }),
+        );
+        assert_eq!(response["result"]["protocolVersion"], PROTOCOL_VERSIONS[0]);
+
+        assert!(server
+            .handle(&json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }).to_string())
+            .is_none());
+
+        let tools = request(
+            &server,
+            json!({ "jsonrpc": "2.0", "id": "t", "method": "tools/list" }),
+        );
+        let names: Vec<&str> = tools["result"]["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|tool| tool["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["list_providers", "get_usage", "refresh"]);
+    }
+
+    #[test]
+    fn protocol_errors() {
+        let server = McpServer::new(Arc::new(FakeSource::default()));
+        let parsed: Value = serde_json::from_str(&server.handle("{not json").unwrap()).unwrap();
+        assert_eq!(parsed["error"]["code"], PARSE_ERROR);
+        let response = request(
+            &server,
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "resources/list" }),
+        );
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+        let response = request(
+            &server,
+            json!({ "jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": { "name": "delete_everything" } }),
+        );
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[test]
+    fn tools_answer_from_the_source() {
+        let source = Arc::new(FakeSource::default());
+        let server = McpServer::new(source.clone());
+
+        let providers = call(&server, "list_providers", json!({}));
+        assert_eq!(
+            providers["structuredContent"]["providers"][1]["id"],
+            "codex"
+        );
+        assert_eq!(
+            providers["structuredContent"]["providers"][0]["lines"][0],
+            "Session"
+        );
+
+        let usage = call(&server, "get_usage", json!({ "provider": "claude" }));
+        assert_eq!(usage["isError"], false);
+        assert_eq!(usage["structuredContent"]["usage"][0]["plan"], "Max");
+        assert!(usage["structuredContent"]["usage"][0]
+            .get("iconUrl")
+            .is_none());
+        let text: Value =
+            serde_json::from_str(usage["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(text, usage["structuredContent"]);
+
+        let missing = call(&server, "get_usage", json!({ "provider": "cursor" }));
+        assert_eq!(missing["isError"], true);
+        assert!(missing["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("claude, codex"));
+
+        let refreshed = call(&server, "refresh", json!({ "providers": ["claude"] }));
+        assert_eq!(refreshed["structuredContent"]["complete"], true);
+        assert_eq!(
+            refreshed["structuredContent"]["usage"]
+                .as_array()
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            call(&server, "refresh", json!({ "providers": ["cursor"] }))["isError"],
+            true
+        );
+        assert_eq!(
+            *source.refreshed.lock().unwrap(),
+            vec![Some(vec!["claude".to_string()])]
+        );
+    }
+
+    #[test]
+    fn serves_newline_delimited_stdio() {
+        let server = McpServer::new(Arc::new(FakeSource::default()));
+        let input = concat!(
+            r#"{"jsonrpc":"2.0","id":1,"method":"ping"}"#,
+            "\n\n",
+            r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","id":2,"method":"tools/list"}"#,
+            "\n"
+        );
+        let mut output = Vec::new();
+        server.serve_stdio(input.as_bytes(), &mut output).unwrap();
+        let lines: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], json!({ "jsonrpc": "2.0", "id": 1, "result": {} }));
+        assert_eq!(lines[1]["id"], 2);
+    }
+}
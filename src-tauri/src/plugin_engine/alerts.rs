@@ -0,0 +1,524 @@
+use crate::plugin_engine::runtime::{MetricLine, PluginOutput};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub const ALERT_STATE_FILE: &str = "alert_state.json";
+
+const DEFAULT_THRESHOLDS: [f64; 2] = [80.0, 95.0];
+pub(crate) const DEFAULT_HYSTERESIS: f64 = 5.0;
+
+/// Alert rules for progress lines, as stored under `alertRules` in the settings store.
+/// Percentages are of the line's limit.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AlertRules {
+    pub enabled: bool,
+    /// Usage percentages that notify when crossed upwards.
+    pub thresholds: Vec<f64>,
+    /// Notify when a line's usage drops back after its limit resets.
+    pub notify_on_reset: bool,
+    /// Points usage must fall below a threshold before it can fire again.
+    pub hysteresis: f64,
+    pub overrides: Vec<AlertOverride>,
+}
+
+impl Default for AlertRules {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            thresholds: DEFAULT_THRESHOLDS.to_vec(),
+            notify_on_reset: true,
+            hysteresis: DEFAULT_HYSTERESIS,
+            overrides: Vec::new(),
+        }
+    }
+}
+
+/// Replaces some rule fields for one plugin, one label (across plugins), or one
+/// label of one plugin. More specific overrides win; equally specific ones apply in
+/// order.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AlertOverride {
+    pub plugin_id: Option<String>,
+    pub label: Option<String>,
+    pub enabled: Option<bool>,
+    pub thresholds: Option<Vec<f64>>,
+    pub notify_on_reset: Option<bool>,
+    pub hysteresis: Option<f64>,
+}
+
+impl AlertOverride {
+    fn matches(&self, plugin_id: &str, label: &str) -> bool {
+        self.plugin_id.as_deref().is_none_or(|id| id == plugin_id)
+            && self.label.as_deref().is_none_or(|l| l == label)
+    }
+
+    fn specificity(&self) -> usize {
+        usize::from(self.plugin_id.is_some()) + usize::from(self.label.is_some())
+    }
+}
+
+/// Effective rule for one line after overrides.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineRule {
+    pub enabled: bool,
+    pub thresholds: Vec<f64>,
+    pub notify_on_reset: bool,
+    pub hysteresis: f64,
+}
+
+impl AlertRules {
+    pub fn resolve(&self, plugin_id: &str, label: &str) -> LineRule {
+        let mut rule = LineRule {
+            enabled: self.enabled,
+            thresholds: self.thresholds.clone(),
+            notify_on_reset: self.notify_on_reset,
+            hysteresis: self.hysteresis,
+        };
+        let mut matching: Vec<&AlertOverride> = self
+            .overrides
+            .iter()
+            .filter(|o| o.matches(plugin_id, label))
+            .collect();
+        matching.sort_by_key(|o| o.specificity());
+        for o in matching {
+            if let Some(enabled) = o.enabled {
+                rule.enabled = enabled;
+            }
+            if let Some(thresholds) = &o.thresholds {
+                rule.thresholds = thresholds.clone();
+            }
+            if let Some(notify_on_reset) = o.notify_on_reset {
+                rule.notify_on_reset = notify_on_reset;
+            }
+            if let Some(hysteresis) = o.hysteresis {
+                rule.hysteresis = hysteresis;
+            }
+        }
+        rule.hysteresis = rule.hysteresis.max(0.0);
+        rule.thresholds.retain(|t| t.is_finite());
+        rule.thresholds.sort_by(f64::total_cmp);
+        rule
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum AlertKind {
+    /// Usage crossed `threshold` percent of the limit.
+    Threshold { threshold: f64 },
+    /// Usage dropped back after the limit reset.
+    Reset,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertNotification {
+    pub plugin_id: String,
+    pub label: String,
+    #[serde(flatten)]
+    pub kind: AlertKind,
+    /// Current usage, in percent of the limit.
+    pub percent: f64,
+    pub title: String,
+    pub body: String,
+}
+
+/// Where fired alerts are delivered.
+pub trait NotificationSink: Send + Sync {
+    fn notify(&self, notification: &AlertNotification);
+}
+
+/// What the engine remembers about a line between probes.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LineState {
+    percent: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    resets_at: Option<String>,
+    /// Thresholds that fired and haven't re-armed yet.
+    #[serde(default)]
+    fired: Vec<f64>,
+}
+
+/// Per plugin, per label line state.
+type AlertStates = BTreeMap<String, BTreeMap<String, LineState>>;
+
+/// Evaluates alert rules against every probe result. Line state is persisted to
+/// `alert_state.json` in the app data dir so alerts don't repeat after a restart.
+pub struct AlertEngine {
+    rules: AlertRules,
+    path: PathBuf,
+    states: AlertStates,
+    sink: Arc<dyn NotificationSink>,
+    read_only: bool,
+}
+
+impl AlertEngine {
+    pub fn new(rules: AlertRules, app_data_dir: &Path, sink: Arc<dyn NotificationSink>) -> Self {
+        let path = app_data_dir.join(ALERT_STATE_FILE);
+        let states = match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|err| {
+                log::warn!("ignoring unreadable alert state {}: {}", path.display(), err);
+                AlertStates::new()
+            }),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => AlertStates::new(),
+            Err(err) => {
+                log::warn!("failed to read alert state {}: {}", path.display(), err);
+                AlertStates::new()
+            }
+        };
+        Self {
+            rules,
+            path,
+            states,
+            sink,
+            read_only: false,
+        }
+    }
+
+    /// Track line state in memory only, for a data dir another process writes.
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    pub fn rules(&self) -> &AlertRules {
+        &self.rules
+    }
+
+    /// Swap in edited rules. Line state is kept, so thresholds that already fired
+    /// stay quiet until usage drops below them.
+    pub fn set_rules(&mut self, rules: AlertRules) {
+        self.rules = rules;
+    }
+
+    /// Check every progress line of a probe result, send what fired to the sink and
+    /// return it. Failed and stale results are ignored.
+    pub fn evaluate(&mut self, output: &PluginOutput) -> Vec<AlertNotification> {
+        if output.error.is_some() || output.stale {
+            return Vec::new();
+        }
+        let mut fired = Vec::new();
+        let mut changed = false;
+        for line in &output.lines {
+            let MetricLine::Progress {
+                label,
+                used,
+                limit,
+                resets_at,
+                ..
+            } = line
+            else {
+                continue;
+            };
+            if !used.is_finite() || !limit.is_finite() || *limit <= 0.0 {
+                continue;
+            }
+            let rule = self.rules.resolve(&output.provider_id, label);
+            let percent = used / limit * 100.0;
+            let state = self
+                .states
+                .entry(output.provider_id.clone())
+                .or_default()
+                .entry(label.clone())
+                .or_default();
+            let before = state.clone();
+            let is_new = before == LineState::default();
+
+            // A reset: usage fell back and the reset time moved on (or was never given).
+            let reset = !is_new
+                && before.percent - percent > rule.hysteresis
+                && (before.resets_at != *resets_at || resets_at.is_none());
+            if reset {
+                state.fired.clear();
+                if rule.enabled && rule.notify_on_reset {
+                    fired.push(AlertNotification {
+                        plugin_id: output.provider_id.clone(),
+                        label: label.clone(),
+                        kind: AlertKind::Reset,
+                        percent,
+                        title: format!("{} {} reset", output.display_name, label),
+                        body: format!("Usage is back to {:.0}%", percent),
+                    });
+                }
+            }
+
+            // Re-arm thresholds usage has clearly dropped below.
+            state.fired.retain(|t| percent >= t - rule.hysteresis);
+
+            if rule.enabled {
+                let crossed: Vec<f64> = rule
+                    .thresholds
+                    .iter()
+                    .copied()
+                    .filter(|t| percent >= *t && !state.fired.contains(t))
+                    .collect();
+                // Jumping past several thresholds at once only notifies for the highest.
+                if let Some(&threshold) = crossed.last() {
+                    fired.push(AlertNotification {
+                        plugin_id: output.provider_id.clone(),
+                        label: label.clone(),
+                        kind: AlertKind::Threshold { threshold },
+                        percent,
+                        title: format!("{} {} at {:.0}%", output.display_name, label, threshold),
+                        body: match resets_at {
+                            Some(at) => format!("{:.0}% used, resets at {}", percent, at),
+                            None => format!("{:.0}% used", percent),
+                        },
+                    });
+                }
+                state.fired.extend(crossed);
+            }
+
+            state.percent = percent;
+            state.resets_at = resets_at.clone();
+            changed |= *state != before;
+        }
+
+        if changed {
+            if let Err(err) = self.save() {
+                log::warn!("failed to write alert state {}: {}", self.path.display(), err);
+            }
+        }
+        for notification in &fired {
+            log::info!("alert: {}", notification.title);
+            self.sink.notify(notification);
+        }
+        fired
+    }
+
+    fn save(&self) -> std::io::Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_vec_pretty(&self.states).map_err(std::io::Error::other)?;
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(&tmp, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::runtime::ProgressFormat;
+    use crate::plugin_engine::temp_dir;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct FakeSink {
+        sent: Mutex<Vec<AlertNotification>>,
+    }
+
+    impl FakeSink {
+        fn take(&self) -> Vec<AlertNotification> {
+            std::mem::take(&mut *self.sent.lock().unwrap())
+        }
+    }
+
+    impl NotificationSink for FakeSink {
+        fn notify(&self, notification: &AlertNotification) {
+            self.sent.lock().unwrap().push(notification.clone());
+        }
+    }
+
+    fn progress(label: &str, used: f64, resets_at: &str) -> MetricLine {
+        MetricLine::Progress {
+            label: label.to_string(),
+            used,
+            limit: 100.0,
+            format: ProgressFormat::Percent,
+            resets_at: Some(resets_at.to_string()),
+            period_duration_ms: None,
+            color: None,
+            forecast: None,
+        }
+    }
+
+    fn output(plugin_id: &str, lines: Vec<MetricLine>) -> PluginOutput {
+        PluginOutput {
+            provider_id: plugin_id.to_string(),
+            display_name: "Claude".to_string(),
+            plan: None,
+            lines,
+            icon_url: String::new(),
+            error: None,
+            fetched_at: None,
+            stale: false,
+        }
+    }
+
+    fn weekly(used: f64) -> PluginOutput {
+        output("claude", vec![progress("Weekly", used, "2026-02-09T00:00:00Z")])
+    }
+
+    fn engine(dir: &Path, rules: AlertRules) -> (AlertEngine, Arc<FakeSink>) {
+        let sink = Arc::new(FakeSink::default());
+        (AlertEngine::new(rules, dir, sink.clone()), sink)
+    }
+
+    fn kinds(sent: &[AlertNotification]) -> Vec<AlertKind> {
+        sent.iter().map(|n| n.kind.clone()).collect()
+    }
+
+    #[test]
+    fn fires_each_threshold_once_with_hysteresis() {
+        let (mut engine, sink) = engine(&temp_dir("hysteresis"), AlertRules::default());
+        engine.evaluate(&weekly(50.0));
+        assert!(sink.take().is_empty());
+
+        engine.evaluate(&weekly(81.0));
+        let sent = sink.take();
+        assert_eq!(kinds(&sent), vec![AlertKind::Threshold { threshold: 80.0 }]);
+        assert_eq!(sent[0].title, "Claude Weekly at 80%");
+
+        // Hovering around the threshold doesn't re-fire until usage drops 5 points below.
+        engine.evaluate(&weekly(79.0));
+        engine.evaluate(&weekly(82.0));
+        assert!(sink.take().is_empty());
+        engine.evaluate(&weekly(74.0));
+        engine.evaluate(&weekly(80.0));
+        assert_eq!(kinds(&sink.take()), vec![AlertKind::Threshold { threshold: 80.0 }]);
+    }
+
+    #[test]
+    fn jumping_past_several_thresholds_notifies_once() {
+        let (mut engine, sink) = engine(&temp_dir("jump"), AlertRules::default());
+        engine.evaluate(&weekly(97.0));
+        assert_eq!(kinds(&sink.take()), vec![AlertKind::Threshold { threshold: 95.0 }]);
+        engine.evaluate(&weekly(99.0));
+        assert!(sink.take().is_empty());
+    }
+
+    #[test]
+    fn notifies_on_reset_and_rearms() {
+        let (mut engine, sink) = engine(&temp_dir("reset"), AlertRules::default());
+        engine.evaluate(&weekly(96.0));
+        sink.take();
+
+        let after_reset = output("claude", vec![progress("Weekly", 2.0, "2026-02-16T00:00:00Z")]);
+        let returned = engine.evaluate(&after_reset);
+        assert_eq!(kinds(&returned), vec![AlertKind::Reset]);
+        assert_eq!(sink.take(), returned);
+
+        // Same reset time with lower usage is not a reset.
+        engine.evaluate(&output("claude", vec![progress("Weekly", 90.0, "2026-02-16T00:00:00Z")]));
+        assert_eq!(kinds(&sink.take()), vec![AlertKind::Threshold { threshold: 80.0 }]);
+        engine.evaluate(&output("claude", vec![progress("Weekly", 40.0, "2026-02-16T00:00:00Z")]));
+        assert!(sink.take().is_empty());
+    }
+
+    #[test]
+    fn edited_rules_apply_to_the_next_result() {
+        let (mut engine, sink) = engine(&temp_dir("edit"), AlertRules::default());
+        engine.evaluate(&weekly(60.0));
+        engine.set_rules(AlertRules {
+            thresholds: vec![50.0],
+            ..AlertRules::default()
+        });
+        engine.evaluate(&weekly(61.0));
+        assert_eq!(kinds(&sink.take()), vec![AlertKind::Threshold { threshold: 50.0 }]);
+        engine.evaluate(&weekly(62.0));
+        assert!(sink.take().is_empty());
+    }
+
+    #[test]
+    fn overrides_apply_by_specificity() {
+        let rules = AlertRules {
+            overrides: vec![
+                AlertOverride {
+                    label: Some("Session".to_string()),
+                    thresholds: Some(vec![50.0]),
+                    ..Default::default()
+                },
+                AlertOverride {
+                    plugin_id: Some("codex".to_string()),
+                    label: Some("Session".to_string()),
+                    enabled: Some(false),
+                    ..Default::default()
+                },
+                AlertOverride {
+                    plugin_id: Some("codex".to_string()),
+                    enabled: Some(true),
+                    notify_on_reset: Some(false),
+                    ..Default::default()
+                },
+            ],
+            ..AlertRules::default()
+        };
+
+        let claude = rules.resolve("claude", "Session");
+        assert!(claude.enabled);
+        assert_eq!(claude.thresholds, vec![50.0]);
+
+        let codex_session = rules.resolve("codex", "Session");
+        assert!(!codex_session.enabled, "plugin+label beats plugin-only");
+        assert!(!codex_session.notify_on_reset);
+
+        let codex_weekly = rules.resolve("codex", "Weekly");
+        assert!(codex_weekly.enabled);
+        assert_eq!(codex_weekly.thresholds, vec![80.0, 95.0]);
+
+        let (mut engine, sink) = engine(&temp_dir("overrides"), rules);
+        engine.evaluate(&output("codex", vec![progress("Session", 99.0, "2026-02-09T00:00:00Z")]));
+        engine.evaluate(&output("claude", vec![progress("Session", 60.0, "2026-02-09T00:00:00Z")]));
+        let sent = sink.take();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].plugin_id, "claude");
+        assert_eq!(sent[0].kind, AlertKind::Threshold { threshold: 50.0 });
+    }
+
+    #[test]
+    fn state_persists_across_restarts() {
+        let dir = temp_dir("persist");
+        let (mut engine, sink) = engine(&dir, AlertRules::default());
+        engine.evaluate(&weekly(85.0));
+        assert_eq!(sink.take().len(), 1);
+
+        let (mut restarted, sink) = self::engine(&dir, AlertRules::default());
+        restarted.evaluate(&weekly(86.0));
+        assert!(sink.take().is_empty(), "already fired before the restart");
+    }
+
+    #[test]
+    fn ignores_failed_and_stale_results() {
+        let dir = temp_dir("skip");
+        let (mut engine, sink) = engine(&dir, AlertRules::default());
+        let mut stale = weekly(99.0);
+        stale.stale = true;
+        engine.evaluate(&stale);
+        assert!(sink.take().is_empty());
+        assert!(!dir.join(ALERT_STATE_FILE).exists());
+    }
+
+    #[test]
+    fn parses_partial_settings() {
+        let rules: AlertRules = serde_json::from_value(serde_json::json!({
+            "thresholds": [90],
+            "overrides": [{ "pluginId": "cursor", "enabled": false }]
+        }))
+        .unwrap();
+        assert!(rules.enabled && rules.notify_on_reset);
+        assert_eq!(rules.thresholds, vec![90.0]);
+        assert!(!rules.resolve("cursor", "Plan").enabled);
+
+        let json = serde_json::to_value(AlertNotification {
+            plugin_id: "claude".to_string(),
+            label: "Weekly".to_string(),
+            kind: AlertKind::Threshold { threshold: 80.0 },
+            percent: 81.0,
+            title: String::new(),
+            body: String::new(),
+        })
+        .unwrap();
+        assert_eq!(json["kind"], "threshold");
+        assert_eq!(json["threshold"], 80.0);
+    }
+}
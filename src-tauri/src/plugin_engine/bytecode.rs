@@ -0,0 +1,443 @@
+use crate::plugin_engine::manifest::EntryType;
+use rquickjs::module::Declared;
+use rquickjs::{qjs, Ctx, Error, Module, Object, Promise, Result, Value, WriteOptions};
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Compiled QuickJS bytecode for plugin entry files and the modules they import, shared
+/// by every probe in the process.
+///
+/// Entries are keyed by plugin id and a hash of the entry's file name and source, so a
+/// changed entry file misses and replaces the stale bytecode. Imported modules are keyed
+/// the same way by their canonical path; see [`BytecodeCache::declare_module`].
+pub struct BytecodeCache {
+    entries: Mutex<HashMap<String, CachedEntry>>,
+    modules: Mutex<HashMap<PathBuf, CachedModule>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+struct CachedEntry {
+    hash: u64,
+    bytecode: Arc<[u8]>,
+}
+
+struct CachedModule {
+    /// Whose import this is, for [`BytecodeCache::invalidate`].
+    plugin_id: String,
+    entry: CachedEntry,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// What evaluating an entry produced. Modules hand back their evaluation promise
+/// (which settles once top-level `await`s finish) and their namespace object.
+pub enum EvaluatedEntry<'js> {
+    Script,
+    Module {
+        promise: Promise<'js>,
+        namespace: Object<'js>,
+    },
+}
+
+impl Default for BytecodeCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BytecodeCache {
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            modules: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// The process-wide cache used by `run_probe`.
+    pub fn shared() -> &'static BytecodeCache {
+        static SHARED: OnceLock<BytecodeCache> = OnceLock::new();
+        SHARED.get_or_init(BytecodeCache::new)
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Cached entries plus cached imported modules.
+    pub fn len(&self) -> usize {
+        let entries = self.entries.lock().map(|entries| entries.len()).unwrap_or(0);
+        let modules = self.modules.lock().map(|modules| modules.len()).unwrap_or(0);
+        entries + modules
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop the cached bytecode for one plugin and its imports, e.g. after it was removed.
+    pub fn invalidate(&self, plugin_id: &str) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.remove(plugin_id);
+        }
+        if let Ok(mut modules) = self.modules.lock() {
+            modules.retain(|_, module| module.plugin_id != plugin_id);
+        }
+    }
+
+    fn lookup(&self, plugin_id: &str, hash: u64) -> Option<Arc<[u8]>> {
+        let entries = self.entries.lock().ok()?;
+        entries
+            .get(plugin_id)
+            .filter(|entry| entry.hash == hash)
+            .map(|entry| Arc::clone(&entry.bytecode))
+    }
+
+    fn store(&self, plugin_id: &str, hash: u64, bytecode: Vec<u8>) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.insert(
+                plugin_id.to_string(),
+                CachedEntry {
+                    hash,
+                    bytecode: bytecode.into(),
+                },
+            );
+        }
+    }
+
+    fn lookup_module(&self, path: &Path, hash: u64) -> Option<Arc<[u8]>> {
+        let modules = self.modules.lock().ok()?;
+        modules
+            .get(path)
+            .filter(|module| module.entry.hash == hash)
+            .map(|module| Arc::clone(&module.entry.bytecode))
+    }
+
+    fn store_module(&self, plugin_id: &str, path: &Path, hash: u64, bytecode: Vec<u8>) {
+        if let Ok(mut modules) = self.modules.lock() {
+            modules.insert(
+                path.to_path_buf(),
+                CachedModule {
+                    plugin_id: plugin_id.to_string(),
+                    entry: CachedEntry {
+                        hash,
+                        bytecode: bytecode.into(),
+                    },
+                },
+            );
+        }
+    }
+
+    /// Declare a module imported by a plugin, from cached bytecode when its source is
+    /// unchanged and compiling (then caching) it otherwise. `path` is the module's
+    /// canonical path and `name` its module name, which stack frames report.
+    pub fn declare_module<'js>(
+        &self,
+        ctx: &Ctx<'js>,
+        plugin_id: &str,
+        path: &Path,
+        name: &str,
+        source: String,
+    ) -> Result<Module<'js, Declared>> {
+        let hash = content_hash(EntryType::Module, name, &source);
+        if let Some(bytecode) = self.lookup_module(path, hash) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            // Safety: the bytes were produced by `Module::write` in this process, so they
+            // match this QuickJS build and were never read from disk.
+            return unsafe { Module::load(ctx.clone(), &bytecode) };
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let module = Module::declare(ctx.clone(), name, source)?;
+        match module.write(WriteOptions::default()) {
+            Ok(bytecode) => self.store_module(plugin_id, path, hash, bytecode),
+            Err(err) => {
+                // The declared module is still usable; drop the exception.
+                let _ = ctx.catch();
+                log::warn!("[plugin:{}] bytecode of {} not cached: {}", plugin_id, name, err);
+            }
+        }
+        Ok(module)
+    }
+
+    /// Evaluate a plugin entry, loading it from cached bytecode when the source is
+    /// unchanged and compiling (then caching) it otherwise. `file_name` is what stack
+    /// frames report, so it must be stable for a given entry.
+    ///
+    /// On a JS exception this returns `Error::Exception` with the exception left
+    /// pending on `ctx`, just like evaluating from source.
+    pub fn eval_entry<'js>(
+        &self,
+        ctx: &Ctx<'js>,
+        plugin_id: &str,
+        entry_type: EntryType,
+        file_name: &str,
+        source: &str,
+    ) -> Result<EvaluatedEntry<'js>> {
+        let hash = content_hash(entry_type, file_name, source);
+        let compiled = match self.lookup(plugin_id, hash) {
+            Some(bytecode) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                read_bytecode(ctx, &bytecode, entry_type)?
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                let compiled = compile(ctx, source, file_name, entry_type)?;
+                match write_bytecode(ctx, compiled) {
+                    Ok(bytecode) => self.store(plugin_id, hash, bytecode),
+                    Err(err) => log::warn!("[plugin:{}] bytecode not cached: {}", plugin_id, err),
+                }
+                compiled
+            }
+        };
+        run_compiled(ctx, compiled, entry_type)
+    }
+}
+
+/// Hash of everything that goes into the compiled bytecode.
+pub fn content_hash(entry_type: EntryType, file_name: &str, source: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    entry_type.hash(&mut hasher);
+    file_name.hash(&mut hasher);
+    source.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Compile without running. Scripts compile to a function, modules to a module record
+/// whose imports have been resolved through the runtime's loader.
+fn compile(ctx: &Ctx<'_>, source: &str, file_name: &str, entry_type: EntryType) -> Result<qjs::JSValue> {
+    let len = source.len();
+    let source = CString::new(source)?;
+    let file_name = CString::new(file_name)?;
+    let eval_type = match entry_type {
+        EntryType::Script => qjs::JS_EVAL_TYPE_GLOBAL,
+        EntryType::Module => qjs::JS_EVAL_TYPE_MODULE,
+    };
+    let flags = (eval_type | qjs::JS_EVAL_FLAG_STRICT | qjs::JS_EVAL_FLAG_COMPILE_ONLY) as i32;
+    let raw = unsafe {
+        qjs::JS_Eval(
+            ctx.as_raw().as_ptr(),
+            source.as_ptr(),
+            len as _,
+            file_name.as_ptr(),
+            flags,
+        )
+    };
+    if unsafe { qjs::JS_IsException(raw) } {
+        return Err(Error::Exception);
+    }
+    Ok(raw)
+}
+
+fn write_bytecode(ctx: &Ctx<'_>, compiled: qjs::JSValue) -> Result<Vec<u8>> {
+    let ctx_ptr = ctx.as_raw().as_ptr();
+    let mut len = 0;
+    let buf = unsafe {
+        qjs::JS_WriteObject(ctx_ptr, &mut len, compiled, qjs::JS_WRITE_OBJ_BYTECODE as i32)
+    };
+    if buf.is_null() {
+        // Serializing failed but the compiled value is still usable; drop the exception.
+        let _ = ctx.catch();
+        return Err(Error::new_from_js_message("bytecode", "buffer", "JS_WriteObject failed"));
+    }
+    let bytecode = unsafe { std::slice::from_raw_parts(buf, len as _) }.to_vec();
+    unsafe { qjs::js_free(ctx_ptr, buf.cast()) };
+    Ok(bytecode)
+}
+
+fn read_bytecode(ctx: &Ctx<'_>, bytecode: &[u8], entry_type: EntryType) -> Result<qjs::JSValue> {
+    let ctx_ptr = ctx.as_raw().as_ptr();
+    // Safety: the bytes were produced by `write_bytecode` in this process, so they
+    // match this QuickJS build and were never read from disk.
+    let raw = unsafe {
+        qjs::JS_ReadObject(
+            ctx_ptr,
+            bytecode.as_ptr(),
+            bytecode.len() as _,
+            qjs::JS_READ_OBJ_BYTECODE as i32,
+        )
+    };
+    if unsafe { qjs::JS_IsException(raw) } {
+        return Err(Error::Exception);
+    }
+    // A module read back from bytecode still has to resolve (and load) its imports.
+    if entry_type == EntryType::Module && unsafe { qjs::JS_ResolveModule(ctx_ptr, raw) } < 0 {
+        return Err(Error::Exception);
+    }
+    Ok(raw)
+}
+
+fn run_compiled<'js>(
+    ctx: &Ctx<'js>,
+    compiled: qjs::JSValue,
+    entry_type: EntryType,
+) -> Result<EvaluatedEntry<'js>> {
+    let ctx_ptr = ctx.as_raw().as_ptr();
+    let module_def = match entry_type {
+        EntryType::Module => Some(unsafe { qjs::JS_VALUE_GET_PTR(compiled) } as *mut qjs::JSModuleDef),
+        EntryType::Script => None,
+    };
+    // JS_EvalFunction takes ownership of `compiled`.
+    let result = unsafe { Value::from_raw(ctx.clone(), qjs::JS_EvalFunction(ctx_ptr, compiled)) };
+    if result.is_exception() {
+        return Err(Error::Exception);
+    }
+    let Some(module_def) = module_def else {
+        return Ok(EvaluatedEntry::Script);
+    };
+    let promise = result
+        .into_promise()
+        .ok_or_else(|| Error::new_from_js("value", "promise"))?;
+    let namespace = unsafe { Value::from_raw(ctx.clone(), qjs::JS_GetModuleNamespace(ctx_ptr, module_def)) };
+    if namespace.is_exception() {
+        return Err(Error::Exception);
+    }
+    let namespace = namespace
+        .into_object()
+        .ok_or_else(|| Error::new_from_js("value", "object"))?;
+    Ok(EvaluatedEntry::Module { promise, namespace })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rquickjs::{Context, Runtime};
+
+    fn with_ctx<F: FnOnce(&Ctx<'_>)>(f: F) {
+        let rt = Runtime::new().expect("runtime");
+        let ctx = Context::full(&rt).expect("context");
+        ctx.with(|ctx| f(&ctx));
+    }
+
+    const SCRIPT: &str = "globalThis.answer = (globalThis.answer || 0) + 42;";
+
+    #[test]
+    fn second_eval_loads_cached_bytecode() {
+        let cache = BytecodeCache::new();
+        for _ in 0..2 {
+            with_ctx(|ctx| {
+                cache
+                    .eval_entry(ctx, "p", EntryType::Script, "plugin.js", SCRIPT)
+                    .expect("eval");
+                let answer: i32 = ctx.globals().get("answer").unwrap();
+                assert_eq!(answer, 42);
+            });
+        }
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 1 });
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn changed_source_recompiles_and_replaces_entry() {
+        let cache = BytecodeCache::new();
+        with_ctx(|ctx| {
+            cache
+                .eval_entry(ctx, "p", EntryType::Script, "plugin.js", "globalThis.v = 1;")
+                .unwrap();
+        });
+        with_ctx(|ctx| {
+            cache
+                .eval_entry(ctx, "p", EntryType::Script, "plugin.js", "globalThis.v = 2;")
+                .unwrap();
+            let v: i32 = ctx.globals().get("v").unwrap();
+            assert_eq!(v, 2);
+        });
+        assert_eq!(cache.stats(), CacheStats { hits: 0, misses: 2 });
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn cached_script_keeps_strict_mode_and_file_name() {
+        let cache = BytecodeCache::new();
+        let source = "function probe() {\n  undeclared = 1;\n}\nprobe();";
+        for _ in 0..2 {
+            with_ctx(|ctx| {
+                let err = cache.eval_entry(ctx, "p", EntryType::Script, "plugin.js", source);
+                assert!(matches!(err, Err(Error::Exception)));
+                let exception = ctx.catch();
+                let stack: String = exception.as_object().unwrap().get("stack").unwrap();
+                assert!(stack.contains("plugin.js:2"), "stack: {}", stack);
+            });
+        }
+        assert_eq!(cache.stats().hits, 1);
+    }
+
+    #[test]
+    fn syntax_error_is_not_cached() {
+        let cache = BytecodeCache::new();
+        with_ctx(|ctx| {
+            let result = cache.eval_entry(ctx, "p", EntryType::Script, "plugin.js", "function (");
+            assert!(matches!(result, Err(Error::Exception)));
+            let _ = ctx.catch();
+        });
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn cached_module_exposes_namespace() {
+        let cache = BytecodeCache::new();
+        for _ in 0..2 {
+            with_ctx(|ctx| {
+                let evaluated = cache
+                    .eval_entry(ctx, "m", EntryType::Module, "plugin.js", "export default { id: 'm' };")
+                    .expect("eval");
+                let EvaluatedEntry::Module { namespace, .. } = evaluated else {
+                    panic!("expected module");
+                };
+                let default: Object = namespace.get("default").unwrap();
+                assert_eq!(default.get::<_, String>("id").unwrap(), "m");
+            });
+        }
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 1 });
+    }
+
+    #[test]
+    fn imported_modules_are_cached_by_path_and_content() {
+        use crate::plugin_engine::modules::PluginModules;
+        use crate::plugin_engine::temp_dir;
+
+        let cache: &'static BytecodeCache = Box::leak(Box::new(BytecodeCache::new()));
+        let dir = temp_dir("imports");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("util.js"), "export const n = 1;").unwrap();
+        let run = || {
+            let rt = Runtime::new().expect("runtime");
+            let modules = PluginModules::new(&dir).with_cache("p", cache);
+            rt.set_loader(modules.clone(), modules);
+            let ctx = Context::full(&rt).expect("context");
+            ctx.with(|ctx| {
+                let evaluated = cache
+                    .eval_entry(&ctx, "p", EntryType::Module, "plugin.js", "import { n } from './util.js'; export default n;")
+                    .expect("eval");
+                let EvaluatedEntry::Module { namespace, .. } = evaluated else {
+                    panic!("expected module");
+                };
+                namespace.get::<_, i32>("default").unwrap()
+            })
+        };
+
+        assert_eq!(run(), 1);
+        assert_eq!(cache.stats(), CacheStats { hits: 0, misses: 2 });
+        assert_eq!(run(), 1);
+        assert_eq!(cache.stats(), CacheStats { hits: 2, misses: 2 }, "entry and import from bytecode");
+        std::fs::write(dir.join("util.js"), "export const n = 2;").unwrap();
+        assert_eq!(run(), 2);
+        assert_eq!(cache.stats(), CacheStats { hits: 3, misses: 3 }, "only the changed import recompiles");
+        assert_eq!(cache.len(), 2);
+        cache.invalidate("p");
+        assert!(cache.is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,257 @@
+use crate::plugin_engine::error::{ProbeError, ProbeErrorKind};
+use crate::plugin_engine::manifest::HttpPermission;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+
+/// Where a plugin's `host.http` requests may go: the schemes and hosts declared under
+/// `permissions.http` in `plugin.json`, and only to public addresses unless the app was
+/// started with the private-network opt-in.
+#[derive(Debug, Clone)]
+pub struct EgressPolicy {
+    plugin_id: String,
+    hosts: Vec<String>,
+    schemes: Vec<String>,
+    allow_private_network: bool,
+}
+
+/// Why a request was refused; surfaced to plugins as a `permission` error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EgressDenial {
+    Scheme(String),
+    Host(String),
+    PrivateAddress { host: String, addr: IpAddr },
+}
+
+impl std::fmt::Display for EgressDenial {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Scheme(scheme) => write!(f, "http scheme {} is not declared in plugin.json", scheme),
+            Self::Host(host) => write!(f, "http host {} is not declared in plugin.json", host),
+            Self::PrivateAddress { host, addr } => {
+                write!(f, "http host {} resolves to non-public address {}", host, addr)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EgressDenial {}
+
+impl EgressDenial {
+    pub fn into_probe_error(self) -> ProbeError {
+        ProbeError::new(ProbeErrorKind::Permission, self.to_string())
+    }
+}
+
+impl EgressPolicy {
+    pub fn new(plugin_id: &str, declared: &HttpPermission, allow_private_network: bool) -> Self {
+        Self {
+            plugin_id: plugin_id.to_string(),
+            hosts: declared.hosts.iter().map(|host| host.to_ascii_lowercase()).collect(),
+            schemes: declared.schemes.iter().map(|scheme| scheme.to_ascii_lowercase()).collect(),
+            allow_private_network,
+        }
+    }
+
+    /// Check a request URL before connecting. Host names are checked again once resolved,
+    /// by [`Self::resolver`].
+    pub fn check_url(&self, url: &reqwest::Url) -> Result<(), EgressDenial> {
+        let result = self.check_url_inner(url);
+        if let Err(denial) = &result {
+            log::warn!("[plugin:{}] blocked request: {}", self.plugin_id, denial);
+        }
+        result
+    }
+
+    fn check_url_inner(&self, url: &reqwest::Url) -> Result<(), EgressDenial> {
+        if !self.schemes.iter().any(|scheme| scheme == url.scheme()) {
+            return Err(EgressDenial::Scheme(url.scheme().to_string()));
+        }
+        // IPv6 literals come bracketed, e.g. `[::1]`.
+        let host = url
+            .host_str()
+            .unwrap_or_default()
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_ascii_lowercase();
+        if !self.hosts.iter().any(|pattern| host_matches(pattern, &host)) {
+            return Err(EgressDenial::Host(host));
+        }
+        // IP literals never go through the resolver.
+        if let Ok(addr) = host.parse::<IpAddr>() {
+            if !self.allow_private_network && !is_public(addr) {
+                return Err(EgressDenial::PrivateAddress { host, addr });
+            }
+        }
+        Ok(())
+    }
+
+    /// DNS resolver that drops non-public addresses, so a declared host can't be pointed
+    /// at the local network. `None` when the private-network opt-in is on.
+    pub fn resolver(&self) -> Option<Arc<PublicResolver>> {
+        (!self.allow_private_network).then(|| Arc::new(PublicResolver))
+    }
+}
+
+/// `pattern` is a host name, or `*.example.com` for any subdomain of `example.com`.
+fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain)
+            .is_some_and(|subdomain| subdomain.len() > 1 && subdomain.ends_with('.')),
+        None => pattern == host,
+    }
+}
+
+/// Whether `addr` is routable on the public internet.
+pub fn is_public(addr: IpAddr) -> bool {
+    match addr {
+        IpAddr::V4(addr) => is_public_v4(addr),
+        IpAddr::V6(addr) => match addr.to_ipv4_mapped() {
+            Some(mapped) => is_public_v4(mapped),
+            None => is_public_v6(addr),
+        },
+    }
+}
+
+fn is_public_v4(addr: Ipv4Addr) -> bool {
+    let [a, b, ..] = addr.octets();
+    !(addr.is_private()
+        || addr.is_loopback()
+        || addr.is_link_local()
+        || addr.is_unspecified()
+        || addr.is_broadcast()
+        || addr.is_multicast()
+        || a == 0
+        // Carrier-grade NAT, 100.64.0.0/10.
+        || (a == 100 && (64..128).contains(&b)))
+}
+
+fn is_public_v6(addr: Ipv6Addr) -> bool {
+    let first = addr.segments()[0];
+    !(addr.is_loopback()
+        || addr.is_unspecified()
+        || addr.is_multicast()
+        // Unique local fc00::/7 and link-local fe80::/10.
+        || (first & 0xfe00) == 0xfc00
+        || (first & 0xffc0) == 0xfe80)
+}
+
+/// System resolver that keeps only public addresses.
+#[derive(Debug)]
+pub struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let resolved: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            let public: Vec<SocketAddr> = resolved.iter().copied().filter(|addr| is_public(addr.ip())).collect();
+            match (public.is_empty(), resolved.first()) {
+                (true, Some(blocked)) => Err(Box::new(EgressDenial::PrivateAddress {
+                    host,
+                    addr: blocked.ip(),
+                }) as Box<dyn std::error::Error + Send + Sync>),
+                _ => Ok(Box::new(public.into_iter()) as Addrs),
+            }
+        })
+    }
+}
+
+/// The denial behind a failed request, if the resolver refused the host.
+pub fn denial_in(err: &(dyn std::error::Error + 'static)) -> Option<EgressDenial> {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if let Some(denial) = err.downcast_ref::<EgressDenial>() {
+            return Some(denial.clone());
+        }
+        source = err.source();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(allow_private_network: bool) -> EgressPolicy {
+        let declared: HttpPermission = serde_json::from_str(
+            r#"{ "hosts": ["api.example.com", "*.cursor.sh", "10.0.0.5", "localhost"] }"#,
+        )
+        .unwrap();
+        EgressPolicy::new("x", &declared, allow_private_network)
+    }
+
+    fn check(policy: &EgressPolicy, url: &str) -> Result<(), EgressDenial> {
+        policy.check_url(&reqwest::Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn allows_declared_hosts_and_schemes_only() {
+        let policy = policy(false);
+        assert!(check(&policy, "https://api.example.com/v1/usage").is_ok());
+        assert!(check(&policy, "https://API.example.com:8443/").is_ok());
+        assert!(check(&policy, "https://api2.cursor.sh/x").is_ok());
+        assert!(check(&policy, "https://a.b.cursor.sh/x").is_ok());
+        assert_eq!(
+            check(&policy, "https://cursor.sh/"),
+            Err(EgressDenial::Host("cursor.sh".to_string()))
+        );
+        assert!(check(&policy, "https://evilcursor.sh/").is_err());
+        assert!(check(&policy, "https://api.example.com.evil.io/").is_err());
+        assert_eq!(
+            check(&policy, "http://api.example.com/"),
+            Err(EgressDenial::Scheme("http".to_string()))
+        );
+        assert!(check(&policy, "file:///etc/passwd").is_err());
+    }
+
+    #[test]
+    fn blocks_private_literals_unless_opted_in() {
+        let denial = check(&policy(false), "https://10.0.0.5/").unwrap_err();
+        assert_eq!(denial.to_string(), "http host 10.0.0.5 resolves to non-public address 10.0.0.5");
+        assert_eq!(denial.into_probe_error().kind, ProbeErrorKind::Permission);
+        assert!(check(&policy(true), "https://10.0.0.5/").is_ok());
+        assert!(policy(false).resolver().is_some());
+        assert!(policy(true).resolver().is_none());
+    }
+
+    #[test]
+    fn classifies_addresses() {
+        for addr in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public(addr.parse().unwrap()), "{} should not be public", addr);
+        }
+        for addr in ["1.1.1.1", "100.128.0.1", "2606:4700::1111", "::ffff:8.8.8.8"] {
+            assert!(is_public(addr.parse().unwrap()), "{} should be public", addr);
+        }
+    }
+
+    #[test]
+    fn resolver_refuses_hosts_without_public_addresses() {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let err = runtime
+            .block_on(async {
+                let name: Name = "localhost".parse().unwrap();
+                PublicResolver.resolve(name).await
+            })
+            .err()
+            .expect("localhost should be refused");
+        assert!(matches!(
+            denial_in(err.as_ref()),
+            Some(EgressDenial::PrivateAddress { host, .. }) if host == "localhost"
+        ));
+    }
+}
@@ -0,0 +1,132 @@
+use serde::{Deserialize, Serialize};
+
+/// Class of a probe failure, so callers can react without parsing display text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProbeErrorKind {
+    /// Credentials are missing, expired or rejected.
+    Auth,
+    /// Connection, DNS or TLS failure talking to the provider.
+    Network,
+    /// The probe or one of its host calls ran out of time.
+    Timeout,
+    /// The provider answered with something the plugin could not understand.
+    Parse,
+    /// The provider reported a failure (e.g. HTTP 5xx, quota API disabled).
+    Provider,
+    /// The plugin itself is broken (bad output, uncaught exception, limits exceeded).
+    PluginBug,
+    /// The plugin or a host API it needs does not work on this OS.
+    UnsupportedPlatform,
+    /// The plugin used a path, secret or database its manifest does not declare.
+    Permission,
+    /// The app failed to set up or run the sandbox.
+    Internal,
+    /// The plugin threw a plain message without classifying it.
+    Unknown,
+}
+
+impl ProbeErrorKind {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "auth" => Some(Self::Auth),
+            "network" => Some(Self::Network),
+            "timeout" => Some(Self::Timeout),
+            "parse" => Some(Self::Parse),
+            "provider" => Some(Self::Provider),
+            "plugin_bug" => Some(Self::PluginBug),
+            "unsupported_platform" => Some(Self::UnsupportedPlatform),
+            "permission" => Some(Self::Permission),
+            "internal" => Some(Self::Internal),
+            "unknown" => Some(Self::Unknown),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Auth => "auth",
+            Self::Network => "network",
+            Self::Timeout => "timeout",
+            Self::Parse => "parse",
+            Self::Provider => "provider",
+            Self::PluginBug => "plugin_bug",
+            Self::UnsupportedPlatform => "unsupported_platform",
+            Self::Permission => "permission",
+            Self::Internal => "internal",
+            Self::Unknown => "unknown",
+        }
+    }
+
+    /// Whether retrying without user action is likely to help.
+    pub fn default_retryable(&self) -> bool {
+        !matches!(
+            self,
+            Self::Auth | Self::PluginBug | Self::UnsupportedPlatform | Self::Permission
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProbeError {
+    pub kind: ProbeErrorKind,
+    pub message: String,
+    pub retryable: bool,
+    /// JS stack trace of the uncaught exception, when there was one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stack: Option<String>,
+}
+
+impl ProbeError {
+    pub fn new(kind: ProbeErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            retryable: kind.default_retryable(),
+            stack: None,
+        }
+    }
+}
+
+impl std::fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.kind.as_str(), self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kind_round_trips_through_wire_name() {
+        for kind in [
+            ProbeErrorKind::Auth,
+            ProbeErrorKind::Network,
+            ProbeErrorKind::Timeout,
+            ProbeErrorKind::Parse,
+            ProbeErrorKind::Provider,
+            ProbeErrorKind::PluginBug,
+            ProbeErrorKind::UnsupportedPlatform,
+            ProbeErrorKind::Permission,
+            ProbeErrorKind::Internal,
+            ProbeErrorKind::Unknown,
+        ] {
+            let json = serde_json::to_value(kind).unwrap();
+            assert_eq!(json, kind.as_str());
+            assert_eq!(ProbeErrorKind::parse(kind.as_str()), Some(kind));
+        }
+        assert_eq!(ProbeErrorKind::parse("bogus"), None);
+    }
+
+    #[test]
+    fn serializes_with_retryable_default() {
+        let err = ProbeError::new(ProbeErrorKind::Auth, "Token expired.");
+        let json = serde_json::to_value(&err).unwrap();
+        assert_eq!(json["kind"], "auth");
+        assert_eq!(json["message"], "Token expired.");
+        assert_eq!(json["retryable"], false);
+        assert!(ProbeError::new(ProbeErrorKind::Network, "x").retryable);
+    }
+}
@@ -0,0 +1,517 @@
+//! Exhaustion forecasts for progress lines. Burn rates are fitted to samples of their
+//! own ([`ForecastSamples`]), kept at probe resolution for the current period only:
+//! the usage history thins older samples to one per hour or day, which would skew the
+//! fit towards the most recent hours.
+
+use crate::plugin_engine::history::UsageSample;
+use crate::plugin_engine::runtime::{MetricLine, PluginOutput};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+const HOUR_MS: f64 = 60.0 * 60.0 * 1000.0;
+/// Fallback estimates need at least this much of the period behind them.
+const MIN_ELAPSED_FRACTION: f64 = 0.05;
+/// Samples spanning this share of the period give full coverage confidence.
+const FULL_COVERAGE_FRACTION: f64 = 0.25;
+/// Coverage target when the line has no period duration.
+const FULL_COVERAGE_MS: f64 = 6.0 * HOUR_MS;
+
+pub const FORECAST_SAMPLES_FILE: &str = "forecast_samples.json";
+/// Newest samples kept per line, about three and a half days of 5-minute probes.
+const MAX_SAMPLES_PER_LINE: usize = 1000;
+
+/// How usage is tracking against the limit by the next reset, using the same bands
+/// as the card's pace dot: up to 80% of the limit is ahead, up to 100% on track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PaceStatus {
+    Ahead,
+    OnTrack,
+    OverPace,
+}
+
+/// Projection for one progress line until its `resetsAt`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Forecast {
+    pub status: PaceStatus,
+    /// Fitted consumption rate, in the line's own unit per hour.
+    pub burn_rate_per_hour: f64,
+    /// Usage expected at reset if the burn rate holds.
+    pub projected_usage: f64,
+    /// RFC 3339 time the limit is reached, if that happens before the reset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exhausts_at: Option<String>,
+    /// 0..1; grows with the number of samples, the time they span and how well a
+    /// straight line fits them.
+    pub confidence: f64,
+    /// Samples the burn rate was fitted to; 0 means it was estimated from the
+    /// period average.
+    pub sample_count: usize,
+}
+
+/// Forecast a progress line from its earlier samples. Only samples from the current
+/// period are used: those after the period start, and after the last drop in usage.
+///
+/// Returns None without a future reset time, a positive limit, or enough data: at
+/// least three samples, or 5% of a known period elapsed.
+pub fn forecast(
+    samples: &[UsageSample],
+    used: f64,
+    limit: f64,
+    resets_at: Option<&str>,
+    period_duration_ms: Option<u64>,
+    now_ms: i64,
+) -> Option<Forecast> {
+    if !used.is_finite() || !limit.is_finite() || limit <= 0.0 {
+        return None;
+    }
+    let reset_ms = parse_ms(resets_at?)?;
+    if reset_ms <= now_ms {
+        return None;
+    }
+    let period_start_ms = period_duration_ms.map(|period| reset_ms - period as i64);
+
+    let points = current_period(samples, used, now_ms, period_start_ms);
+    let (rate_per_ms, confidence, sample_count) = match fit(&points) {
+        Some((slope, r2)) if points.len() >= 3 => {
+            let span = (points[points.len() - 1].0 - points[0].0) as f64;
+            let target = period_duration_ms
+                .map(|period| period as f64 * FULL_COVERAGE_FRACTION)
+                .unwrap_or(FULL_COVERAGE_MS);
+            let count_factor = 1.0 - 1.0 / (points.len() - 1) as f64;
+            let coverage = (span / target).min(1.0);
+            (slope.max(0.0), r2 * count_factor * coverage, points.len())
+        }
+        _ => {
+            let period = period_duration_ms? as f64;
+            let elapsed = (now_ms - period_start_ms?) as f64;
+            let fraction = elapsed / period;
+            if fraction < MIN_ELAPSED_FRACTION {
+                return None;
+            }
+            (used.max(0.0) / elapsed, 0.25 * fraction.min(1.0), 0)
+        }
+    };
+
+    let projected_usage = used + rate_per_ms * (reset_ms - now_ms) as f64;
+    let exhausts_at = if used >= limit {
+        Some(now_ms)
+    } else if rate_per_ms > 0.0 {
+        let at = now_ms + ((limit - used) / rate_per_ms).round() as i64;
+        (at < reset_ms).then_some(at)
+    } else {
+        None
+    };
+    let status = if projected_usage <= limit * 0.8 {
+        PaceStatus::Ahead
+    } else if projected_usage <= limit {
+        PaceStatus::OnTrack
+    } else {
+        PaceStatus::OverPace
+    };
+
+    Some(Forecast {
+        status,
+        burn_rate_per_hour: rate_per_ms * HOUR_MS,
+        projected_usage,
+        exhausts_at: exhausts_at.and_then(format_ms),
+        confidence: confidence.clamp(0.0, 1.0),
+        sample_count,
+    })
+}
+
+/// Attach a forecast to every progress line of a successful output, fitted to the
+/// earlier samples of that line, then add the output's values to `samples`.
+pub fn attach_forecasts(output: &mut PluginOutput, samples: &mut ForecastSamples, now_ms: i64) {
+    if output.error.is_some() || output.stale {
+        return;
+    }
+    for line in &mut output.lines {
+        if let MetricLine::Progress {
+            label,
+            used,
+            limit,
+            resets_at,
+            period_duration_ms,
+            forecast: slot,
+            ..
+        } = line
+        {
+            *slot = forecast(
+                samples.get(&output.provider_id, label),
+                *used,
+                *limit,
+                resets_at.as_deref(),
+                *period_duration_ms,
+                now_ms,
+            );
+            let period_start_ms = resets_at
+                .as_deref()
+                .and_then(parse_ms)
+                .zip(*period_duration_ms)
+                .map(|(reset_ms, period)| reset_ms - period as i64);
+            samples.record(
+                &output.provider_id,
+                label,
+                UsageSample {
+                    timestamp_ms: now_ms,
+                    used: *used,
+                    limit: *limit,
+                    resets_at: None,
+                },
+                period_start_ms,
+            );
+        }
+    }
+}
+
+/// Every probed value of each progress line in its current period, persisted as JSON
+/// under the app data dir so forecasts survive a restart. Samples from before the
+/// period start or a drop in usage are discarded as new ones come in.
+#[derive(Debug)]
+pub struct ForecastSamples {
+    path: PathBuf,
+    /// Samples by plugin id, then line label, oldest first.
+    series: BTreeMap<String, BTreeMap<String, Vec<UsageSample>>>,
+    dirty: bool,
+    read_only: bool,
+}
+
+impl ForecastSamples {
+    /// Load the samples file in `app_data_dir`. A missing or unreadable file starts empty.
+    pub fn load(app_data_dir: &Path) -> Self {
+        let path = app_data_dir.join(FORECAST_SAMPLES_FILE);
+        let series = match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|err| {
+                log::warn!(
+                    "ignoring unreadable forecast samples {}: {}",
+                    path.display(),
+                    err
+                );
+                BTreeMap::new()
+            }),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => {
+                log::warn!(
+                    "failed to read forecast samples {}: {}",
+                    path.display(),
+                    err
+                );
+                BTreeMap::new()
+            }
+        };
+        Self {
+            path,
+            series,
+            dirty: false,
+            read_only: false,
+        }
+    }
+
+    /// Never write the file, for a data dir another process writes.
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    /// Samples of one line, oldest first.
+    pub fn get(&self, plugin_id: &str, label: &str) -> &[UsageSample] {
+        self.series
+            .get(plugin_id)
+            .and_then(|lines| lines.get(label))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    fn record(
+        &mut self,
+        plugin_id: &str,
+        label: &str,
+        sample: UsageSample,
+        period_start_ms: Option<i64>,
+    ) {
+        let samples = self
+            .series
+            .entry(plugin_id.to_string())
+            .or_default()
+            .entry(label.to_string())
+            .or_default();
+        samples.retain(|old| {
+            old.timestamp_ms < sample.timestamp_ms
+                && period_start_ms.is_none_or(|start| old.timestamp_ms >= start)
+        });
+        // Usage going down means the limit reset; the old period is no use any more.
+        if samples.last().is_some_and(|last| sample.used < last.used) {
+            samples.clear();
+        }
+        samples.push(sample);
+        if samples.len() > MAX_SAMPLES_PER_LINE {
+            samples.drain(..samples.len() - MAX_SAMPLES_PER_LINE);
+        }
+        self.dirty = true;
+    }
+
+    /// Write the samples if anything was recorded since the last save.
+    pub fn save(&mut self) -> std::io::Result<()> {
+        if !self.dirty || self.read_only {
+            return Ok(());
+        }
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_vec(&self.series).map_err(std::io::Error::other)?;
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(&tmp, &self.path)?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+/// (timestamp, used) points of the current period, ending with the current value.
+fn current_period(
+    samples: &[UsageSample],
+    used: f64,
+    now_ms: i64,
+    period_start_ms: Option<i64>,
+) -> Vec<(i64, f64)> {
+    let mut points: Vec<(i64, f64)> = samples
+        .iter()
+        .filter(|sample| sample.timestamp_ms < now_ms)
+        .filter(|sample| period_start_ms.is_none_or(|start| sample.timestamp_ms >= start))
+        .map(|sample| (sample.timestamp_ms, sample.used))
+        .collect();
+    points.push((now_ms, used));
+    // A drop in usage means the limit reset in between; only the newest run counts.
+    let start = points
+        .windows(2)
+        .rposition(|pair| pair[1].1 < pair[0].1)
+        .map(|index| index + 1)
+        .unwrap_or(0);
+    points.split_off(start)
+}
+
+/// Least-squares slope (per ms) and r² of usage over time.
+fn fit(points: &[(i64, f64)]) -> Option<(f64, f64)> {
+    if points.len() < 2 {
+        return None;
+    }
+    let n = points.len() as f64;
+    let origin = points[0].0;
+    let mean_t = points.iter().map(|(t, _)| (t - origin) as f64).sum::<f64>() / n;
+    let mean_u = points.iter().map(|(_, u)| u).sum::<f64>() / n;
+    let (mut cov, mut var_t, mut var_u) = (0.0, 0.0, 0.0);
+    for (t, u) in points {
+        let dt = (t - origin) as f64 - mean_t;
+        let du = u - mean_u;
+        cov += dt * du;
+        var_t += dt * dt;
+        var_u += du * du;
+    }
+    if var_t == 0.0 {
+        return None;
+    }
+    // Flat usage is fitted perfectly by a zero slope.
+    let r2 = if var_u == 0.0 {
+        1.0
+    } else {
+        cov * cov / (var_t * var_u)
+    };
+    Some((cov / var_t, r2))
+}
+
+fn parse_ms(value: &str) -> Option<i64> {
+    let parsed = OffsetDateTime::parse(value, &Rfc3339).ok()?;
+    Some((parsed.unix_timestamp_nanos() / 1_000_000) as i64)
+}
+
+fn format_ms(ms: i64) -> Option<String> {
+    OffsetDateTime::from_unix_timestamp_nanos(ms as i128 * 1_000_000)
+        .ok()?
+        .format(&Rfc3339)
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::runtime::ProgressFormat;
+    use crate::plugin_engine::temp_dir;
+
+    const NOW: i64 = 1_770_000_000_000;
+    const HOUR: i64 = 60 * 60 * 1000;
+
+    fn sample(hours_ago: i64, used: f64) -> UsageSample {
+        UsageSample {
+            timestamp_ms: NOW - hours_ago * HOUR,
+            used,
+            limit: 100.0,
+            resets_at: None,
+        }
+    }
+
+    fn resets_in(hours: i64) -> String {
+        format_ms(NOW + hours * HOUR).unwrap()
+    }
+
+    #[test]
+    fn fits_steady_burn_and_projects_exhaustion() {
+        // 5 per hour for the last 6 hours, 40 used now, limit 100, reset in 24h.
+        let samples: Vec<UsageSample> = (1..=6)
+            .rev()
+            .map(|h| sample(h, 40.0 - 5.0 * h as f64))
+            .collect();
+        let reset = resets_in(24);
+        let result = forecast(&samples, 40.0, 100.0, Some(&reset), None, NOW).unwrap();
+
+        assert_eq!(result.status, PaceStatus::OverPace);
+        assert!((result.burn_rate_per_hour - 5.0).abs() < 1e-9);
+        assert!((result.projected_usage - 160.0).abs() < 1e-6);
+        assert_eq!(result.exhausts_at, format_ms(NOW + 12 * HOUR));
+        assert_eq!(result.sample_count, 7);
+        assert!(result.confidence > 0.8, "confidence {}", result.confidence);
+    }
+
+    #[test]
+    fn slow_burn_is_ahead_without_exhaustion() {
+        let samples = vec![sample(3, 10.0), sample(2, 10.5), sample(1, 11.0)];
+        let reset = resets_in(10);
+        let result = forecast(&samples, 11.5, 100.0, Some(&reset), None, NOW).unwrap();
+        assert_eq!(result.status, PaceStatus::Ahead);
+        assert!(result.exhausts_at.is_none());
+    }
+
+    #[test]
+    fn ignores_samples_before_a_reset() {
+        // Usage dropped from 90 to 2 three hours ago; only the new run is fitted.
+        let samples = vec![
+            sample(5, 80.0),
+            sample(4, 90.0),
+            sample(3, 2.0),
+            sample(2, 4.0),
+            sample(1, 6.0),
+        ];
+        let reset = resets_in(5);
+        let result = forecast(&samples, 8.0, 100.0, Some(&reset), None, NOW).unwrap();
+        assert_eq!(result.sample_count, 4);
+        assert!((result.burn_rate_per_hour - 2.0).abs() < 1e-9);
+        assert_eq!(result.status, PaceStatus::Ahead);
+    }
+
+    #[test]
+    fn falls_back_to_period_average_with_low_confidence() {
+        // Half of a 10h period gone with 60 used: 12/h, 120 projected.
+        let reset = resets_in(5);
+        let result = forecast(&[], 60.0, 100.0, Some(&reset), Some(10 * HOUR as u64), NOW).unwrap();
+        assert_eq!(result.sample_count, 0);
+        assert_eq!(result.status, PaceStatus::OverPace);
+        assert!((result.projected_usage - 120.0).abs() < 1e-6);
+        assert!(result.confidence <= 0.25);
+        assert_eq!(result.exhausts_at, format_ms(NOW + 3 * HOUR + HOUR / 3));
+    }
+
+    #[test]
+    fn needs_a_future_reset_and_enough_data() {
+        let samples = vec![sample(2, 1.0), sample(1, 2.0)];
+        assert!(forecast(&samples, 3.0, 100.0, None, None, NOW).is_none());
+        assert!(forecast(&samples, 3.0, 100.0, Some(&resets_in(-1)), None, NOW).is_none());
+        assert!(forecast(&samples, 3.0, 0.0, Some(&resets_in(1)), None, NOW).is_none());
+        // Two samples and no period to fall back on.
+        assert!(forecast(&samples[1..], 3.0, 100.0, Some(&resets_in(1)), None, NOW).is_none());
+        // Too early in the period for the average to mean anything.
+        let period = Some(100 * HOUR as u64);
+        assert!(forecast(&[], 3.0, 100.0, Some(&resets_in(99)), period, NOW).is_none());
+    }
+
+    fn progress_output(used: f64, resets_at: &str) -> PluginOutput {
+        PluginOutput {
+            provider_id: "codex".to_string(),
+            display_name: "Codex".to_string(),
+            plan: None,
+            lines: vec![MetricLine::Progress {
+                label: "Session".to_string(),
+                used,
+                limit: 100.0,
+                format: ProgressFormat::Percent,
+                resets_at: Some(resets_at.to_string()),
+                period_duration_ms: Some(10 * HOUR as u64),
+                color: None,
+                forecast: None,
+            }],
+            icon_url: String::new(),
+            error: None,
+            fetched_at: None,
+            stale: false,
+        }
+    }
+
+    fn attached(output: &PluginOutput) -> Option<&Forecast> {
+        match &output.lines[0] {
+            MetricLine::Progress { forecast, .. } => forecast.as_ref(),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn attach_fits_every_probe_and_keeps_samples_across_restarts() {
+        let dir = temp_dir("forecast-samples");
+        let reset = resets_in(5);
+        let mut samples = ForecastSamples::load(&dir);
+        // Five probes ten minutes apart, 1 used per probe.
+        for step in 0..5 {
+            let mut output = progress_output(50.0 + step as f64, &reset);
+            attach_forecasts(&mut output, &mut samples, NOW - (4 - step) * HOUR / 6);
+        }
+        samples.save().unwrap();
+
+        let mut samples = ForecastSamples::load(&dir);
+        assert_eq!(samples.get("codex", "Session").len(), 5);
+        let mut output = progress_output(55.0, &reset);
+        attach_forecasts(&mut output, &mut samples, NOW + HOUR / 6);
+        let result = attached(&output).unwrap();
+        assert_eq!(result.sample_count, 6);
+        assert!((result.burn_rate_per_hour - 6.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn samples_are_dropped_at_a_reset_and_capped() {
+        let dir = temp_dir("forecast-reset");
+        let reset = resets_in(5);
+        let mut samples = ForecastSamples::load(&dir);
+        for step in 0..3 {
+            attach_forecasts(
+                &mut progress_output(80.0 + step as f64, &reset),
+                &mut samples,
+                NOW + step,
+            );
+        }
+        attach_forecasts(&mut progress_output(1.0, &reset), &mut samples, NOW + 3);
+        assert_eq!(samples.get("codex", "Session").len(), 1);
+
+        for step in 0..MAX_SAMPLES_PER_LINE as i64 {
+            attach_forecasts(
+                &mut progress_output(1.0, &reset),
+                &mut samples,
+                NOW + 4 + step,
+            );
+        }
+        let kept = samples.get("codex", "Session");
+        assert_eq!(kept.len(), MAX_SAMPLES_PER_LINE);
+        assert_eq!(kept[0].timestamp_ms, NOW + 4);
+    }
+
+    #[test]
+    fn serializes_kebab_case_status() {
+        let reset = resets_in(24);
+        let samples = vec![sample(2, 100.0), sample(1, 100.0)];
+        let result = forecast(&samples, 100.0, 100.0, Some(&reset), None, NOW).unwrap();
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["status"], "on-track");
+        assert_eq!(json["burnRatePerHour"], 0.0);
+        assert_eq!(json["exhaustsAt"], format_ms(NOW).unwrap());
+    }
+}
@@ -0,0 +1,367 @@
+use crate::plugin_engine::runtime::{MetricLine, PluginOutput};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+pub const HISTORY_DIR: &str = "usage_history";
+
+const HOUR_MS: i64 = 60 * 60 * 1000;
+const DAY_MS: i64 = 24 * HOUR_MS;
+
+/// One recorded value of a progress line.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageSample {
+    pub timestamp_ms: i64,
+    pub used: f64,
+    pub limit: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resets_at: Option<String>,
+}
+
+/// How long samples are kept and at what resolution. Samples younger than `raw_ms`
+/// are kept as recorded; up to `hourly_ms` one per hour survives, up to `max_age_ms`
+/// one per day, and anything older is dropped.
+#[derive(Debug, Clone, Copy)]
+pub struct RetentionPolicy {
+    pub raw_ms: i64,
+    pub hourly_ms: i64,
+    pub max_age_ms: i64,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            raw_ms: 2 * DAY_MS,
+            hourly_ms: 30 * DAY_MS,
+            max_age_ms: 180 * DAY_MS,
+        }
+    }
+}
+
+/// Inclusive time bounds for a query; a missing bound is open.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryRange {
+    pub from_ms: Option<i64>,
+    pub to_ms: Option<i64>,
+}
+
+impl HistoryRange {
+    fn contains(&self, timestamp_ms: i64) -> bool {
+        self.from_ms.is_none_or(|from| timestamp_ms >= from)
+            && self.to_ms.is_none_or(|to| timestamp_ms <= to)
+    }
+}
+
+/// Current wall time in Unix milliseconds, the timestamp unit of [`UsageSample`].
+pub fn now_ms() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+/// Samples by progress line label, for one plugin.
+type PluginSeries = BTreeMap<String, Vec<UsageSample>>;
+
+/// Time series of every progress line, stored as one JSON file per plugin under
+/// `usage_history/` in the app data dir. Files are loaded on first use and rewritten
+/// (after compaction) whenever a probe adds samples.
+#[derive(Debug)]
+pub struct UsageHistory {
+    dir: PathBuf,
+    policy: RetentionPolicy,
+    plugins: HashMap<String, PluginSeries>,
+    read_only: bool,
+}
+
+impl UsageHistory {
+    pub fn open(app_data_dir: &Path) -> Self {
+        Self::with_policy(app_data_dir, RetentionPolicy::default())
+    }
+
+    pub fn with_policy(app_data_dir: &Path, policy: RetentionPolicy) -> Self {
+        Self {
+            dir: app_data_dir.join(HISTORY_DIR),
+            policy,
+            plugins: HashMap::new(),
+            read_only: false,
+        }
+    }
+
+    /// Record samples in memory only, for a data dir another process writes.
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    /// Record every progress line of a successful output. Failed and stale outputs
+    /// carry no new data and are skipped.
+    pub fn record_output(&mut self, output: &PluginOutput, now_ms: i64) -> std::io::Result<()> {
+        if output.error.is_some() || output.stale {
+            return Ok(());
+        }
+        let samples: Vec<(String, UsageSample)> = output
+            .lines
+            .iter()
+            .filter_map(|line| match line {
+                MetricLine::Progress {
+                    label,
+                    used,
+                    limit,
+                    resets_at,
+                    ..
+                } => Some((
+                    label.clone(),
+                    UsageSample {
+                        timestamp_ms: now_ms,
+                        used: *used,
+                        limit: *limit,
+                        resets_at: resets_at.clone(),
+                    },
+                )),
+                _ => None,
+            })
+            .collect();
+        if samples.is_empty() {
+            return Ok(());
+        }
+
+        let policy = self.policy;
+        let series = self.series_mut(&output.provider_id);
+        for (label, sample) in samples {
+            let entries = series.entry(label).or_default();
+            entries.push(sample);
+            compact(entries, now_ms, &policy);
+        }
+        series.retain(|_, samples| !samples.is_empty());
+        self.save(&output.provider_id)
+    }
+
+    /// Samples of one line within `range`, oldest first.
+    pub fn query(&mut self, plugin_id: &str, label: &str, range: HistoryRange) -> Vec<UsageSample> {
+        self.series_mut(plugin_id)
+            .get(label)
+            .map(|samples| {
+                samples
+                    .iter()
+                    .filter(|sample| range.contains(sample.timestamp_ms))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Labels with recorded samples for a plugin.
+    pub fn labels(&mut self, plugin_id: &str) -> Vec<String> {
+        self.series_mut(plugin_id).keys().cloned().collect()
+    }
+
+    fn series_mut(&mut self, plugin_id: &str) -> &mut PluginSeries {
+        let path = self.file_for(plugin_id);
+        self.plugins
+            .entry(plugin_id.to_string())
+            .or_insert_with(|| load_series(path.as_deref()))
+    }
+
+    fn save(&self, plugin_id: &str) -> std::io::Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+        let (Some(path), Some(series)) = (self.file_for(plugin_id), self.plugins.get(plugin_id)) else {
+            return Ok(());
+        };
+        std::fs::create_dir_all(&self.dir)?;
+        let json = serde_json::to_vec(series).map_err(std::io::Error::other)?;
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(&tmp, &path)
+    }
+
+    /// History file for a plugin; None for ids that aren't safe as a file name.
+    fn file_for(&self, plugin_id: &str) -> Option<PathBuf> {
+        let safe = !plugin_id.is_empty()
+            && plugin_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        safe.then(|| self.dir.join(format!("{}.json", plugin_id)))
+    }
+}
+
+fn load_series(path: Option<&Path>) -> PluginSeries {
+    let Some(path) = path else {
+        return PluginSeries::new();
+    };
+    match std::fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text).unwrap_or_else(|err| {
+            log::warn!("ignoring unreadable usage history {}: {}", path.display(), err);
+            PluginSeries::new()
+        }),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => PluginSeries::new(),
+        Err(err) => {
+            log::warn!("failed to read usage history {}: {}", path.display(), err);
+            PluginSeries::new()
+        }
+    }
+}
+
+/// Apply the retention policy to samples sorted by time: drop expired ones and keep
+/// only the latest sample of each hour/day bucket in the downsampled tiers.
+fn compact(samples: &mut Vec<UsageSample>, now_ms: i64, policy: &RetentionPolicy) {
+    let mut kept: Vec<UsageSample> = Vec::with_capacity(samples.len());
+    let mut last_bucket: Option<(i64, i64)> = None;
+    for sample in samples.drain(..) {
+        let age = now_ms - sample.timestamp_ms;
+        let bucket_ms = if age >= policy.max_age_ms {
+            continue;
+        } else if age >= policy.hourly_ms {
+            DAY_MS
+        } else if age >= policy.raw_ms {
+            HOUR_MS
+        } else {
+            kept.push(sample);
+            last_bucket = None;
+            continue;
+        };
+        let bucket = (bucket_ms, sample.timestamp_ms.div_euclid(bucket_ms));
+        if last_bucket == Some(bucket) {
+            kept.pop();
+        }
+        last_bucket = Some(bucket);
+        kept.push(sample);
+    }
+    *samples = kept;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::error::{ProbeError, ProbeErrorKind};
+    use crate::plugin_engine::runtime::ProgressFormat;
+    use crate::plugin_engine::temp_dir;
+
+    fn output(used: f64) -> PluginOutput {
+        PluginOutput {
+            provider_id: "claude".to_string(),
+            display_name: "Claude".to_string(),
+            plan: None,
+            lines: vec![
+                MetricLine::Progress {
+                    label: "Weekly".to_string(),
+                    used,
+                    limit: 100.0,
+                    format: ProgressFormat::Percent,
+                    resets_at: Some("2026-02-09T00:00:00Z".to_string()),
+                    period_duration_ms: None,
+                    color: None,
+                    forecast: None,
+                },
+                MetricLine::Text {
+                    label: "Plan".to_string(),
+                    value: "Max".to_string(),
+                    color: None,
+                    subtitle: None,
+                },
+            ],
+            icon_url: String::new(),
+            error: None,
+            fetched_at: None,
+            stale: false,
+        }
+    }
+
+    fn sample(timestamp_ms: i64) -> UsageSample {
+        UsageSample {
+            timestamp_ms,
+            used: timestamp_ms as f64,
+            limit: 100.0,
+            resets_at: None,
+        }
+    }
+
+    #[test]
+    fn records_progress_lines_and_queries_by_range() {
+        let dir = temp_dir("record");
+        let mut history = UsageHistory::open(&dir);
+        let start = 1_770_000_000_000;
+        for step in 0..4 {
+            history
+                .record_output(&output(10.0 * step as f64), start + step * 60_000)
+                .unwrap();
+        }
+
+        let all = history.query("claude", "Weekly", HistoryRange::default());
+        assert_eq!(all.len(), 4);
+        assert_eq!(all[3].used, 30.0);
+        assert_eq!(all[0].resets_at.as_deref(), Some("2026-02-09T00:00:00Z"));
+
+        let range = HistoryRange {
+            from_ms: Some(start + 60_000),
+            to_ms: Some(start + 120_000),
+        };
+        assert_eq!(history.query("claude", "Weekly", range).len(), 2);
+        assert!(history.query("claude", "Plan", HistoryRange::default()).is_empty());
+        assert_eq!(history.labels("claude"), vec!["Weekly".to_string()]);
+
+        let mut reopened = UsageHistory::open(&dir);
+        assert_eq!(reopened.query("claude", "Weekly", HistoryRange::default()), all);
+    }
+
+    #[test]
+    fn skips_failed_and_stale_outputs() {
+        let mut history = UsageHistory::open(&temp_dir("skip"));
+        let mut failed = output(50.0);
+        failed.error = Some(ProbeError::new(ProbeErrorKind::Network, "offline"));
+        history.record_output(&failed, 1).unwrap();
+        let mut stale = output(50.0);
+        stale.stale = true;
+        history.record_output(&stale, 2).unwrap();
+        assert!(history.query("claude", "Weekly", HistoryRange::default()).is_empty());
+    }
+
+    #[test]
+    fn compaction_downsamples_older_tiers_and_drops_expired() {
+        let policy = RetentionPolicy {
+            raw_ms: 2 * HOUR_MS,
+            hourly_ms: 2 * DAY_MS,
+            max_age_ms: 5 * DAY_MS,
+        };
+        let now = 10 * DAY_MS;
+        let mut samples = vec![
+            sample(now - 6 * DAY_MS),                  // expired
+            sample(now - 4 * DAY_MS),                  // daily tier...
+            sample(now - 4 * DAY_MS + HOUR_MS),        // ...same day, replaces the previous
+            sample(now - 3 * DAY_MS),                  // next day
+            sample(now - 10 * HOUR_MS),                // hourly tier...
+            sample(now - 10 * HOUR_MS + 60_000),       // ...same hour
+            sample(now - 9 * HOUR_MS),                 // next hour
+            sample(now - HOUR_MS),                     // raw
+            sample(now - HOUR_MS + 60_000),            // raw
+        ];
+        compact(&mut samples, now, &policy);
+        let kept: Vec<i64> = samples.iter().map(|s| now - s.timestamp_ms).collect();
+        assert_eq!(
+            kept,
+            vec![
+                4 * DAY_MS - HOUR_MS,
+                3 * DAY_MS,
+                10 * HOUR_MS - 60_000,
+                9 * HOUR_MS,
+                HOUR_MS,
+                HOUR_MS - 60_000,
+            ]
+        );
+    }
+
+    #[test]
+    fn unsafe_plugin_ids_are_not_persisted() {
+        let dir = temp_dir("unsafe");
+        let mut history = UsageHistory::open(&dir);
+        let mut weird = output(1.0);
+        weird.provider_id = "../escape".to_string();
+        history.record_output(&weird, 1).unwrap();
+        assert_eq!(history.query("../escape", "Weekly", HistoryRange::default()).len(), 1);
+        assert!(!dir.join(HISTORY_DIR).exists());
+    }
+}
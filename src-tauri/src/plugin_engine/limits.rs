@@ -0,0 +1,199 @@
+use crate::plugin_engine::manifest::PluginManifest;
+use crate::plugin_engine::secrets::SecretStoreKind;
+use rquickjs::allocator::{Allocator, RustAllocator};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_PROBE_TIMEOUT_MS: u64 = 30_000;
+pub const DEFAULT_MEMORY_LIMIT_MB: u64 = 64;
+pub const DEFAULT_STACK_LIMIT_KB: u64 = 1024;
+pub const DEFAULT_MAX_CONCURRENT_PROBES: usize = 4;
+
+/// App-wide probe defaults. Plugins may override individual values in `plugin.json`.
+#[derive(Debug, Clone)]
+pub struct ProbeConfig {
+    pub default_timeout_ms: u64,
+    pub default_memory_limit_mb: u64,
+    pub default_stack_limit_kb: u64,
+    /// How many probes may run at the same time across all batches.
+    pub max_concurrent_probes: usize,
+    /// Let `host.http` reach loopback, private and link-local addresses; for plugin development.
+    pub allow_private_network: bool,
+    /// Where `host.keychain` keeps secrets.
+    pub secret_store: SecretStoreKind,
+}
+
+impl Default for ProbeConfig {
+    fn default() -> Self {
+        Self {
+            default_timeout_ms: DEFAULT_PROBE_TIMEOUT_MS,
+            default_memory_limit_mb: DEFAULT_MEMORY_LIMIT_MB,
+            default_stack_limit_kb: DEFAULT_STACK_LIMIT_KB,
+            max_concurrent_probes: DEFAULT_MAX_CONCURRENT_PROBES,
+            allow_private_network: false,
+            secret_store: SecretStoreKind::Auto,
+        }
+    }
+}
+
+impl ProbeConfig {
+    /// Resolve the effective limits for one plugin (manifest overrides win).
+    pub fn limits_for(&self, manifest: &PluginManifest) -> ProbeLimits {
+        let timeout_ms = manifest
+            .limits
+            .timeout_ms
+            .filter(|ms| *ms > 0)
+            .unwrap_or(self.default_timeout_ms);
+        let memory_limit_mb = manifest
+            .limits
+            .memory_limit_mb
+            .filter(|mb| *mb > 0)
+            .unwrap_or(self.default_memory_limit_mb);
+        let stack_limit_kb = manifest
+            .limits
+            .stack_limit_kb
+            .filter(|kb| *kb > 0)
+            .unwrap_or(self.default_stack_limit_kb);
+        ProbeLimits {
+            timeout: Duration::from_millis(timeout_ms),
+            memory_limit_bytes: (memory_limit_mb as usize).saturating_mul(1024 * 1024),
+            stack_limit_bytes: (stack_limit_kb as usize).saturating_mul(1024),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ProbeLimits {
+    pub timeout: Duration,
+    pub memory_limit_bytes: usize,
+    pub stack_limit_bytes: usize,
+}
+
+/// Wall-clock deadline shared by the interrupt handler and blocking host calls.
+#[derive(Debug, Clone, Copy)]
+pub struct Deadline {
+    started: Instant,
+    timeout: Duration,
+}
+
+impl Deadline {
+    pub fn start(timeout: Duration) -> Self {
+        Self {
+            started: Instant::now(),
+            timeout,
+        }
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.timeout.saturating_sub(self.started.elapsed())
+    }
+
+    pub fn expired(&self) -> bool {
+        self.started.elapsed() >= self.timeout
+    }
+
+    /// Clamp a host call timeout so it never outlives the probe.
+    pub fn clamp(&self, requested: Duration) -> Duration {
+        requested.min(self.remaining())
+    }
+}
+
+/// QuickJS allocator that enforces the memory budget itself, so running out of
+/// memory is observable (the engine cannot allocate the error it would throw).
+pub struct BudgetAllocator {
+    inner: RustAllocator,
+    limit: usize,
+    used: usize,
+    exhausted: Arc<AtomicBool>,
+}
+
+impl BudgetAllocator {
+    pub fn new(limit: usize) -> (Self, MemoryBudget) {
+        let exhausted = Arc::new(AtomicBool::new(false));
+        let allocator = Self {
+            inner: RustAllocator,
+            limit,
+            used: 0,
+            exhausted: Arc::clone(&exhausted),
+        };
+        (allocator, MemoryBudget { limit, exhausted })
+    }
+
+    fn reserve(&mut self, size: usize) -> bool {
+        if self.used.saturating_add(size) > self.limit {
+            self.exhausted.store(true, Ordering::SeqCst);
+            return false;
+        }
+        true
+    }
+}
+
+unsafe impl Allocator for BudgetAllocator {
+    fn alloc(&mut self, size: usize) -> *mut u8 {
+        if !self.reserve(size) {
+            return std::ptr::null_mut();
+        }
+        let ptr = self.inner.alloc(size);
+        if !ptr.is_null() {
+            self.used += unsafe { RustAllocator::usable_size(ptr) };
+        }
+        ptr
+    }
+
+    fn calloc(&mut self, count: usize, size: usize) -> *mut u8 {
+        let Some(total) = count.checked_mul(size) else {
+            return std::ptr::null_mut();
+        };
+        if !self.reserve(total) {
+            return std::ptr::null_mut();
+        }
+        let ptr = self.inner.calloc(count, size);
+        if !ptr.is_null() {
+            self.used += unsafe { RustAllocator::usable_size(ptr) };
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&mut self, ptr: *mut u8) {
+        self.used = self.used.saturating_sub(RustAllocator::usable_size(ptr));
+        self.inner.dealloc(ptr);
+    }
+
+    unsafe fn realloc(&mut self, ptr: *mut u8, new_size: usize) -> *mut u8 {
+        let old_size = RustAllocator::usable_size(ptr);
+        if new_size > old_size && !self.reserve(new_size - old_size) {
+            return std::ptr::null_mut();
+        }
+        let new_ptr = self.inner.realloc(ptr, new_size);
+        if !new_ptr.is_null() {
+            self.used = self.used.saturating_sub(old_size) + RustAllocator::usable_size(new_ptr);
+        }
+        new_ptr
+    }
+
+    unsafe fn usable_size(ptr: *mut u8) -> usize {
+        RustAllocator::usable_size(ptr)
+    }
+}
+
+/// Handle for checking whether a probe ran out of memory.
+#[derive(Debug, Clone)]
+pub struct MemoryBudget {
+    limit: usize,
+    exhausted: Arc<AtomicBool>,
+}
+
+impl MemoryBudget {
+    pub fn limit_bytes(&self) -> usize {
+        self.limit
+    }
+
+    pub fn exhausted(&self) -> bool {
+        self.exhausted.load(Ordering::SeqCst)
+    }
+}
@@ -1,5 +1,5 @@
 use base64::{engine::general_purpose::STANDARD, Engine};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Deserialize)]
@@ -14,6 +14,93 @@ pub struct ManifestLine {
     pub primary_order: Option<u32>,
 }
 
+/// Optional per-plugin overrides for the sandbox limits.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestLimits {
+    /// Probe deadline in milliseconds; falls back to the app default when absent.
+    pub timeout_ms: Option<u64>,
+    /// QuickJS heap limit in megabytes.
+    pub memory_limit_mb: Option<u64>,
+    /// QuickJS max stack size in kilobytes.
+    pub stack_limit_kb: Option<u64>,
+}
+
+/// Whether a declared resource may only be read, or also written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Access {
+    Read,
+    /// Also allows reading.
+    Write,
+}
+
+impl Access {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Write => "write",
+        }
+    }
+}
+
+/// A file, directory or SQLite database a plugin may open. `~` expands like in `host.fs`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PathPermission {
+    pub path: String,
+    pub access: Access,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeychainPermission {
+    pub service: String,
+    pub access: Access,
+}
+
+/// Where `host.http` may connect. Hosts are exact names or `*.example.com` for subdomains.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HttpPermission {
+    pub hosts: Vec<String>,
+    pub schemes: Vec<String>,
+}
+
+impl Default for HttpPermission {
+    fn default() -> Self {
+        Self {
+            hosts: Vec::new(),
+            schemes: vec!["https".to_string()],
+        }
+    }
+}
+
+/// Host resources a plugin needs besides its own data dir; everything else is denied.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestPermissions {
+    #[serde(default)]
+    pub http: HttpPermission,
+    #[serde(default)]
+    pub fs: Vec<PathPermission>,
+    #[serde(default)]
+    pub keychain: Vec<KeychainPermission>,
+    #[serde(default)]
+    pub sqlite: Vec<PathPermission>,
+}
+
+/// How the entry file is evaluated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EntryType {
+    /// Classic script that assigns `globalThis.__openusage_plugin`.
+    #[default]
+    Script,
+    /// ES module; may `import` other files from the plugin directory.
+    Module,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PluginManifest {
@@ -22,9 +109,15 @@ pub struct PluginManifest {
     pub name: String,
     pub version: String,
     pub entry: String,
+    #[serde(default)]
+    pub entry_type: EntryType,
     pub icon: String,
     pub brand_color: Option<String>,
     pub lines: Vec<ManifestLine>,
+    #[serde(default)]
+    pub limits: ManifestLimits,
+    #[serde(default)]
+    pub permissions: ManifestPermissions,
 }
 
 #[derive(Debug, Clone)]
@@ -35,6 +128,62 @@ pub struct LoadedPlugin {
     pub icon_data_url: String,
 }
 
+/// What the UI (and the HTTP API) gets to know about a plugin, from `list_plugins`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginMeta {
+    pub id: String,
+    pub name: String,
+    pub icon_url: String,
+    pub brand_color: Option<String>,
+    pub lines: Vec<ManifestLineDto>,
+    /// Ordered list of primary metric candidates (sorted by primaryOrder).
+    /// Frontend picks the first one that exists in runtime data.
+    pub primary_candidates: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestLineDto {
+    #[serde(rename = "type")]
+    pub line_type: String,
+    pub label: String,
+    pub scope: String,
+}
+
+impl From<&LoadedPlugin> for PluginMeta {
+    fn from(plugin: &LoadedPlugin) -> Self {
+        // Extract primary candidates: progress lines with primary_order, sorted by order
+        let mut candidates: Vec<_> = plugin
+            .manifest
+            .lines
+            .iter()
+            .filter(|line| line.line_type == "progress" && line.primary_order.is_some())
+            .collect();
+        candidates.sort_by_key(|line| line.primary_order.unwrap());
+        let primary_candidates: Vec<String> =
+            candidates.iter().map(|line| line.label.clone()).collect();
+
+        PluginMeta {
+            id: plugin.manifest.id.clone(),
+            name: plugin.manifest.name.clone(),
+            icon_url: plugin.icon_data_url.clone(),
+            brand_color: plugin.manifest.brand_color.clone(),
+            lines: plugin
+                .manifest
+                .lines
+                .iter()
+                .map(|line| ManifestLineDto {
+                    line_type: line.line_type.clone(),
+                    label: line.label.clone(),
+                    scope: line.scope.clone(),
+                })
+                .collect(),
+            primary_candidates,
+        }
+    }
+}
+
 pub fn load_plugins_from_dir(plugins_dir: &std::path::Path) -> Vec<LoadedPlugin> {
     let mut plugins = Vec::new();
     let entries = match std::fs::read_dir(plugins_dir) {
@@ -79,23 +228,7 @@ fn load_single_plugin(
         }
     }
 
-    if manifest.entry.trim().is_empty() {
-        return Err("plugin entry field cannot be empty".into());
-    }
-    if Path::new(&manifest.entry).is_absolute() {
-        return Err("plugin entry must be a relative path".into());
-    }
-
-    let entry_path = plugin_dir.join(&manifest.entry);
-    let canonical_plugin_dir = plugin_dir.canonicalize()?;
-    let canonical_entry_path = entry_path.canonicalize()?;
-    if !canonical_entry_path.starts_with(&canonical_plugin_dir) {
-        return Err("plugin entry must remain within plugin directory".into());
-    }
-    if !canonical_entry_path.is_file() {
-        return Err("plugin entry must be a file".into());
-    }
-
+    let canonical_entry_path = resolve_plugin_file(plugin_dir, &manifest.entry, "entry")?;
     let entry_script = std::fs::read_to_string(&canonical_entry_path)?;
 
     let icon_file = plugin_dir.join(&manifest.icon);
@@ -110,9 +243,36 @@ fn load_single_plugin(
     })
 }
 
+/// Resolve `relative` to a file inside `plugin_dir`, following symlinks, and reject
+/// anything that escapes the directory. `what` names the field in error messages.
+pub fn resolve_plugin_file(
+    plugin_dir: &Path,
+    relative: &str,
+    what: &str,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    if relative.trim().is_empty() {
+        return Err(format!("plugin {} field cannot be empty", what).into());
+    }
+    if Path::new(relative).is_absolute() {
+        return Err(format!("plugin {} must be a relative path", what).into());
+    }
+
+    let path = plugin_dir.join(relative);
+    let canonical_plugin_dir = plugin_dir.canonicalize()?;
+    let canonical_path = path.canonicalize()?;
+    if !canonical_path.starts_with(&canonical_plugin_dir) {
+        return Err(format!("plugin {} must remain within plugin directory", what).into());
+    }
+    if !canonical_path.is_file() {
+        return Err(format!("plugin {} must be a file", what).into());
+    }
+    Ok(canonical_path)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::plugin_engine::temp_dir;
 
     fn parse_manifest(json: &str) -> PluginManifest {
         serde_json::from_str::<PluginManifest>(json).expect("manifest parse failed")
@@ -199,4 +359,133 @@ mod tests {
 
         assert_eq!(labels, vec!["First", "Second", "Third"]);
     }
+
+    #[test]
+    fn entry_type_defaults_to_script() {
+        let manifest = parse_manifest(
+            r#"
+            {
+              "schemaVersion": 1,
+              "id": "x",
+              "name": "X",
+              "version": "0.0.1",
+              "entry": "plugin.js",
+              "icon": "icon.svg",
+              "lines": []
+            }
+            "#,
+        );
+        assert_eq!(manifest.entry_type, EntryType::Script);
+    }
+
+    #[test]
+    fn entry_type_module_parsed() {
+        let manifest = parse_manifest(
+            r#"
+            {
+              "schemaVersion": 1,
+              "id": "x",
+              "name": "X",
+              "version": "0.0.1",
+              "entry": "plugin.js",
+              "entryType": "module",
+              "icon": "icon.svg",
+              "lines": []
+            }
+            "#,
+        );
+        assert_eq!(manifest.entry_type, EntryType::Module);
+    }
+
+    #[test]
+    fn resolve_plugin_file_rejects_escapes() {
+        let root = temp_dir("resolve");
+        let plugin_dir = root.join("plugin");
+        std::fs::create_dir_all(plugin_dir.join("lib")).unwrap();
+        std::fs::write(plugin_dir.join("lib/util.js"), "").unwrap();
+        std::fs::write(root.join("secret.js"), "").unwrap();
+
+        let resolved = resolve_plugin_file(&plugin_dir, "lib/../lib/util.js", "import").unwrap();
+        assert!(resolved.ends_with("lib/util.js"));
+
+        let err = resolve_plugin_file(&plugin_dir, "../secret.js", "import").unwrap_err();
+        assert_eq!(err.to_string(), "plugin import must remain within plugin directory");
+        let err = resolve_plugin_file(&plugin_dir, "/etc/passwd", "import").unwrap_err();
+        assert_eq!(err.to_string(), "plugin import must be a relative path");
+        assert!(resolve_plugin_file(&plugin_dir, "lib", "import").is_err());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn limits_default_to_empty() {
+        let manifest = parse_manifest(
+            r#"
+            {
+              "schemaVersion": 1,
+              "id": "x",
+              "name": "X",
+              "version": "0.0.1",
+              "entry": "plugin.js",
+              "icon": "icon.svg",
+              "brandColor": null,
+              "lines": []
+            }
+            "#,
+        );
+        assert!(manifest.limits.timeout_ms.is_none());
+        assert!(manifest.limits.memory_limit_mb.is_none());
+        assert!(manifest.limits.stack_limit_kb.is_none());
+    }
+
+    #[test]
+    fn limits_parsed_correctly() {
+        let manifest = parse_manifest(
+            r#"
+            {
+              "schemaVersion": 1,
+              "id": "x",
+              "name": "X",
+              "version": "0.0.1",
+              "entry": "plugin.js",
+              "icon": "icon.svg",
+              "brandColor": null,
+              "lines": [],
+              "limits": { "timeoutMs": 5000, "memoryLimitMb": 32, "stackLimitKb": 512 }
+            }
+            "#,
+        );
+        assert_eq!(manifest.limits.timeout_ms, Some(5000));
+        assert_eq!(manifest.limits.memory_limit_mb, Some(32));
+        assert_eq!(manifest.limits.stack_limit_kb, Some(512));
+    }
+
+    #[test]
+    fn permissions_parsed_correctly() {
+        let manifest = parse_manifest(
+            r#"
+            {
+              "schemaVersion": 1,
+              "id": "x",
+              "name": "X",
+              "version": "0.0.1",
+              "entry": "plugin.js",
+              "icon": "icon.svg",
+              "brandColor": null,
+              "lines": [],
+              "permissions": {
+                "fs": [{ "path": "~/.x/auth.json", "access": "write" }],
+                "keychain": [{ "service": "X-credentials", "access": "read" }]
+              }
+            }
+            "#,
+        );
+        assert_eq!(manifest.permissions.fs[0].path, "~/.x/auth.json");
+        assert_eq!(manifest.permissions.fs[0].access, Access::Write);
+        assert_eq!(manifest.permissions.keychain[0].access, Access::Read);
+        assert!(manifest.permissions.sqlite.is_empty());
+        assert!(manifest.permissions.http.hosts.is_empty());
+        assert_eq!(manifest.permissions.http.schemes, vec!["https"]);
+        assert!(serde_json::from_str::<PathPermission>(r#"{ "path": "~/x" }"#).is_err());
+    }
 }
@@ -0,0 +1,165 @@
+use crate::plugin_engine::runtime::{MetricLine, PluginOutput};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::Duration;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+/// Content type of the Prometheus text exposition format.
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Render progress lines and probe timings in the Prometheus text format.
+///
+/// Every `progress` line becomes `openusage_used` and `openusage_limit` samples labelled
+/// with the provider id and line label, plus `openusage_resets_at_seconds` when it has a
+/// reset time. Stale outputs still report their last good values.
+pub fn render(outputs: &[PluginOutput], probe_durations: &BTreeMap<String, Duration>) -> String {
+    let mut used = Vec::new();
+    let mut limit = Vec::new();
+    let mut resets_at = Vec::new();
+    for output in outputs {
+        for line in &output.lines {
+            let MetricLine::Progress {
+                label,
+                used: line_used,
+                limit: line_limit,
+                resets_at: line_resets_at,
+                ..
+            } = line
+            else {
+                continue;
+            };
+            let labels = format!(
+                "provider=\"{}\",label=\"{}\"",
+                escape(&output.provider_id),
+                escape(label)
+            );
+            used.push((labels.clone(), *line_used));
+            limit.push((labels.clone(), *line_limit));
+            if let Some(seconds) = line_resets_at.as_deref().and_then(unix_seconds) {
+                resets_at.push((labels, seconds));
+            }
+        }
+    }
+    let durations: Vec<(String, f64)> = probe_durations
+        .iter()
+        .map(|(plugin_id, duration)| (format!("provider=\"{}\"", escape(plugin_id)), duration.as_secs_f64()))
+        .collect();
+
+    let mut text = String::new();
+    family(&mut text, "openusage_used", "Amount used of a progress line's limit.", &used);
+    family(&mut text, "openusage_limit", "Limit of a progress line.", &limit);
+    family(
+        &mut text,
+        "openusage_resets_at_seconds",
+        "Unix time a progress line's usage resets.",
+        &resets_at,
+    );
+    family(
+        &mut text,
+        "openusage_probe_duration_seconds",
+        "Wall time of the provider's last probe.",
+        &durations,
+    );
+    text
+}
+
+fn family(text: &mut String, name: &str, help: &str, samples: &[(String, f64)]) {
+    if samples.is_empty() {
+        return;
+    }
+    let _ = writeln!(text, "# HELP {} {}", name, help);
+    let _ = writeln!(text, "# TYPE {} gauge", name);
+    for (labels, value) in samples {
+        let _ = writeln!(text, "{}{{{}}} {}", name, labels, format_value(*value));
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn unix_seconds(value: &str) -> Option<f64> {
+    let parsed = OffsetDateTime::parse(value, &Rfc3339).ok()?;
+    Some(parsed.unix_timestamp() as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::runtime::ProgressFormat;
+
+    fn output(provider_id: &str, lines: Vec<MetricLine>) -> PluginOutput {
+        PluginOutput {
+            provider_id: provider_id.to_string(),
+            display_name: provider_id.to_string(),
+            plan: None,
+            lines,
+            icon_url: String::new(),
+            error: None,
+            fetched_at: None,
+            stale: false,
+        }
+    }
+
+    fn progress(label: &str, used: f64, limit: f64, resets_at: Option<&str>) -> MetricLine {
+        MetricLine::Progress {
+            label: label.to_string(),
+            used,
+            limit,
+            format: ProgressFormat::Percent,
+            resets_at: resets_at.map(str::to_string),
+            period_duration_ms: None,
+            color: None,
+            forecast: None,
+        }
+    }
+
+    #[test]
+    fn renders_progress_lines_and_probe_durations() {
+        let outputs = vec![
+            output(
+                "claude",
+                vec![
+                    progress("Session", 42.5, 100.0, Some("2026-02-01T00:00:00Z")),
+                    MetricLine::Text {
+                        label: "Plan".to_string(),
+                        value: "Max".to_string(),
+                        color: None,
+                        subtitle: None,
+                    },
+                ],
+            ),
+            output("codex", vec![progress("Weekly \"Pro\"", 3.0, 10.0, None)]),
+        ];
+        let durations = BTreeMap::from([("claude".to_string(), Duration::from_millis(1250))]);
+        let text = render(&outputs, &durations);
+
+        assert!(text.contains("# TYPE openusage_used gauge\n"));
+        assert!(text.contains("openusage_used{provider=\"claude\",label=\"Session\"} 42.5\n"));
+        assert!(text.contains("openusage_limit{provider=\"claude\",label=\"Session\"} 100\n"));
+        assert!(text.contains("openusage_resets_at_seconds{provider=\"claude\",label=\"Session\"} 1769904000\n"));
+        assert!(text.contains("openusage_used{provider=\"codex\",label=\"Weekly \\\"Pro\\\"\"} 3\n"));
+        assert!(!text.contains("openusage_resets_at_seconds{provider=\"codex\""));
+        assert!(text.contains("openusage_probe_duration_seconds{provider=\"claude\"} 1.25\n"));
+        assert!(!text.contains("Plan"));
+    }
+
+    #[test]
+    fn empty_input_renders_nothing() {
+        assert_eq!(render(&[], &BTreeMap::new()), "");
+    }
+}
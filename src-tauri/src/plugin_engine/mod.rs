@@ -1,6 +1,25 @@
+pub mod alerts;
+pub mod audit;
+pub mod bytecode;
+pub mod egress;
+pub mod error;
+pub mod forecast;
+pub mod history;
 pub mod host_api;
+pub mod limits;
 pub mod manifest;
+pub mod metrics;
+pub mod modules;
+pub mod output_cache;
+pub mod permissions;
+pub mod pipeline;
 pub mod runtime;
+pub mod scheduler;
+pub mod secrets;
+pub mod sinks;
+pub mod sqlite;
+pub mod state;
+pub mod telemetry;
 
 use manifest::LoadedPlugin;
 use std::path::{Path, PathBuf};
@@ -30,6 +49,34 @@ pub fn initialize_plugins(
     (install_dir, plugins)
 }
 
+/// The plugins [`initialize_plugins`] would load, without installing the bundled ones
+/// into `app_data_dir`; for a process that doesn't own the data dir.
+pub fn load_installed_plugins(app_data_dir: &Path) -> (PathBuf, Vec<LoadedPlugin>) {
+    if let Some(dev_dir) = find_dev_plugins_dir() {
+        if !is_dir_empty(&dev_dir) {
+            let plugins = manifest::load_plugins_from_dir(&dev_dir);
+            return (dev_dir, plugins);
+        }
+    }
+
+    let install_dir = app_data_dir.join("plugins");
+    let plugins = manifest::load_plugins_from_dir(&install_dir);
+    (install_dir, plugins)
+}
+
+/// A fresh path under the system temp dir for one test's files; not created yet.
+#[cfg(test)]
+pub(crate) fn temp_dir(label: &str) -> PathBuf {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let n = NEXT.fetch_add(1, Ordering::SeqCst);
+    std::env::temp_dir().join(format!("openusage-test-{}-{}-{}", label, nanos, n))
+}
+
 fn find_dev_plugins_dir() -> Option<PathBuf> {
     let cwd = std::env::current_dir().ok()?;
     let direct = cwd.join("plugins");
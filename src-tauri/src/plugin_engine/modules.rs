@@ -0,0 +1,77 @@
+use crate::plugin_engine::bytecode::BytecodeCache;
+use crate::plugin_engine::manifest::resolve_plugin_file;
+use rquickjs::loader::{Loader, Resolver};
+use rquickjs::module::Declared;
+use rquickjs::{Ctx, Error, Module, Result};
+use std::path::{Component, Path, PathBuf};
+
+/// Resolves and loads `import`s for module-mode plugins. Only relative specifiers
+/// are allowed, and they must resolve to files inside the plugin directory.
+#[derive(Clone)]
+pub struct PluginModules {
+    plugin_dir: PathBuf,
+    /// Plugin id and cache that imported modules are compiled through.
+    cache: Option<(String, &'static BytecodeCache)>,
+}
+
+impl PluginModules {
+    pub fn new(plugin_dir: &Path) -> Self {
+        Self {
+            plugin_dir: plugin_dir.to_path_buf(),
+            cache: None,
+        }
+    }
+
+    /// Load imports from `cache` when their source is unchanged, instead of compiling
+    /// them on every probe.
+    pub fn with_cache(mut self, plugin_id: &str, cache: &'static BytecodeCache) -> Self {
+        self.cache = Some((plugin_id.to_string(), cache));
+        self
+    }
+
+    /// Canonical module name for a file: its path relative to the plugin directory,
+    /// with `/` separators (e.g. `lib/format.js`). Used for the entry and every import.
+    pub fn module_name(&self, relative: &str) -> std::result::Result<String, String> {
+        let path = resolve_plugin_file(&self.plugin_dir, relative, "import")
+            .map_err(|e| e.to_string())?;
+        let root = self.plugin_dir.canonicalize().map_err(|e| e.to_string())?;
+        let rel = path.strip_prefix(&root).map_err(|e| e.to_string())?;
+        let parts: Vec<String> = rel
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+                _ => None,
+            })
+            .collect();
+        Ok(parts.join("/"))
+    }
+}
+
+impl Resolver for PluginModules {
+    fn resolve<'js>(&mut self, _ctx: &Ctx<'js>, base: &str, name: &str) -> Result<String> {
+        if !(name.starts_with("./") || name.starts_with("../")) {
+            return Err(Error::new_resolving_message(
+                base,
+                name,
+                "only relative imports from the plugin directory are allowed",
+            ));
+        }
+        let base_dir = Path::new(base).parent().unwrap_or_else(|| Path::new(""));
+        let joined = base_dir.join(name);
+        self.module_name(&joined.to_string_lossy())
+            .map_err(|msg| Error::new_resolving_message(base, name, msg))
+    }
+}
+
+impl Loader for PluginModules {
+    fn load<'js>(&mut self, ctx: &Ctx<'js>, name: &str) -> Result<Module<'js, Declared>> {
+        let path = resolve_plugin_file(&self.plugin_dir, name, "import")
+            .map_err(|e| Error::new_loading_message(name, e.to_string()))?;
+        let source = std::fs::read_to_string(&path)
+            .map_err(|e| Error::new_loading_message(name, e.to_string()))?;
+        match &self.cache {
+            Some((plugin_id, cache)) => cache.declare_module(ctx, plugin_id, &path, name, source),
+            None => Module::declare(ctx.clone(), name, source),
+        }
+    }
+}
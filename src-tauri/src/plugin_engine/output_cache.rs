@@ -0,0 +1,180 @@
+use crate::plugin_engine::runtime::PluginOutput;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub const OUTPUT_CACHE_FILE: &str = "probe_outputs.json";
+
+/// Last successful output per plugin, persisted as JSON under the app data dir so
+/// cards have data right after a restart and survive transient probe failures.
+#[derive(Debug)]
+pub struct OutputCache {
+    path: PathBuf,
+    outputs: BTreeMap<String, PluginOutput>,
+    read_only: bool,
+}
+
+impl OutputCache {
+    /// Load the cache file in `app_data_dir`. A missing or unreadable file starts empty.
+    pub fn load(app_data_dir: &Path) -> Self {
+        let path = app_data_dir.join(OUTPUT_CACHE_FILE);
+        let outputs = match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|err| {
+                log::warn!("ignoring unreadable output cache {}: {}", path.display(), err);
+                BTreeMap::new()
+            }),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => {
+                log::warn!("failed to read output cache {}: {}", path.display(), err);
+                BTreeMap::new()
+            }
+        };
+        Self {
+            path,
+            outputs,
+            read_only: false,
+        }
+    }
+
+    /// Keep recorded outputs in memory only, for a data dir another process writes.
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    pub fn get(&self, plugin_id: &str) -> Option<&PluginOutput> {
+        self.outputs.get(plugin_id)
+    }
+
+    /// Every cached output, ordered by plugin id.
+    pub fn all(&self) -> Vec<PluginOutput> {
+        self.outputs.values().cloned().collect()
+    }
+
+    /// Cached outputs for the given plugins, in that order.
+    pub fn outputs_for<'a, I: IntoIterator<Item = &'a str>>(&self, plugin_ids: I) -> Vec<PluginOutput> {
+        plugin_ids
+            .into_iter()
+            .filter_map(|id| self.outputs.get(id).cloned())
+            .collect()
+    }
+
+    /// Record a finished probe and return what should be reported for it.
+    ///
+    /// A successful output replaces the cached one and is written to disk. A failed one
+    /// leaves the cache alone and comes back as the last good output, marked `stale`
+    /// and carrying the new error; with nothing cached it is returned unchanged.
+    pub fn record(&mut self, output: PluginOutput) -> PluginOutput {
+        let Some(error) = output.error.clone() else {
+            self.outputs.insert(output.provider_id.clone(), output.clone());
+            if let Err(err) = self.save() {
+                log::warn!("failed to write output cache {}: {}", self.path.display(), err);
+            }
+            return output;
+        };
+        match self.outputs.get(&output.provider_id) {
+            Some(cached) => PluginOutput {
+                error: Some(error),
+                stale: true,
+                ..cached.clone()
+            },
+            None => output,
+        }
+    }
+
+    fn save(&self) -> std::io::Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_vec_pretty(&self.outputs).map_err(std::io::Error::other)?;
+        // Write then rename so a crash mid-write can't leave a truncated cache.
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(&tmp, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::error::{ProbeError, ProbeErrorKind};
+    use crate::plugin_engine::runtime::MetricLine;
+    use crate::plugin_engine::temp_dir;
+
+    fn ok_output(value: &str) -> PluginOutput {
+        PluginOutput {
+            provider_id: "codex".to_string(),
+            display_name: "Codex".to_string(),
+            plan: Some("Pro".to_string()),
+            lines: vec![MetricLine::Text {
+                label: "Session".to_string(),
+                value: value.to_string(),
+                color: None,
+                subtitle: None,
+            }],
+            icon_url: String::new(),
+            error: None,
+            fetched_at: Some("2026-01-01T00:00:00Z".to_string()),
+            stale: false,
+        }
+    }
+
+    fn failed_output() -> PluginOutput {
+        PluginOutput {
+            lines: vec![],
+            plan: None,
+            error: Some(ProbeError::new(ProbeErrorKind::Network, "offline")),
+            fetched_at: None,
+            ..ok_output("")
+        }
+    }
+
+    #[test]
+    fn success_is_persisted_and_reloaded() {
+        let dir = temp_dir("persist");
+        let mut cache = OutputCache::load(&dir);
+        cache.record(ok_output("42%"));
+
+        let reloaded = OutputCache::load(&dir);
+        let output = reloaded.get("codex").expect("cached output");
+        assert_eq!(output.plan.as_deref(), Some("Pro"));
+        assert_eq!(output.fetched_at.as_deref(), Some("2026-01-01T00:00:00Z"));
+        assert_eq!(reloaded.outputs_for(["codex", "cursor"]).len(), 1);
+        assert_eq!(reloaded.all().len(), 1);
+    }
+
+    #[test]
+    fn failure_returns_last_good_output_marked_stale() {
+        let dir = temp_dir("stale");
+        let mut cache = OutputCache::load(&dir);
+        cache.record(ok_output("42%"));
+
+        let reported = cache.record(failed_output());
+        assert!(reported.stale);
+        assert_eq!(reported.error.unwrap().kind, ProbeErrorKind::Network);
+        assert_eq!(reported.lines.len(), 1);
+        assert_eq!(reported.fetched_at.as_deref(), Some("2026-01-01T00:00:00Z"));
+
+        let cached = cache.get("codex").unwrap();
+        assert!(!cached.stale && cached.error.is_none(), "good data must not be replaced");
+    }
+
+    #[test]
+    fn failure_without_cached_output_is_unchanged() {
+        let mut cache = OutputCache::load(&temp_dir("empty"));
+        let reported = cache.record(failed_output());
+        assert!(!reported.stale);
+        assert!(reported.lines.is_empty());
+        assert!(cache.get("codex").is_none());
+    }
+
+    #[test]
+    fn corrupt_file_starts_empty() {
+        let dir = temp_dir("corrupt");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(OUTPUT_CACHE_FILE), "{not json").unwrap();
+        assert!(OutputCache::load(&dir).get("codex").is_none());
+    }
+}
@@ -0,0 +1,288 @@
+use crate::plugin_engine::error::{ProbeError, ProbeErrorKind};
+use crate::plugin_engine::host_api::expand_path;
+use crate::plugin_engine::manifest::{Access, ManifestPermissions, PathPermission};
+use std::path::{Component, Path, PathBuf};
+
+/// What a plugin may touch through `host.fs`, `host.keychain` and `host.sqlite`: the
+/// resources declared under `permissions` in `plugin.json`, plus its own data dir.
+#[derive(Debug, Clone)]
+pub struct Permissions {
+    plugin_id: String,
+    fs: Vec<(PathBuf, Access)>,
+    keychain: Vec<(String, Access)>,
+    sqlite: Vec<(PathBuf, Access)>,
+}
+
+impl Permissions {
+    pub fn new(plugin_id: &str, declared: &ManifestPermissions, plugin_data_dir: &Path) -> Self {
+        let own_dir = (resolve(plugin_data_dir), Access::Write);
+        let paths = |grants: &[PathPermission]| {
+            grants
+                .iter()
+                .map(|grant| (resolve(Path::new(&expand_path(&grant.path))), grant.access))
+                .chain(std::iter::once(own_dir.clone()))
+                .collect()
+        };
+        Self {
+            plugin_id: plugin_id.to_string(),
+            fs: paths(&declared.fs),
+            keychain: declared
+                .keychain
+                .iter()
+                .map(|grant| (grant.service.clone(), grant.access))
+                .collect(),
+            sqlite: paths(&declared.sqlite),
+        }
+    }
+
+    /// Check a `host.fs` path and return it expanded, with `.`, `..` and symlinks resolved.
+    pub fn check_fs(&self, path: &str, access: Access) -> Result<PathBuf, ProbeError> {
+        self.check_path(&self.fs, "fs", path, access)
+    }
+
+    /// Check a `host.sqlite` database path and return it like [`Self::check_fs`].
+    pub fn check_sqlite(&self, path: &str, access: Access) -> Result<PathBuf, ProbeError> {
+        self.check_path(&self.sqlite, "sqlite", path, access)
+    }
+
+    pub fn check_keychain(&self, service: &str, access: Access) -> Result<(), ProbeError> {
+        let allowed = self
+            .keychain
+            .iter()
+            .any(|(granted, granted_access)| granted == service && *granted_access >= access);
+        if allowed {
+            Ok(())
+        } else {
+            Err(self.deny("keychain", service, access))
+        }
+    }
+
+    fn check_path(
+        &self,
+        grants: &[(PathBuf, Access)],
+        api: &str,
+        path: &str,
+        access: Access,
+    ) -> Result<PathBuf, ProbeError> {
+        let resolved = resolve(Path::new(&expand_path(path)));
+        // A grant covers the path itself and, for directories, everything below it.
+        let allowed = resolved.is_absolute()
+            && grants.iter().any(|(granted, granted_access)| {
+                resolved.starts_with(granted) && *granted_access >= access
+            });
+        if allowed {
+            Ok(resolved)
+        } else {
+            Err(self.deny(api, path, access))
+        }
+    }
+
+    fn deny(&self, api: &str, resource: &str, access: Access) -> ProbeError {
+        log::warn!(
+            "[plugin:{}] denied undeclared {} {} access to {}",
+            self.plugin_id,
+            api,
+            access.as_str(),
+            resource
+        );
+        ProbeError::new(
+            ProbeErrorKind::Permission,
+            format!(
+                "{} {} access to {} is not declared in plugin.json",
+                api,
+                access.as_str(),
+                resource
+            ),
+        )
+    }
+}
+
+/// Resolve `.`, `..` and symlinks, so neither `..` nor a link inside a grant can reach
+/// outside it. Paths that don't exist yet (files about to be written) resolve through
+/// their nearest existing parent.
+fn resolve(path: &Path) -> PathBuf {
+    let normalized = normalize(path);
+    let mut existing = normalized.as_path();
+    let mut missing = Vec::new();
+    loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            return missing
+                .iter()
+                .rev()
+                .fold(canonical, |path, part| path.join(part));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name);
+                existing = parent;
+            }
+            _ => return normalized,
+        }
+    }
+}
+
+/// Resolve `.` and `..` without touching the filesystem, so `..` can't climb out of a grant.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::temp_dir;
+
+    fn permissions() -> Permissions {
+        let declared: ManifestPermissions = serde_json::from_str(
+            r#"{
+              "fs": [
+                { "path": "/home/u/.x/auth.json", "access": "write" },
+                { "path": "/home/u/.x/config", "access": "read" }
+              ],
+              "keychain": [
+                { "service": "X-credentials", "access": "write" },
+                { "service": "gh:github.com", "access": "read" }
+              ],
+              "sqlite": [{ "path": "/home/u/.x/state.db", "access": "read" }]
+            }"#,
+        )
+        .unwrap();
+        Permissions::new("x", &declared, Path::new("/data/plugins_data/x"))
+    }
+
+    #[test]
+    fn allows_declared_paths_up_to_their_access() {
+        let permissions = permissions();
+        assert_eq!(
+            permissions
+                .check_fs("/home/u/.x/auth.json", Access::Write)
+                .unwrap(),
+            PathBuf::from("/home/u/.x/auth.json")
+        );
+        assert!(permissions
+            .check_fs("/home/u/.x/config/a/b.json", Access::Read)
+            .is_ok());
+        assert!(permissions
+            .check_fs("/home/u/.x/config/b.json", Access::Write)
+            .is_err());
+        assert!(permissions
+            .check_fs("/data/plugins_data/x/state.json", Access::Write)
+            .is_ok());
+        assert!(permissions
+            .check_sqlite("/data/plugins_data/x/cache.db", Access::Write)
+            .is_ok());
+        assert!(permissions
+            .check_sqlite("/home/u/.x/state.db", Access::Read)
+            .is_ok());
+        assert!(permissions
+            .check_sqlite("/home/u/.x/state.db", Access::Write)
+            .is_err());
+    }
+
+    #[test]
+    fn denies_undeclared_and_escaping_paths() {
+        let permissions = permissions();
+        let err = permissions
+            .check_fs("/home/u/.ssh/id_ed25519", Access::Read)
+            .unwrap_err();
+        assert_eq!(err.kind, ProbeErrorKind::Permission);
+        assert_eq!(
+            err.message,
+            "fs read access to /home/u/.ssh/id_ed25519 is not declared in plugin.json"
+        );
+        assert!(!err.retryable);
+        assert!(permissions
+            .check_fs("/home/u/.x/config/../../.ssh/id_ed25519", Access::Read)
+            .is_err());
+        assert!(permissions
+            .check_fs("/data/plugins_data/x/../y/auth.json", Access::Read)
+            .is_err());
+        assert!(permissions
+            .check_fs("/home/u/.x/auth.json.bak", Access::Read)
+            .is_err());
+        assert!(permissions
+            .check_fs("relative/auth.json", Access::Read)
+            .is_err());
+        assert!(permissions
+            .check_sqlite("/home/u/.x/auth.json", Access::Read)
+            .is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_inside_a_grant_cannot_leave_it() {
+        use std::os::unix::fs::symlink;
+        let root = temp_dir("symlink");
+        let granted = root.join("granted");
+        let data_dir = root.join("plugins_data").join("x");
+        let outside = root.join("outside");
+        for dir in [&granted, &data_dir, &outside] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        std::fs::write(outside.join("secret"), "s").unwrap();
+        std::fs::write(granted.join("auth.json"), "{}").unwrap();
+        symlink(&outside, granted.join("link")).unwrap();
+        symlink(&outside, data_dir.join("link")).unwrap();
+        symlink(&granted, root.join("alias")).unwrap();
+        let declared: ManifestPermissions = serde_json::from_value(serde_json::json!({
+            "fs": [{ "path": root.join("alias"), "access": "read" }]
+        }))
+        .unwrap();
+        let permissions = Permissions::new("x", &declared, &data_dir);
+
+        let path = |path: PathBuf| path.to_string_lossy().to_string();
+        assert!(permissions
+            .check_fs(&path(granted.join("link/secret")), Access::Read)
+            .is_err());
+        assert!(permissions
+            .check_fs(&path(data_dir.join("link/secret")), Access::Read)
+            .is_err());
+        assert!(permissions
+            .check_fs(&path(data_dir.join("link/new.json")), Access::Write)
+            .is_err());
+        assert!(permissions
+            .check_sqlite(&path(data_dir.join("link/a/b.db")), Access::Write)
+            .is_err());
+
+        // Grants resolve the same way, so a grant named through a link covers its target.
+        let real = granted.canonicalize().unwrap();
+        assert_eq!(
+            permissions
+                .check_fs(&path(granted.join("auth.json")), Access::Read)
+                .unwrap(),
+            real.join("auth.json")
+        );
+        assert_eq!(
+            permissions
+                .check_fs(&path(data_dir.join("new/state.json")), Access::Write)
+                .unwrap(),
+            data_dir.canonicalize().unwrap().join("new/state.json")
+        );
+    }
+
+    #[test]
+    fn keychain_services_match_exactly() {
+        let permissions = permissions();
+        assert!(permissions
+            .check_keychain("X-credentials", Access::Write)
+            .is_ok());
+        assert!(permissions
+            .check_keychain("gh:github.com", Access::Read)
+            .is_ok());
+        assert!(permissions
+            .check_keychain("gh:github.com", Access::Write)
+            .is_err());
+        assert!(permissions
+            .check_keychain("X-credentials-2", Access::Read)
+            .is_err());
+    }
+}
@@ -0,0 +1,384 @@
+use crate::plugin_engine::alerts::{AlertEngine, AlertRules, NotificationSink};
+use crate::plugin_engine::audit::HostAudit;
+use crate::plugin_engine::forecast::{self, ForecastSamples};
+use crate::plugin_engine::history::{self, UsageHistory};
+use crate::plugin_engine::limits::ProbeConfig;
+use crate::plugin_engine::manifest::LoadedPlugin;
+use crate::plugin_engine::metrics;
+use crate::plugin_engine::output_cache::OutputCache;
+use crate::plugin_engine::runtime::{self, PluginOutput};
+use crate::plugin_engine::telemetry::{OtlpExporter, ProbeTrace, SpanRecorder};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// What happens to every probe result before it is reported, shared by the app and
+/// `openusaged`: progress lines are recorded in the usage history and get forecasts,
+/// alert rules are checked, and the last good output is cached so failures come back
+/// stale instead of empty.
+#[derive(Clone)]
+pub struct ResultPipeline {
+    pub output_cache: Arc<Mutex<OutputCache>>,
+    pub usage_history: Arc<Mutex<UsageHistory>>,
+    pub forecast_samples: Arc<Mutex<ForecastSamples>>,
+    pub alerts: Arc<Mutex<AlertEngine>>,
+    /// Host calls of the most recent probes.
+    pub host_audit: Arc<Mutex<HostAudit>>,
+    /// Last reported output per plugin this session, including failures.
+    latest: Arc<Mutex<HashMap<String, PluginOutput>>>,
+    /// Wall time of each plugin's last `run_probe`.
+    probe_durations: Arc<Mutex<BTreeMap<String, Duration>>>,
+    telemetry: Option<Arc<OtlpExporter>>,
+}
+
+impl ResultPipeline {
+    /// Open the stores kept in `app_data_dir`.
+    pub fn open(
+        app_data_dir: &Path,
+        alert_rules: AlertRules,
+        sink: Arc<dyn NotificationSink>,
+    ) -> Self {
+        Self::with_stores(
+            OutputCache::load(app_data_dir),
+            UsageHistory::open(app_data_dir),
+            ForecastSamples::load(app_data_dir),
+            AlertEngine::new(alert_rules, app_data_dir, sink),
+        )
+    }
+
+    /// Open the stores of a data dir another process holds the [`DataDirLock`] of. They
+    /// start from that process's files, but nothing is written back.
+    pub fn open_read_only(
+        app_data_dir: &Path,
+        alert_rules: AlertRules,
+        sink: Arc<dyn NotificationSink>,
+    ) -> Self {
+        Self::with_stores(
+            OutputCache::load(app_data_dir).read_only(),
+            UsageHistory::open(app_data_dir).read_only(),
+            ForecastSamples::load(app_data_dir).read_only(),
+            AlertEngine::new(alert_rules, app_data_dir, sink).read_only(),
+        )
+    }
+
+    fn with_stores(
+        output_cache: OutputCache,
+        usage_history: UsageHistory,
+        forecast_samples: ForecastSamples,
+        alerts: AlertEngine,
+    ) -> Self {
+        Self {
+            output_cache: Arc::new(Mutex::new(output_cache)),
+            usage_history: Arc::new(Mutex::new(usage_history)),
+            forecast_samples: Arc::new(Mutex::new(forecast_samples)),
+            alerts: Arc::new(Mutex::new(alerts)),
+            host_audit: Arc::new(Mutex::new(HostAudit::default())),
+            latest: Arc::new(Mutex::new(HashMap::new())),
+            probe_durations: Arc::new(Mutex::new(BTreeMap::new())),
+            telemetry: None,
+        }
+    }
+
+    /// Push a trace and gauges for every probe run through [`Self::probe`].
+    pub fn with_telemetry(mut self, exporter: OtlpExporter) -> Self {
+        self.telemetry = Some(Arc::new(exporter));
+        self
+    }
+
+    /// Probe a plugin, time it, run the output through [`Self::process`] and record its
+    /// host calls in the audit trail.
+    pub fn probe(
+        &self,
+        plugin: &LoadedPlugin,
+        app_data_dir: &Path,
+        app_version: &str,
+        config: &ProbeConfig,
+    ) -> PluginOutput {
+        let spans = SpanRecorder::default();
+        let start = SystemTime::now();
+        let started = Instant::now();
+        let output = runtime::run_probe_traced(plugin, app_data_dir, app_version, config, &spans);
+        let duration = started.elapsed();
+        if let Ok(mut durations) = self.probe_durations.lock() {
+            durations.insert(plugin.manifest.id.clone(), duration);
+        }
+        let output = self.process(output);
+        let trace = ProbeTrace {
+            start,
+            duration,
+            spans: spans.take(),
+        };
+        if let Ok(mut audit) = self.host_audit.lock() {
+            audit.record(&trace, &output);
+        }
+        if let Some(exporter) = &self.telemetry {
+            exporter.export_probe(&trace, &output);
+        }
+        output
+    }
+
+    /// Prometheus exposition of the current outputs and probe timings of `plugin_ids`.
+    pub fn metrics<'a, I: IntoIterator<Item = &'a str> + Clone>(&self, plugin_ids: I) -> String {
+        let durations: BTreeMap<String, Duration> = match self.probe_durations.lock() {
+            Ok(durations) => plugin_ids
+                .clone()
+                .into_iter()
+                .filter_map(|id| {
+                    durations
+                        .get(id)
+                        .map(|duration| (id.to_string(), *duration))
+                })
+                .collect(),
+            Err(_) => BTreeMap::new(),
+        };
+        metrics::render(&self.current(plugin_ids), &durations)
+    }
+
+    /// What the panel currently shows for each of `plugin_ids`: the last reported output,
+    /// or the cached one for plugins not probed yet this session.
+    pub fn current<'a, I: IntoIterator<Item = &'a str>>(&self, plugin_ids: I) -> Vec<PluginOutput> {
+        let latest = self.latest.lock().ok();
+        let cache = self.output_cache.lock().ok();
+        plugin_ids
+            .into_iter()
+            .filter_map(|id| {
+                latest
+                    .as_ref()
+                    .and_then(|latest| latest.get(id).cloned())
+                    .or_else(|| cache.as_ref().and_then(|cache| cache.get(id).cloned()))
+            })
+            .collect()
+    }
+
+    /// Run a finished probe's output through the stores and return what to report.
+    pub fn process(&self, mut output: PluginOutput) -> PluginOutput {
+        match &output.error {
+            Some(error) => log::warn!(
+                "probe {} completed with error ({})",
+                output.provider_id,
+                error
+            ),
+            None => log::info!(
+                "probe {} completed ok ({} lines)",
+                output.provider_id,
+                output.lines.len()
+            ),
+        }
+        let now_ms = history::now_ms();
+        if let Ok(mut history) = self.usage_history.lock() {
+            if let Err(err) = history.record_output(&output, now_ms) {
+                log::warn!(
+                    "failed to record usage history for {}: {}",
+                    output.provider_id,
+                    err
+                );
+            }
+        }
+        if let Ok(mut samples) = self.forecast_samples.lock() {
+            forecast::attach_forecasts(&mut output, &mut samples, now_ms);
+            if let Err(err) = samples.save() {
+                log::warn!("failed to save forecast samples: {}", err);
+            }
+        }
+        if let Ok(mut alerts) = self.alerts.lock() {
+            alerts.evaluate(&output);
+        }
+        // Keep the last good data on failure; the result is then marked stale.
+        let reported = match self.output_cache.lock() {
+            Ok(mut cache) => cache.record(output),
+            Err(_) => output,
+        };
+        if let Ok(mut latest) = self.latest.lock() {
+            latest.insert(reported.provider_id.clone(), reported.clone());
+        }
+        reported
+    }
+}
+
+/// Held by the one process that writes a data dir's result stores.
+const LOCK_FILE: &str = "openusage.lock";
+
+/// Exclusive claim on a data dir. Every [`ResultPipeline`] keeps the output cache,
+/// usage history, forecast samples and alert state in memory and rewrites their files from it, so two
+/// processes on one dir would overwrite each other's writes. The lock is released when
+/// this is dropped or the process exits.
+pub struct DataDirLock {
+    _file: File,
+}
+
+impl DataDirLock {
+    pub fn acquire(data_dir: &Path) -> Result<Self, String> {
+        std::fs::create_dir_all(data_dir)
+            .map_err(|err| format!("failed to create {}: {}", data_dir.display(), err))?;
+        let path = data_dir.join(LOCK_FILE);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(|err| format!("failed to open {}: {}", path.display(), err))?;
+        match file.try_lock() {
+            Ok(()) => {
+                // Only informational: tells whoever is locked out which process to look for.
+                let _ = file.set_len(0);
+                let _ = write!(file, "{}", std::process::id());
+                Ok(Self { _file: file })
+            }
+            Err(TryLockError::WouldBlock) => {
+                let holder = std::fs::read_to_string(&path)
+                    .ok()
+                    .filter(|pid| !pid.trim().is_empty())
+                    .map(|pid| format!(" (pid {})", pid.trim()))
+                    .unwrap_or_default();
+                Err(format!(
+                    "{} is in use by another OpenUsage app or openusaged{}; \
+                     give each one its own data dir",
+                    data_dir.display(),
+                    holder
+                ))
+            }
+            Err(TryLockError::Error(err)) => {
+                Err(format!("failed to lock {}: {}", path.display(), err))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::alerts::AlertNotification;
+    use crate::plugin_engine::error::{ProbeError, ProbeErrorKind};
+    use crate::plugin_engine::history::HistoryRange;
+    use crate::plugin_engine::runtime::{MetricLine, ProgressFormat};
+    use crate::plugin_engine::temp_dir;
+
+    #[derive(Default)]
+    struct CountingSink(Mutex<usize>);
+
+    impl NotificationSink for CountingSink {
+        fn notify(&self, _notification: &AlertNotification) {
+            *self.0.lock().unwrap() += 1;
+        }
+    }
+
+    fn output(used: f64, error: Option<ProbeError>) -> PluginOutput {
+        PluginOutput {
+            provider_id: "codex".to_string(),
+            display_name: "Codex".to_string(),
+            plan: None,
+            lines: vec![MetricLine::Progress {
+                label: "Session".to_string(),
+                used,
+                limit: 100.0,
+                format: ProgressFormat::Percent,
+                resets_at: None,
+                period_duration_ms: None,
+                color: None,
+                forecast: None,
+            }],
+            icon_url: String::new(),
+            error,
+            fetched_at: None,
+            stale: false,
+        }
+    }
+
+    #[test]
+    fn success_feeds_every_store_and_failure_comes_back_stale() {
+        let dir = temp_dir("process");
+        let sink = Arc::new(CountingSink::default());
+        let pipeline = ResultPipeline::open(&dir, AlertRules::default(), sink.clone());
+
+        let reported = pipeline.process(output(90.0, None));
+        assert!(!reported.stale);
+        assert_eq!(*sink.0.lock().unwrap(), 1, "80% threshold fired");
+        let samples = pipeline.usage_history.lock().unwrap().query(
+            "codex",
+            "Session",
+            HistoryRange::default(),
+        );
+        assert_eq!(samples.len(), 1);
+        let forecast_samples = pipeline.forecast_samples.lock().unwrap();
+        assert_eq!(forecast_samples.get("codex", "Session").len(), 1);
+        drop(forecast_samples);
+
+        let failed = output(
+            0.0,
+            Some(ProbeError::new(ProbeErrorKind::Network, "offline")),
+        );
+        let reported = pipeline.process(failed);
+        assert!(reported.stale);
+        assert!(matches!(reported.lines[0], MetricLine::Progress { used, .. } if used == 90.0));
+        assert_eq!(*sink.0.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn data_dir_lock_refuses_a_second_writer() {
+        let dir = temp_dir("lock");
+        let first = DataDirLock::acquire(&dir).expect("first lock");
+        let err = DataDirLock::acquire(&dir)
+            .err()
+            .expect("second lock refused");
+        assert!(
+            err.contains(&format!("pid {}", std::process::id())),
+            "{}",
+            err
+        );
+        drop(first);
+        assert!(DataDirLock::acquire(&dir).is_ok());
+    }
+
+    #[test]
+    fn read_only_pipeline_reports_without_writing() {
+        let dir = temp_dir("read-only");
+        let sink = Arc::new(CountingSink::default());
+        ResultPipeline::open(&dir, AlertRules::default(), sink.clone()).process(output(10.0, None));
+        let files = |dir: &Path| {
+            let mut files: Vec<_> = std::fs::read_dir(dir)
+                .unwrap()
+                .map(|entry| {
+                    let path = entry.unwrap().path();
+                    (path.clone(), std::fs::read(&path).ok())
+                })
+                .collect();
+            files.sort();
+            files
+        };
+        let before = files(&dir);
+
+        let pipeline = ResultPipeline::open_read_only(&dir, AlertRules::default(), sink.clone());
+        assert_eq!(
+            pipeline.current(["codex"]).len(),
+            1,
+            "starts from the owner's cache"
+        );
+        let reported = pipeline.process(output(90.0, None));
+        assert!(!reported.stale);
+        assert_eq!(*sink.0.lock().unwrap(), 1, "alerts still fire");
+        assert_eq!(files(&dir), before);
+    }
+
+    #[test]
+    fn current_prefers_this_sessions_result_over_the_cache() {
+        let dir = temp_dir("current");
+        let sink = Arc::new(CountingSink::default());
+        ResultPipeline::open(&dir, AlertRules::default(), sink.clone()).process(output(10.0, None));
+
+        let restarted = ResultPipeline::open(&dir, AlertRules::default(), sink);
+        let current = restarted.current(["codex", "claude"]);
+        assert_eq!(current.len(), 1);
+        assert!(!current[0].stale);
+
+        restarted.process(output(
+            0.0,
+            Some(ProbeError::new(ProbeErrorKind::Network, "offline")),
+        ));
+        let current = restarted.current(["codex"]);
+        assert!(current[0].stale && current[0].error.is_some());
+    }
+}
@@ -1,10 +1,16 @@
-use crate::plugin_engine::host_api;
-use crate::plugin_engine::manifest::LoadedPlugin;
-use rquickjs::{Array, Context, Ctx, Error, Object, Promise, Runtime, Value};
-use serde::Serialize;
-use std::path::PathBuf;
+use crate::plugin_engine::bytecode::{BytecodeCache, EvaluatedEntry};
+use crate::plugin_engine::error::{ProbeError, ProbeErrorKind};
+use crate::plugin_engine::forecast::Forecast;
+use crate::plugin_engine::host_api::{self, AsyncHost, HostCompletions};
+use crate::plugin_engine::limits::{BudgetAllocator, Deadline, MemoryBudget, ProbeConfig};
+use crate::plugin_engine::manifest::{EntryType, LoadedPlugin};
+use crate::plugin_engine::modules::PluginModules;
+use crate::plugin_engine::telemetry::SpanRecorder;
+use rquickjs::{Array, Context, Ctx, Error, FromJs, Object, Promise, Runtime, Value};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "kind", rename_all = "camelCase")]
 pub enum ProgressFormat {
     Percent,
@@ -12,7 +18,7 @@ pub enum ProgressFormat {
     Count { suffix: String },
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "camelCase")]
 pub enum MetricLine {
     Text {
@@ -31,6 +37,9 @@ pub enum MetricLine {
         #[serde(rename = "periodDurationMs")]
         period_duration_ms: Option<u64>,
         color: Option<String>,
+        /// Filled in by the host from usage history; plugins don't set it.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        forecast: Option<Forecast>,
     },
     Badge {
         label: String,
@@ -40,7 +49,7 @@ pub enum MetricLine {
     },
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PluginOutput {
     pub provider_id: String,
@@ -48,55 +57,106 @@ pub struct PluginOutput {
     pub plan: Option<String>,
     pub lines: Vec<MetricLine>,
     pub icon_url: String,
+    /// Set when the probe failed as a whole; `lines` is empty in that case unless `stale`.
+    pub error: Option<ProbeError>,
+    /// RFC 3339 time `lines` were fetched; older than the probe itself when `stale`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fetched_at: Option<String>,
+    /// The probe failed and `lines` are the last successful ones, kept from an earlier probe.
+    #[serde(default)]
+    pub stale: bool,
 }
 
 pub fn run_probe(
     plugin: &LoadedPlugin,
     app_data_dir: &PathBuf,
     app_version: &str,
+    config: &ProbeConfig,
 ) -> PluginOutput {
-    let fallback = error_output(plugin, "runtime error".to_string());
+    run_probe_traced(plugin, app_data_dir, app_version, config, &SpanRecorder::default())
+}
+
+/// [`run_probe`], recording each `host.http` request and `host.sqlite` call in `spans`.
+pub fn run_probe_traced(
+    plugin: &LoadedPlugin,
+    app_data_dir: &Path,
+    app_version: &str,
+    config: &ProbeConfig,
+    spans: &SpanRecorder,
+) -> PluginOutput {
+    let fallback = internal_error(plugin, "runtime error");
 
-    let rt = match Runtime::new() {
+    let limits = config.limits_for(&plugin.manifest);
+    let (allocator, memory) = BudgetAllocator::new(limits.memory_limit_bytes);
+    let rt = match Runtime::new_with_alloc(allocator) {
         Ok(rt) => rt,
         Err(_) => return fallback,
     };
+    rt.set_max_stack_size(limits.stack_limit_bytes);
+
+    let deadline = Deadline::start(limits.timeout);
+    rt.set_interrupt_handler(Some(Box::new(move || deadline.expired())));
+
+    if plugin.manifest.entry_type == EntryType::Module {
+        let modules = PluginModules::new(&plugin.plugin_dir)
+            .with_cache(&plugin.manifest.id, BytecodeCache::shared());
+        rt.set_loader(modules.clone(), modules);
+    }
 
     let ctx = match Context::full(&rt) {
         Ok(ctx) => ctx,
+        Err(_) if memory.exhausted() => return memory_output(plugin, &memory),
         Err(_) => return fallback,
     };
 
+    let (async_host, completions) = AsyncHost::new();
+
     let plugin_id = plugin.manifest.id.clone();
     let display_name = plugin.manifest.name.clone();
-    let entry_script = plugin.entry_script.clone();
     let icon_url = plugin.icon_data_url.clone();
-    let app_data = app_data_dir.clone();
+    let app_data = app_data_dir.to_path_buf();
 
     ctx.with(|ctx| {
-        if host_api::inject_host_api(&ctx, &plugin_id, &app_data, app_version).is_err() {
-            return error_output(plugin, "host api injection failed".to_string());
+        if host_api::inject_host_api(
+            &ctx,
+            &plugin.manifest,
+            &app_data,
+            app_version,
+            deadline,
+            &async_host,
+            spans,
+            config,
+        )
+        .is_err()
+        {
+            return internal_error(plugin, "host api injection failed");
         }
         if host_api::patch_http_wrapper(&ctx).is_err() {
-            return error_output(plugin, "http wrapper patch failed".to_string());
+            return internal_error(plugin, "http wrapper patch failed");
         }
         if host_api::inject_utils(&ctx).is_err() {
-            return error_output(plugin, "utils injection failed".to_string());
+            return internal_error(plugin, "utils injection failed");
         }
 
-        if ctx.eval::<(), _>(entry_script.as_bytes()).is_err() {
-            return error_output(plugin, "script eval failed".to_string());
+        if let Err(err) = eval_entry(&ctx, plugin, &completions, &deadline) {
+            if let Some(output) = limit_output(plugin, &deadline, &memory) {
+                return output;
+            }
+            if let Error::WouldBlock = err {
+                return plugin_bug(plugin, "module evaluation did not finish");
+            }
+            return error_output(plugin, extract_error(&ctx, plugin, "script eval failed"));
         }
 
         let globals = ctx.globals();
         let plugin_obj: Object = match globals.get("__openusage_plugin") {
             Ok(obj) => obj,
-            Err(_) => return error_output(plugin, "missing __openusage_plugin".to_string()),
+            Err(_) => return plugin_bug(plugin, "missing __openusage_plugin"),
         };
 
         let probe_fn: rquickjs::Function = match plugin_obj.get("probe") {
             Ok(f) => f,
-            Err(_) => return error_output(plugin, "missing probe()".to_string()),
+            Err(_) => return plugin_bug(plugin, "missing probe()"),
         };
 
         let probe_ctx: Value = globals
@@ -105,24 +165,33 @@ pub fn run_probe(
 
         let result_value: Value = match probe_fn.call((probe_ctx,)) {
             Ok(r) => r,
-            Err(_) => return error_output(plugin, extract_error_string(&ctx)),
+            Err(_) => {
+                if let Some(output) = limit_output(plugin, &deadline, &memory) {
+                    return output;
+                }
+                return error_output(plugin, extract_error(&ctx, plugin, GENERIC_FAILURE));
+            }
         };
         let result: Object = if result_value.is_promise() {
             let promise: Promise = match result_value.into_promise() {
                 Some(promise) => promise,
-                None => return error_output(plugin, "probe() returned invalid promise".to_string()),
+                None => return plugin_bug(plugin, "probe() returned invalid promise"),
             };
-            match promise.finish::<Object>() {
+            match drive_promise(&ctx, &promise, &completions, &deadline) {
                 Ok(obj) => obj,
+                Err(_) if deadline.expired() || memory.exhausted() => {
+                    return limit_output(plugin, &deadline, &memory)
+                        .unwrap_or_else(|| error_output(plugin, extract_error(&ctx, plugin, GENERIC_FAILURE)))
+                }
                 Err(Error::WouldBlock) => {
-                    return error_output(plugin, "probe() returned unresolved promise".to_string())
+                    return plugin_bug(plugin, "probe() returned unresolved promise")
                 }
-                Err(_) => return error_output(plugin, extract_error_string(&ctx)),
+                Err(_) => return error_output(plugin, extract_error(&ctx, plugin, GENERIC_FAILURE)),
             }
         } else {
             match result_value.into_object() {
                 Some(obj) => obj,
-                None => return error_output(plugin, "probe() returned non-object".to_string()),
+                None => return plugin_bug(plugin, "probe() returned non-object"),
             }
         };
 
@@ -130,8 +199,8 @@ pub fn run_probe(
 
         let lines = match parse_lines(&result) {
             Ok(lines) if !lines.is_empty() => lines,
-            Ok(_) => vec![error_line("no lines returned".to_string())],
-            Err(msg) => vec![error_line(msg)],
+            Ok(_) => return plugin_bug(plugin, "no lines returned"),
+            Err(msg) => return plugin_bug(plugin, &msg),
         };
 
         PluginOutput {
@@ -140,10 +209,75 @@ pub fn run_probe(
             plan,
             lines,
             icon_url,
+            error: None,
+            fetched_at: Some(host_api::iso_now()),
+            stale: false,
         }
     })
 }
 
+/// Evaluate the entry from cached bytecode (compiling it on first use). Scripts are
+/// compiled under their manifest file name so stack frames point at the plugin's own
+/// file and lines. A module may either assign `globalThis.__openusage_plugin` or
+/// `export default { id, probe }`.
+fn eval_entry<'js>(
+    ctx: &Ctx<'js>,
+    plugin: &LoadedPlugin,
+    completions: &HostCompletions,
+    deadline: &Deadline,
+) -> rquickjs::Result<()> {
+    let file_name = match plugin.manifest.entry_type {
+        EntryType::Script => plugin.manifest.entry.clone(),
+        EntryType::Module => PluginModules::new(&plugin.plugin_dir)
+            .module_name(&plugin.manifest.entry)
+            .unwrap_or_else(|_| plugin.manifest.entry.clone()),
+    };
+    let evaluated = BytecodeCache::shared().eval_entry(
+        ctx,
+        &plugin.manifest.id,
+        plugin.manifest.entry_type,
+        &file_name,
+        &plugin.entry_script,
+    )?;
+    let EvaluatedEntry::Module { promise, namespace } = evaluated else {
+        return Ok(());
+    };
+    drive_promise::<Value>(ctx, &promise, completions, deadline)?;
+
+    let globals = ctx.globals();
+    if !globals.contains_key("__openusage_plugin")? {
+        if let Ok(default) = namespace.get::<_, Object>("default") {
+            globals.set("__openusage_plugin", default)?;
+        }
+    }
+    Ok(())
+}
+
+/// Run the QuickJS job queue until `promise` settles, feeding in completions of
+/// async host calls as they arrive. Returns `WouldBlock` when nothing can settle it.
+fn drive_promise<'js, T: FromJs<'js>>(
+    ctx: &Ctx<'js>,
+    promise: &Promise<'js>,
+    completions: &HostCompletions,
+    deadline: &Deadline,
+) -> rquickjs::Result<T> {
+    loop {
+        if let Some(result) = promise.result::<T>() {
+            return result;
+        }
+        if ctx.execute_pending_job() {
+            continue;
+        }
+        if completions.in_flight() == 0 || deadline.expired() {
+            return Err(Error::WouldBlock);
+        }
+        match completions.wait(deadline.remaining()) {
+            Some(completion) => host_api::settle_completion(ctx, completion)?,
+            None => return Err(Error::WouldBlock),
+        }
+    }
+}
+
 fn parse_lines(result: &Object) -> Result<Vec<MetricLine>, String> {
     let lines: Array = result
         .get("lines")
@@ -390,6 +524,7 @@ fn parse_lines(result: &Object) -> Result<Vec<MetricLine>, String> {
                     resets_at,
                     period_duration_ms,
                     color,
+                    forecast: None,
                 });
             }
             "badge" => {
@@ -408,29 +543,148 @@ fn parse_lines(result: &Object) -> Result<Vec<MetricLine>, String> {
     Ok(out)
 }
 
-fn error_output(plugin: &LoadedPlugin, message: String) -> PluginOutput {
+const GENERIC_FAILURE: &str = "The plugin failed, try again or contact plugin author.";
+
+pub fn error_output(plugin: &LoadedPlugin, error: ProbeError) -> PluginOutput {
     PluginOutput {
         provider_id: plugin.manifest.id.clone(),
         display_name: plugin.manifest.name.clone(),
         plan: None,
-        lines: vec![error_line(message)],
+        lines: Vec::new(),
         icon_url: plugin.icon_data_url.clone(),
+        error: Some(error),
+        fetched_at: None,
+        stale: false,
     }
 }
 
-fn extract_error_string(ctx: &Ctx<'_>) -> String {
-    let exc = ctx.catch();
-    if exc.is_null() || exc.is_undefined() {
-        return "The plugin failed, try again or contact plugin author.".to_string();
+fn plugin_bug(plugin: &LoadedPlugin, message: &str) -> PluginOutput {
+    error_output(plugin, ProbeError::new(ProbeErrorKind::PluginBug, message))
+}
+
+fn internal_error(plugin: &LoadedPlugin, message: &str) -> PluginOutput {
+    error_output(plugin, ProbeError::new(ProbeErrorKind::Internal, message))
+}
+
+/// Map a failed probe to a timeout or out-of-memory error when a sandbox limit tripped.
+fn limit_output(
+    plugin: &LoadedPlugin,
+    deadline: &Deadline,
+    memory: &MemoryBudget,
+) -> Option<PluginOutput> {
+    if memory.exhausted() {
+        return Some(memory_output(plugin, memory));
+    }
+    if deadline.expired() {
+        return Some(timeout_output(plugin, deadline));
     }
+    None
+}
+
+fn memory_output(plugin: &LoadedPlugin, memory: &MemoryBudget) -> PluginOutput {
+    log::warn!(
+        "[plugin:{}] probe exceeded its {} byte memory limit",
+        plugin.manifest.id,
+        memory.limit_bytes()
+    );
+    error_output(
+        plugin,
+        ProbeError::new(
+            ProbeErrorKind::PluginBug,
+            format!(
+                "Plugin exceeded memory limit ({} MB).",
+                memory.limit_bytes() / (1024 * 1024)
+            ),
+        ),
+    )
+}
+
+fn timeout_output(plugin: &LoadedPlugin, deadline: &Deadline) -> PluginOutput {
+    log::warn!(
+        "[plugin:{}] probe exceeded its {}ms deadline",
+        plugin.manifest.id,
+        deadline.timeout().as_millis()
+    );
+    error_output(
+        plugin,
+        ProbeError::new(
+            ProbeErrorKind::Timeout,
+            format!(
+                "Probe timed out after {}s. Try again later.",
+                deadline.timeout().as_secs_f64()
+            ),
+        ),
+    )
+}
+
+/// Classify the pending exception. Plugins may throw a plain string or `Error`
+/// (kind `unknown`), or an object with `kind`, `message` and optional `retryable`,
+/// like the host APIs do. Built-in error types (`TypeError`, ...) are plugin bugs.
+fn extract_error(ctx: &Ctx<'_>, plugin: &LoadedPlugin, fallback: &str) -> ProbeError {
+    let exc = ctx.catch();
     if let Some(str_val) = exc.as_string() {
         let message: String = str_val.to_string().unwrap_or_default();
         let trimmed = message.trim();
         if !trimmed.is_empty() {
-            return trimmed.to_string();
+            return ProbeError::new(ProbeErrorKind::Unknown, trimmed);
         }
     }
-    "The plugin failed, try again or contact plugin author.".to_string()
+    if let Some(obj) = exc.as_object() {
+        let kind = obj
+            .get::<_, String>("kind")
+            .ok()
+            .and_then(|kind| ProbeErrorKind::parse(&kind));
+        let message = obj
+            .get::<_, String>("message")
+            .ok()
+            .map(|message| message.trim().to_string())
+            .filter(|message| !message.is_empty());
+        let stack = obj
+            .get::<_, String>("stack")
+            .ok()
+            .map(|stack| stack.trim_end().to_string())
+            .filter(|stack| !stack.is_empty());
+
+        let mut error = match (kind, message) {
+            (Some(kind), Some(message)) => ProbeError::new(kind, message),
+            (None, Some(message)) if exc.is_error() => {
+                let name = obj
+                    .get::<_, String>("name")
+                    .unwrap_or_else(|_| "Error".to_string());
+                if name == "Error" {
+                    ProbeError::new(ProbeErrorKind::Unknown, message)
+                } else {
+                    ProbeError::new(ProbeErrorKind::PluginBug, format!("{}: {}", name, message))
+                }
+            }
+            _ => ProbeError::new(ProbeErrorKind::PluginBug, fallback),
+        };
+        if let Ok(retryable) = obj.get::<_, bool>("retryable") {
+            error.retryable = retryable;
+        }
+        if let Some(stack) = stack {
+            let stack = host_api::redact_log_message(&map_stack(&stack));
+            log::error!(
+                "[plugin:{}] uncaught {}\n{}",
+                plugin.manifest.id,
+                host_api::redact_log_message(&error.message),
+                stack
+            );
+            error.stack = Some(stack);
+        }
+        return error;
+    }
+    ProbeError::new(ProbeErrorKind::PluginBug, fallback)
+}
+
+/// Frames from the host's injected helpers are labelled `<host>`; plugin frames already
+/// carry the entry file name (see `eval_entry`).
+fn map_stack(stack: &str) -> String {
+    stack
+        .lines()
+        .map(|line| line.trim().replace("(eval_script:", "(<host>:"))
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 fn error_line(message: String) -> MetricLine {
@@ -445,10 +699,10 @@ fn error_line(message: String) -> MetricLine {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::plugin_engine::manifest::{LoadedPlugin, PluginManifest};
+    use crate::plugin_engine::manifest::{LoadedPlugin, ManifestLimits, ManifestPermissions, PluginManifest};
+    use crate::plugin_engine::temp_dir;
     use serde_json::Value as JsonValue;
     use std::path::PathBuf;
-    use std::time::{SystemTime, UNIX_EPOCH};
 
     fn test_plugin(entry_script: &str) -> LoadedPlugin {
         LoadedPlugin {
@@ -458,9 +712,12 @@ mod tests {
                 name: "Test".to_string(),
                 version: "0.0.0".to_string(),
                 entry: "plugin.js".to_string(),
+                entry_type: EntryType::Script,
                 icon: "icon.svg".to_string(),
                 brand_color: None,
                 lines: vec![],
+                limits: ManifestLimits::default(),
+                permissions: ManifestPermissions::default(),
             },
             plugin_dir: PathBuf::from("."),
             entry_script: entry_script.to_string(),
@@ -468,19 +725,28 @@ mod tests {
         }
     }
 
-    fn temp_app_dir(label: &str) -> PathBuf {
-        let nanos = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_nanos();
-        std::env::temp_dir().join(format!("openusage-test-{}-{}", label, nanos))
+    /// Write `files` into a fresh plugin directory and return a module plugin for it.
+    fn module_plugin(label: &str, files: &[(&str, &str)]) -> LoadedPlugin {
+        let dir = temp_dir(label).join("plugin");
+        for (name, source) in files {
+            let path = dir.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).expect("create dir");
+            std::fs::write(&path, source).expect("write file");
+        }
+        let mut plugin = test_plugin(files[0].1);
+        plugin.manifest.entry = files[0].0.to_string();
+        plugin.manifest.entry_type = EntryType::Module;
+        plugin.plugin_dir = dir;
+        plugin
+    }
+
+    fn probe_error(output: PluginOutput) -> ProbeError {
+        assert!(output.lines.is_empty(), "error output should carry no lines");
+        output.error.expect("expected probe error")
     }
 
     fn error_text(output: PluginOutput) -> String {
-        match output.lines.first() {
-            Some(MetricLine::Badge { text, .. }) => text.clone(),
-            other => panic!("expected error badge, got {:?}", other),
-        }
+        probe_error(output).message
     }
 
     #[test]
@@ -494,8 +760,10 @@ mod tests {
             };
             "#,
         );
-        let output = run_probe(&plugin, &temp_app_dir("sync"), "0.0.0");
-        assert_eq!(error_text(output), "boom");
+        let output = run_probe(&plugin, &temp_dir("sync"), "0.0.0", &ProbeConfig::default());
+        let error = probe_error(output);
+        assert_eq!(error.message, "boom");
+        assert_eq!(error.kind, ProbeErrorKind::Unknown);
     }
 
     #[test]
@@ -509,10 +777,497 @@ mod tests {
             };
             "#,
         );
-        let output = run_probe(&plugin, &temp_app_dir("async"), "0.0.0");
+        let output = run_probe(&plugin, &temp_dir("async"), "0.0.0", &ProbeConfig::default());
         assert_eq!(error_text(output), "boom");
     }
 
+    #[test]
+    fn run_probe_uses_kind_from_thrown_object() {
+        let plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe() {
+                    throw { kind: "auth", message: "Token expired. Sign in again." };
+                }
+            };
+            "#,
+        );
+        let error = probe_error(run_probe(&plugin, &temp_dir("kind"), "0.0.0", &ProbeConfig::default()));
+        assert_eq!(error.kind, ProbeErrorKind::Auth);
+        assert_eq!(error.message, "Token expired. Sign in again.");
+        assert!(!error.retryable);
+    }
+
+    #[test]
+    fn run_probe_honors_retryable_override() {
+        let plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe: async function () {
+                    throw { kind: "provider", message: "Usage API disabled.", retryable: false };
+                }
+            };
+            "#,
+        );
+        let error = probe_error(run_probe(&plugin, &temp_dir("retry"), "0.0.0", &ProbeConfig::default()));
+        assert_eq!(error.kind, ProbeErrorKind::Provider);
+        assert!(!error.retryable);
+    }
+
+    #[test]
+    fn run_probe_reports_unclassified_exception_as_plugin_bug() {
+        let plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe() {
+                    return undefinedFn();
+                }
+            };
+            "#,
+        );
+        let error = probe_error(run_probe(&plugin, &temp_dir("bug"), "0.0.0", &ProbeConfig::default()));
+        assert_eq!(error.kind, ProbeErrorKind::PluginBug);
+        assert!(!error.retryable);
+    }
+
+    #[test]
+    fn run_probe_reports_missing_lines_as_plugin_bug() {
+        let plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe() {
+                    return { lines: [] };
+                }
+            };
+            "#,
+        );
+        let error = probe_error(run_probe(&plugin, &temp_dir("nolines"), "0.0.0", &ProbeConfig::default()));
+        assert_eq!(error.kind, ProbeErrorKind::PluginBug);
+        assert_eq!(error.message, "no lines returned");
+    }
+
+    #[test]
+    fn run_probe_preserves_error_message_and_maps_stack() {
+        let plugin = test_plugin(
+            "globalThis.__openusage_plugin = {\n  probe() {\n    throw new Error(\"Usage API changed\");\n  }\n};\n",
+        );
+        let error = probe_error(run_probe(&plugin, &temp_dir("error-obj"), "0.0.0", &ProbeConfig::default()));
+        assert_eq!(error.kind, ProbeErrorKind::Unknown);
+        assert_eq!(error.message, "Usage API changed");
+        let stack = error.stack.expect("stack");
+        assert!(stack.contains("plugin.js:3"), "stack should point at plugin.js:3, got {}", stack);
+        assert!(!stack.contains("eval_script"), "unexpected eval_script frame in {}", stack);
+    }
+
+    #[test]
+    fn run_probe_reports_type_error_with_name() {
+        let plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe: async function () {
+                    var data = null;
+                    return data.lines;
+                }
+            };
+            "#,
+        );
+        let error = probe_error(run_probe(&plugin, &temp_dir("type-error"), "0.0.0", &ProbeConfig::default()));
+        assert_eq!(error.kind, ProbeErrorKind::PluginBug);
+        assert!(error.message.starts_with("TypeError: "), "got {}", error.message);
+        let stack = error.stack.expect("stack");
+        assert!(stack.contains("at probe (plugin.js:"), "got {}", stack);
+    }
+
+    #[test]
+    fn run_probe_reports_syntax_error_location() {
+        let plugin = test_plugin("globalThis.__openusage_plugin = {\n  probe() { return {\n};\n");
+        let error = probe_error(run_probe(&plugin, &temp_dir("syntax"), "0.0.0", &ProbeConfig::default()));
+        assert_eq!(error.kind, ProbeErrorKind::PluginBug);
+        assert!(error.message.starts_with("SyntaxError: "), "got {}", error.message);
+        assert!(error.stack.expect("stack").contains("plugin.js:"));
+    }
+
+    #[test]
+    fn run_probe_loads_module_with_relative_imports() {
+        let plugin = module_plugin(
+            "module",
+            &[
+                (
+                    "plugin.js",
+                    r#"
+                    import { label } from "./lib/format.js";
+                    export default {
+                        id: "test",
+                        probe(ctx) {
+                            return { lines: [ctx.line.text({ label: label("usage"), value: "ok" })] };
+                        }
+                    };
+                    "#,
+                ),
+                (
+                    "lib/format.js",
+                    r#"
+                    import { upper } from "../util.js";
+                    export function label(text) { return upper(text); }
+                    "#,
+                ),
+                ("util.js", "export const upper = (s) => s.toUpperCase();"),
+            ],
+        );
+        // The second probe evaluates the entry from cached bytecode.
+        for _ in 0..2 {
+            let output = run_probe(&plugin, &temp_dir("module-out"), "0.0.0", &ProbeConfig::default());
+            assert!(output.error.is_none(), "unexpected error: {:?}", output.error);
+            match &output.lines[0] {
+                MetricLine::Text { label, .. } => assert_eq!(label, "USAGE"),
+                other => panic!("expected text line, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn run_probe_rejects_module_import_outside_plugin_dir() {
+        let plugin = module_plugin(
+            "module-escape",
+            &[
+                (
+                    "plugin.js",
+                    r#"
+                    import secret from "../outside.js";
+                    globalThis.__openusage_plugin = { id: "test", probe() { return secret; } };
+                    "#,
+                ),
+                ("../outside.js", "export default { lines: [] };"),
+            ],
+        );
+        let error = probe_error(run_probe(&plugin, &temp_dir("escape-out"), "0.0.0", &ProbeConfig::default()));
+        assert!(
+            error.message.contains("must remain within plugin directory"),
+            "got {}",
+            error.message
+        );
+    }
+
+    #[test]
+    fn run_probe_rejects_bare_module_specifier() {
+        let plugin = module_plugin(
+            "module-bare",
+            &[(
+                "plugin.js",
+                r#"
+                import fs from "fs";
+                globalThis.__openusage_plugin = { id: "test", probe() { return fs; } };
+                "#,
+            )],
+        );
+        let error = probe_error(run_probe(&plugin, &temp_dir("bare-out"), "0.0.0", &ProbeConfig::default()));
+        assert!(error.message.contains("only relative imports"), "got {}", error.message);
+    }
+
+    #[test]
+    fn run_probe_interrupts_infinite_loop_at_deadline() {
+        let mut plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe() {
+                    while (true) {}
+                }
+            };
+            "#,
+        );
+        plugin.manifest.limits.timeout_ms = Some(200);
+        let started = std::time::Instant::now();
+        let output = run_probe(&plugin, &temp_dir("loop"), "0.0.0", &ProbeConfig::default());
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        let error = probe_error(output);
+        assert!(error.message.contains("timed out"));
+        assert_eq!(error.kind, ProbeErrorKind::Timeout);
+        assert!(error.retryable);
+    }
+
+    #[test]
+    fn run_probe_uses_configured_default_timeout() {
+        let plugin = test_plugin(
+            r#"
+            while (true) {}
+            "#,
+        );
+        let config = ProbeConfig {
+            default_timeout_ms: 100,
+            ..ProbeConfig::default()
+        };
+        let output = run_probe(&plugin, &temp_dir("eval-loop"), "0.0.0", &config);
+        assert!(error_text(output).contains("timed out"));
+    }
+
+    #[test]
+    fn run_probe_reports_memory_limit_exceeded() {
+        let mut plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe() {
+                    var chunks = [];
+                    while (true) {
+                        chunks.push(new Array(10000).fill("x" + chunks.length));
+                    }
+                }
+            };
+            "#,
+        );
+        plugin.manifest.limits.memory_limit_mb = Some(8);
+        let output = run_probe(&plugin, &temp_dir("oom"), "0.0.0", &ProbeConfig::default());
+        let error = probe_error(output);
+        assert_eq!(error.message, "Plugin exceeded memory limit (8 MB).");
+        assert_eq!(error.kind, ProbeErrorKind::PluginBug);
+    }
+
+    #[test]
+    fn run_probe_survives_stack_overflow() {
+        let mut plugin = test_plugin(
+            r#"
+            function recurse(n) { return recurse(n + 1) + 1; }
+            globalThis.__openusage_plugin = {
+                probe() {
+                    try {
+                        recurse(0);
+                    } catch (e) {
+                        throw "stack overflow caught";
+                    }
+                }
+            };
+            "#,
+        );
+        plugin.manifest.limits.stack_limit_kb = Some(256);
+        let output = run_probe(&plugin, &temp_dir("stack"), "0.0.0", &ProbeConfig::default());
+        assert_eq!(error_text(output), "stack overflow caught");
+    }
+
+    /// A plugin allowed to reach `http://127.0.0.1`; probe it with [`local_network`].
+    fn local_http_plugin(entry_script: &str) -> LoadedPlugin {
+        let mut plugin = test_plugin(entry_script);
+        plugin.manifest.permissions.http = serde_json::from_str(
+            r#"{ "hosts": ["127.0.0.1"], "schemes": ["http"] }"#,
+        )
+        .expect("http permission");
+        plugin
+    }
+
+    fn local_network() -> ProbeConfig {
+        ProbeConfig {
+            allow_private_network: true,
+            ..ProbeConfig::default()
+        }
+    }
+
+    /// Minimal HTTP server that answers each connection with `body` after `delay`.
+    fn spawn_http_server(connections: usize, delay: std::time::Duration, body: &str) -> String {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        let body = body.to_string();
+        std::thread::spawn(move || {
+            let mut workers = Vec::new();
+            for stream in listener.incoming().take(connections) {
+                let mut stream = stream.expect("accept");
+                let body = body.clone();
+                workers.push(std::thread::spawn(move || {
+                    let mut buf = [0u8; 4096];
+                    let _ = stream.read(&mut buf);
+                    std::thread::sleep(delay);
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                    let _ = stream.write_all(response.as_bytes());
+                }));
+            }
+            for worker in workers {
+                let _ = worker.join();
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    #[test]
+    fn run_probe_drives_concurrent_async_requests() {
+        let base = spawn_http_server(3, std::time::Duration::from_millis(300), "ok");
+        let plugin = local_http_plugin(&format!(
+            r#"
+            globalThis.__openusage_plugin = {{
+                probe: async function (ctx) {{
+                    var responses = await Promise.all([
+                        ctx.host.http.requestAsync({{ url: "{base}/a" }}),
+                        ctx.host.http.requestAsync({{ url: "{base}/b" }}),
+                        ctx.host.http.requestAsync({{ url: "{base}/c" }}),
+                    ]);
+                    return {{
+                        lines: responses.map(function (resp, i) {{
+                            return ctx.line.text({{ label: String(i), value: resp.status + ":" + resp.bodyText }});
+                        }})
+                    }};
+                }}
+            }};
+            "#
+        ));
+        let started = std::time::Instant::now();
+        let output = run_probe(&plugin, &temp_dir("async-http"), "0.0.0", &local_network());
+        assert!(
+            started.elapsed() < std::time::Duration::from_millis(800),
+            "requests should overlap, took {:?}",
+            started.elapsed()
+        );
+        assert_eq!(output.lines.len(), 3);
+        for line in output.lines {
+            match line {
+                MetricLine::Text { value, .. } => assert_eq!(value, "200:ok"),
+                other => panic!("expected text line, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn run_probe_traced_records_redacted_http_spans() {
+        let base = spawn_http_server(2, std::time::Duration::ZERO, "ok");
+        let plugin = local_http_plugin(&format!(
+            r#"
+            globalThis.__openusage_plugin = {{
+                probe: async function (ctx) {{
+                    ctx.host.http.request({{ url: "{base}/usage?api_key=sk-live-1234567890abcdef" }});
+                    var resp = await ctx.host.http.requestAsync({{ method: "POST", url: "{base}/b", bodyText: "{{}}" }});
+                    return {{ lines: [ctx.line.text({{ label: "status", value: String(resp.status) }})] }};
+                }}
+            }};
+            "#
+        ));
+        let spans = SpanRecorder::default();
+        let output = run_probe_traced(&plugin, &temp_dir("traced"), "0.0.0", &local_network(), &spans);
+        assert!(output.error.is_none(), "{:?}", output.error);
+
+        let spans = spans.take();
+        assert_eq!(spans.len(), 2);
+        let url = spans[0]
+            .attributes
+            .iter()
+            .find(|(key, _)| key == "url.full")
+            .map(|(_, value)| value.clone());
+        assert_eq!(
+            url,
+            Some(crate::plugin_engine::telemetry::AttributeValue::String(format!(
+                "{}/usage?api_key=sk-l...cdef",
+                base
+            )))
+        );
+        assert_eq!(spans[1].name, "POST");
+        assert!(spans[1]
+            .attributes
+            .contains(&("http.response.status_code".to_string(), 200i64.into())));
+    }
+
+    #[test]
+    fn run_probe_rejects_failed_async_request() {
+        let port = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+            listener.local_addr().expect("addr").port()
+        };
+        let plugin = local_http_plugin(&format!(
+            r#"
+            globalThis.__openusage_plugin = {{
+                probe: async function (ctx) {{
+                    try {{
+                        await ctx.host.http.requestAsync({{ url: "http://127.0.0.1:{port}/" }});
+                    }} catch (e) {{
+                        throw "request failed";
+                    }}
+                    throw "request unexpectedly succeeded";
+                }}
+            }};
+            "#
+        ));
+        let output = run_probe(&plugin, &temp_dir("async-fail"), "0.0.0", &local_network());
+        assert_eq!(error_text(output), "request failed");
+    }
+
+    #[test]
+    fn run_probe_classifies_uncaught_network_failure() {
+        let port = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+            listener.local_addr().expect("addr").port()
+        };
+        let plugin = local_http_plugin(&format!(
+            r#"
+            globalThis.__openusage_plugin = {{
+                probe: async function (ctx) {{
+                    await ctx.host.http.requestAsync({{ url: "http://127.0.0.1:{port}/" }});
+                }}
+            }};
+            "#
+        ));
+        let error = probe_error(run_probe(&plugin, &temp_dir("net"), "0.0.0", &local_network()));
+        assert_eq!(error.kind, ProbeErrorKind::Network);
+        assert!(error.retryable);
+    }
+
+    #[test]
+    fn run_probe_enforces_the_egress_policy() {
+        // Every request is refused before connecting, so nothing needs to listen.
+        let plugin = local_http_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe: function (ctx) {
+                    var outcome = function (url) {
+                        try {
+                            return String(ctx.host.http.request({ url: url }).status);
+                        } catch (e) {
+                            return e.kind + ": " + e.message;
+                        }
+                    };
+                    return {
+                        lines: [
+                            ctx.line.text({ label: "private", value: outcome("http://127.0.0.1:9/") }),
+                            ctx.line.text({ label: "host", value: outcome("http://example.com/") }),
+                            ctx.line.text({ label: "scheme", value: outcome("https://127.0.0.1/") }),
+                        ]
+                    };
+                }
+            };
+            "#,
+        );
+        let output = run_probe(&plugin, &temp_dir("egress"), "0.0.0", &ProbeConfig::default());
+        let values: Vec<String> = output
+            .lines
+            .into_iter()
+            .map(|line| match line {
+                MetricLine::Text { value, .. } => value,
+                other => panic!("expected text line, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                "permission: http host 127.0.0.1 resolves to non-public address 127.0.0.1".to_string(),
+                "permission: http host example.com is not declared in plugin.json".to_string(),
+                "permission: http scheme https is not declared in plugin.json".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn run_probe_reports_never_settling_promise() {
+        let plugin = test_plugin(
+            r#"
+            globalThis.__openusage_plugin = {
+                probe() {
+                    return new Promise(function () {});
+                }
+            };
+            "#,
+        );
+        let output = run_probe(&plugin, &temp_dir("pending"), "0.0.0", &ProbeConfig::default());
+        assert_eq!(error_text(output), "probe() returned unresolved promise");
+    }
+
     #[test]
     fn progress_resets_at_serializes_as_resets_at_camelcase() {
         let line = MetricLine::Progress {
@@ -523,6 +1278,7 @@ mod tests {
             resets_at: Some("2099-01-01T00:00:00.000Z".to_string()),
             period_duration_ms: None,
             color: None,
+            forecast: None,
         };
 
         let json: JsonValue = serde_json::to_value(&line).expect("serialize");
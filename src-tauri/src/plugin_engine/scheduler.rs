@@ -0,0 +1,629 @@
+use crate::plugin_engine::error::{ProbeError, ProbeErrorKind};
+use crate::plugin_engine::manifest::LoadedPlugin;
+use crate::plugin_engine::runtime::{error_output, PluginOutput};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub type ProbeFn = Arc<dyn Fn(&LoadedPlugin) -> PluginOutput + Send + Sync>;
+pub type EventFn = Arc<dyn Fn(ProbeEvent) + Send + Sync>;
+
+/// A probe's output as delivered to one batch; sent to the UI as `probe:result`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProbeResult {
+    pub batch_id: String,
+    pub output: PluginOutput,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProbeBatchStarted {
+    pub batch_id: String,
+    pub plugin_ids: Vec<String>,
+}
+
+/// The plugins a batch asked for, in the order asked, without duplicates; unknown ids
+/// are skipped. `None` means every plugin.
+pub fn select_plugins(plugins: Vec<LoadedPlugin>, plugin_ids: Option<Vec<String>>) -> Vec<LoadedPlugin> {
+    let Some(ids) = plugin_ids else {
+        return plugins;
+    };
+    let mut by_id: HashMap<String, LoadedPlugin> = plugins
+        .into_iter()
+        .map(|plugin| (plugin.manifest.id.clone(), plugin))
+        .collect();
+    let mut seen = HashSet::new();
+    ids.into_iter()
+        .filter_map(|id| {
+            if !seen.insert(id.clone()) {
+                return None;
+            }
+            by_id.remove(&id)
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub enum ProbeEvent {
+    /// A new probe was queued (not emitted when a batch joins an in-flight probe).
+    Queued { plugin_id: String },
+    /// A worker picked the probe up.
+    Started { plugin_id: String },
+    /// A queued probe was dropped because every batch waiting on it was cancelled.
+    Dropped { plugin_id: String },
+    /// A probe finished, panics included; emitted once per probe, before its results.
+    Finished {
+        plugin_id: String,
+        output: PluginOutput,
+        duration: Duration,
+        panicked: bool,
+    },
+    /// A probe finished; emitted once per batch waiting on it.
+    Result { batch_id: String, output: PluginOutput },
+    /// Every plugin in the batch has reported, or the batch was cancelled.
+    BatchComplete { batch_id: String, cancelled: bool },
+}
+
+/// Runs probes on a fixed pool of worker threads.
+///
+/// A plugin is probed at most once at a time: a batch that asks for a plugin which is
+/// already queued or running joins that probe and receives the same output, so
+/// overlapping batches (timer tick plus manual refresh) never race on token refreshes.
+pub struct ProbeScheduler {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    work_ready: Condvar,
+    /// Notified whenever batches complete.
+    batch_done: Condvar,
+    probe: ProbeFn,
+    on_event: EventFn,
+}
+
+#[derive(Default)]
+struct State {
+    queue: VecDeque<LoadedPlugin>,
+    /// Queued or running probes by plugin id, with the batches waiting on each.
+    flights: HashMap<String, Flight>,
+    /// Open batches and the plugin ids they still wait for.
+    batches: HashMap<String, HashSet<String>>,
+    shutdown: bool,
+}
+
+struct Flight {
+    batches: Vec<String>,
+    running: bool,
+}
+
+impl ProbeScheduler {
+    pub fn new(max_workers: usize, probe: ProbeFn, on_event: EventFn) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State::default()),
+            work_ready: Condvar::new(),
+            batch_done: Condvar::new(),
+            probe,
+            on_event,
+        });
+        // Host API async calls look for an ambient tokio runtime; share the caller's.
+        let runtime = tokio::runtime::Handle::try_current().ok();
+        for index in 0..max_workers.max(1) {
+            let shared = Arc::clone(&shared);
+            let runtime = runtime.clone();
+            let spawned = thread::Builder::new()
+                .name(format!("probe-worker-{}", index))
+                .spawn(move || {
+                    let _guard = runtime.as_ref().map(|handle| handle.enter());
+                    worker_loop(&shared);
+                });
+            if let Err(err) = spawned {
+                log::error!("failed to spawn probe worker {}: {}", index, err);
+            }
+        }
+        Self { shared }
+    }
+
+    /// Queue a batch. Plugins already queued or running are joined rather than probed
+    /// again. Fails if a batch with the same id is still open.
+    pub fn submit(&self, batch_id: &str, plugins: Vec<LoadedPlugin>) -> Result<(), String> {
+        let mut events = Vec::new();
+        {
+            let mut state = self.shared.lock();
+            if state.batches.contains_key(batch_id) {
+                return Err(format!("batch {} is already running", batch_id));
+            }
+            if !plugins.is_empty() {
+                let mut pending = HashSet::new();
+                for plugin in plugins {
+                    let plugin_id = plugin.manifest.id.clone();
+                    if !pending.insert(plugin_id.clone()) {
+                        continue;
+                    }
+                    match state.flights.get_mut(&plugin_id) {
+                        Some(flight) => {
+                            log::debug!("batch {} joins in-flight probe {}", batch_id, plugin_id);
+                            flight.batches.push(batch_id.to_string());
+                        }
+                        None => {
+                            events.push(ProbeEvent::Queued {
+                                plugin_id: plugin_id.clone(),
+                            });
+                            state.flights.insert(
+                                plugin_id,
+                                Flight {
+                                    batches: vec![batch_id.to_string()],
+                                    running: false,
+                                },
+                            );
+                            state.queue.push_back(plugin);
+                        }
+                    }
+                }
+                state.batches.insert(batch_id.to_string(), pending);
+            } else {
+                events.push(ProbeEvent::BatchComplete {
+                    batch_id: batch_id.to_string(),
+                    cancelled: false,
+                });
+            }
+            self.shared.emit(events);
+        }
+        self.shared.work_ready.notify_all();
+        Ok(())
+    }
+
+    /// Queue a batch of the plugins named in `plugin_ids` (all when `None`), as the
+    /// `start_probe_batch` command and the HTTP API do, and report what was queued.
+    pub fn start_batch(
+        &self,
+        batch_id: &str,
+        plugins: Vec<LoadedPlugin>,
+        plugin_ids: Option<Vec<String>>,
+    ) -> Result<ProbeBatchStarted, String> {
+        let selected = select_plugins(plugins, plugin_ids);
+        let plugin_ids: Vec<String> = selected.iter().map(|plugin| plugin.manifest.id.clone()).collect();
+        log::info!("probe batch {} starting: {:?}", batch_id, plugin_ids);
+        self.submit(batch_id, selected)?;
+        Ok(ProbeBatchStarted {
+            batch_id: batch_id.to_string(),
+            plugin_ids,
+        })
+    }
+
+    /// Cancel an open batch. Its queued probes are dropped unless another batch also
+    /// waits on them; probes already running finish, but their results are not
+    /// delivered to this batch. Returns false if the batch is unknown or already done.
+    pub fn cancel(&self, batch_id: &str) -> bool {
+        let mut events = Vec::new();
+        {
+            let mut state = self.shared.lock();
+            let Some(pending) = state.batches.remove(batch_id) else {
+                return false;
+            };
+            for plugin_id in pending {
+                let abandoned = match state.flights.get_mut(&plugin_id) {
+                    Some(flight) => {
+                        flight.batches.retain(|id| id != batch_id);
+                        flight.batches.is_empty() && !flight.running
+                    }
+                    None => false,
+                };
+                if abandoned {
+                    state.flights.remove(&plugin_id);
+                    state.queue.retain(|plugin| plugin.manifest.id != plugin_id);
+                    events.push(ProbeEvent::Dropped { plugin_id });
+                }
+            }
+            events.push(ProbeEvent::BatchComplete {
+                batch_id: batch_id.to_string(),
+                cancelled: true,
+            });
+            self.shared.emit(events);
+        }
+        self.shared.batch_done.notify_all();
+        log::info!("probe batch {} cancelled", batch_id);
+        true
+    }
+
+    /// Block until the batch has completed or been cancelled, up to `timeout`.
+    /// Returns false on timeout; unknown batches count as complete.
+    pub fn wait_batch(&self, batch_id: &str, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.shared.lock();
+        while state.batches.contains_key(batch_id) {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return false;
+            }
+            state = self
+                .shared
+                .batch_done
+                .wait_timeout(state, remaining)
+                .map(|(state, _)| state)
+                .unwrap_or_else(|poisoned| poisoned.into_inner().0);
+        }
+        true
+    }
+
+    /// Plugin ids currently queued or running.
+    pub fn in_flight(&self) -> Vec<String> {
+        self.shared.lock().flights.keys().cloned().collect()
+    }
+}
+
+impl Drop for ProbeScheduler {
+    fn drop(&mut self) {
+        self.shared.lock().shutdown = true;
+        self.shared.work_ready.notify_all();
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // Probes run outside the lock, so a poisoned mutex still holds consistent state.
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Events are emitted while the state lock is held so listeners see them in order
+    /// (a plugin's `Queued` before its `Started`, `Finished` before it is queued again).
+    /// Listeners therefore must not call back into the scheduler.
+    fn emit(&self, events: Vec<ProbeEvent>) {
+        for event in events {
+            (self.on_event)(event);
+        }
+    }
+}
+
+fn worker_loop(shared: &Shared) {
+    loop {
+        let plugin = {
+            let mut state = shared.lock();
+            loop {
+                if state.shutdown {
+                    return;
+                }
+                if let Some(plugin) = state.queue.pop_front() {
+                    if let Some(flight) = state.flights.get_mut(&plugin.manifest.id) {
+                        flight.running = true;
+                    }
+                    shared.emit(vec![ProbeEvent::Started {
+                        plugin_id: plugin.manifest.id.clone(),
+                    }]);
+                    break plugin;
+                }
+                state = shared
+                    .work_ready
+                    .wait(state)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+            }
+        };
+
+        let started = Instant::now();
+        let (output, panicked) = run_guarded(shared, &plugin);
+        let duration = started.elapsed();
+
+        let mut state = shared.lock();
+        let mut events = vec![ProbeEvent::Finished {
+            plugin_id: plugin.manifest.id.clone(),
+            output: output.clone(),
+            duration,
+            panicked,
+        }];
+        let waiting = state
+            .flights
+            .remove(&plugin.manifest.id)
+            .map(|flight| flight.batches)
+            .unwrap_or_default();
+        for batch_id in waiting {
+            let Some(pending) = state.batches.get_mut(&batch_id) else {
+                continue;
+            };
+            pending.remove(&plugin.manifest.id);
+            let done = pending.is_empty();
+            events.push(ProbeEvent::Result {
+                batch_id: batch_id.clone(),
+                output: output.clone(),
+            });
+            if done {
+                state.batches.remove(&batch_id);
+                events.push(ProbeEvent::BatchComplete {
+                    batch_id,
+                    cancelled: false,
+                });
+            }
+        }
+        shared.emit(events);
+        shared.batch_done.notify_all();
+    }
+}
+
+/// Run one probe, turning a panic into an internal error so the worker survives.
+/// The flag is true when the probe panicked.
+fn run_guarded(shared: &Shared, plugin: &LoadedPlugin) -> (PluginOutput, bool) {
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| (shared.probe)(plugin)));
+    match result {
+        Ok(output) => (output, false),
+        Err(_) => {
+            log::error!("probe {} panicked", plugin.manifest.id);
+            let output = error_output(
+                plugin,
+                ProbeError::new(
+                    ProbeErrorKind::Internal,
+                    "The probe crashed unexpectedly. Try again.",
+                ),
+            );
+            (output, true)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::manifest::{EntryType, ManifestLimits, ManifestPermissions, PluginManifest};
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    fn plugin(id: &str) -> LoadedPlugin {
+        LoadedPlugin {
+            manifest: PluginManifest {
+                schema_version: 1,
+                id: id.to_string(),
+                name: id.to_string(),
+                version: "0.0.0".to_string(),
+                entry: "plugin.js".to_string(),
+                entry_type: EntryType::Script,
+                icon: "icon.svg".to_string(),
+                brand_color: None,
+                lines: vec![],
+                limits: ManifestLimits::default(),
+                permissions: ManifestPermissions::default(),
+            },
+            plugin_dir: PathBuf::from("."),
+            entry_script: String::new(),
+            icon_data_url: String::new(),
+        }
+    }
+
+    fn ok_output(plugin: &LoadedPlugin) -> PluginOutput {
+        PluginOutput {
+            provider_id: plugin.manifest.id.clone(),
+            display_name: plugin.manifest.name.clone(),
+            plan: None,
+            lines: vec![],
+            icon_url: String::new(),
+            error: None,
+            fetched_at: None,
+            stale: false,
+        }
+    }
+
+    /// Probes block until released through the returned sender, one release per probe.
+    fn gated(
+        max_workers: usize,
+    ) -> (ProbeScheduler, mpsc::Sender<()>, mpsc::Receiver<ProbeEvent>, Arc<AtomicUsize>) {
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let release_rx = Arc::new(Mutex::new(release_rx));
+        let (event_tx, event_rx) = mpsc::channel();
+        let event_tx = Mutex::new(event_tx);
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let scheduler = ProbeScheduler::new(
+            max_workers,
+            Arc::new(move |plugin: &LoadedPlugin| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let _ = release_rx.lock().unwrap().recv_timeout(Duration::from_secs(5));
+                ok_output(plugin)
+            }),
+            Arc::new(move |event| {
+                let _ = event_tx.lock().unwrap().send(event);
+            }),
+        );
+        (scheduler, release_tx, event_rx, calls)
+    }
+
+    /// Next batch-level event, skipping lifecycle events.
+    fn next(events: &mpsc::Receiver<ProbeEvent>) -> ProbeEvent {
+        loop {
+            match events.recv_timeout(Duration::from_secs(5)).expect("event") {
+                event @ (ProbeEvent::Result { .. } | ProbeEvent::BatchComplete { .. }) => return event,
+                _ => continue,
+            }
+        }
+    }
+
+    fn wait_until(mut condition: impl FnMut() -> bool) {
+        for _ in 0..500 {
+            if condition() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(2));
+        }
+        panic!("condition not reached");
+    }
+
+    #[test]
+    fn select_plugins_keeps_requested_order_without_duplicates() {
+        let all = vec![plugin("claude"), plugin("codex"), plugin("cursor")];
+        let ids = |plugins: Vec<LoadedPlugin>| -> Vec<String> {
+            plugins.into_iter().map(|plugin| plugin.manifest.id).collect()
+        };
+        assert_eq!(ids(select_plugins(all.clone(), None)), vec!["claude", "codex", "cursor"]);
+        let requested = ["cursor", "nope", "claude", "cursor"].map(String::from).to_vec();
+        assert_eq!(ids(select_plugins(all, Some(requested))), vec!["cursor", "claude"]);
+    }
+
+    #[test]
+    fn overlapping_batches_share_one_probe() {
+        let (scheduler, release, events, calls) = gated(2);
+        scheduler.submit("timer", vec![plugin("codex")]).unwrap();
+        wait_until(|| calls.load(Ordering::SeqCst) == 1);
+        scheduler.submit("manual", vec![plugin("codex")]).unwrap();
+        release.send(()).unwrap();
+
+        let mut results = Vec::new();
+        let mut completed = Vec::new();
+        while completed.len() < 2 {
+            match next(&events) {
+                ProbeEvent::Result { batch_id, output } => results.push((batch_id, output.provider_id)),
+                ProbeEvent::BatchComplete { batch_id, .. } => completed.push(batch_id),
+                other => panic!("unexpected event {:?}", other),
+            }
+        }
+        results.sort();
+        assert_eq!(
+            results,
+            vec![
+                ("manual".to_string(), "codex".to_string()),
+                ("timer".to_string(), "codex".to_string())
+            ]
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn wait_batch_returns_once_the_batch_completes() {
+        let (scheduler, release, _events, calls) = gated(1);
+        let started = scheduler.start_batch("refresh", vec![plugin("codex")], None).unwrap();
+        assert_eq!(started.plugin_ids, vec!["codex".to_string()]);
+        wait_until(|| calls.load(Ordering::SeqCst) == 1);
+        assert!(!scheduler.wait_batch("refresh", Duration::from_millis(20)));
+        release.send(()).unwrap();
+        assert!(scheduler.wait_batch("refresh", Duration::from_secs(5)));
+        assert!(scheduler.wait_batch("unknown", Duration::ZERO));
+    }
+
+    #[test]
+    fn never_runs_more_than_max_workers() {
+        let (scheduler, release, events, calls) = gated(2);
+        scheduler
+            .submit("b", vec![plugin("a"), plugin("b"), plugin("c"), plugin("d")])
+            .unwrap();
+        wait_until(|| calls.load(Ordering::SeqCst) == 2);
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        for _ in 0..4 {
+            release.send(()).unwrap();
+        }
+        let mut results = 0;
+        loop {
+            match next(&events) {
+                ProbeEvent::Result { .. } => results += 1,
+                ProbeEvent::BatchComplete { cancelled, .. } => {
+                    assert!(!cancelled);
+                    break;
+                }
+                other => panic!("unexpected event {:?}", other),
+            }
+        }
+        assert_eq!(results, 4);
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn cancel_drops_queued_probes_and_completes_batch() {
+        let (scheduler, release, events, calls) = gated(1);
+        scheduler.submit("b", vec![plugin("a"), plugin("b")]).unwrap();
+        wait_until(|| calls.load(Ordering::SeqCst) == 1);
+
+        assert!(scheduler.cancel("b"));
+        assert!(matches!(
+            next(&events),
+            ProbeEvent::BatchComplete { cancelled: true, .. }
+        ));
+        assert!(!scheduler.cancel("b"));
+
+        release.send(()).unwrap();
+        wait_until(|| scheduler.in_flight().is_empty());
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(calls.load(Ordering::SeqCst), 1, "queued probe should be dropped");
+        let leaked = events
+            .try_iter()
+            .any(|event| matches!(event, ProbeEvent::Result { .. }));
+        assert!(!leaked, "cancelled batch got a result");
+    }
+
+    #[test]
+    fn cancel_keeps_probes_other_batches_wait_on() {
+        let (scheduler, release, events, calls) = gated(1);
+        scheduler.submit("first", vec![plugin("a"), plugin("b")]).unwrap();
+        wait_until(|| calls.load(Ordering::SeqCst) == 1);
+        scheduler.submit("second", vec![plugin("b")]).unwrap();
+        scheduler.cancel("first");
+        assert!(matches!(next(&events), ProbeEvent::BatchComplete { .. }));
+
+        release.send(()).unwrap();
+        release.send(()).unwrap();
+        match next(&events) {
+            ProbeEvent::Result { batch_id, output } => {
+                assert_eq!(batch_id, "second");
+                assert_eq!(output.provider_id, "b");
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+        assert!(matches!(
+            next(&events),
+            ProbeEvent::BatchComplete { cancelled: false, .. }
+        ));
+    }
+
+    #[test]
+    fn panicking_probe_reports_internal_error() {
+        let (event_tx, events) = mpsc::channel();
+        let event_tx = Mutex::new(event_tx);
+        let scheduler = ProbeScheduler::new(
+            1,
+            Arc::new(|_: &LoadedPlugin| panic!("boom")),
+            Arc::new(move |event| {
+                let _ = event_tx.lock().unwrap().send(event);
+            }),
+        );
+        scheduler.submit("b", vec![plugin("a")]).unwrap();
+        let events: Vec<ProbeEvent> = (0..5).map(|_| events.recv_timeout(Duration::from_secs(5)).unwrap()).collect();
+        assert!(matches!(&events[0], ProbeEvent::Queued { plugin_id } if plugin_id == "a"));
+        assert!(matches!(&events[1], ProbeEvent::Started { plugin_id } if plugin_id == "a"));
+        assert!(matches!(&events[2], ProbeEvent::Finished { panicked: true, .. }));
+        match &events[3] {
+            ProbeEvent::Result { output, .. } => {
+                assert_eq!(output.error.as_ref().unwrap().kind, ProbeErrorKind::Internal);
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+        assert!(matches!(&events[4], ProbeEvent::BatchComplete { .. }));
+    }
+
+    #[test]
+    fn cancelled_queued_probe_is_reported_dropped() {
+        let (scheduler, release, events, calls) = gated(1);
+        scheduler.submit("b", vec![plugin("a"), plugin("b")]).unwrap();
+        wait_until(|| calls.load(Ordering::SeqCst) == 1);
+        scheduler.cancel("b");
+        release.send(()).unwrap();
+        wait_until(|| scheduler.in_flight().is_empty());
+
+        let events: Vec<ProbeEvent> = events.try_iter().collect();
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, ProbeEvent::Dropped { plugin_id } if plugin_id == "b")));
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, ProbeEvent::Finished { plugin_id, panicked: false, .. } if plugin_id == "a")));
+    }
+
+    #[test]
+    fn empty_batch_completes_immediately() {
+        let (scheduler, _release, events, _calls) = gated(1);
+        scheduler.submit("empty", vec![]).unwrap();
+        assert!(matches!(
+            next(&events),
+            ProbeEvent::BatchComplete { cancelled: false, .. }
+        ));
+    }
+}
@@ -0,0 +1,703 @@
+//! Secret storage behind `host.keychain`. Entries are a service name plus an optional
+//! account, kept in the macOS Keychain, a Secret Service provider on Linux (GNOME
+//! Keyring, KWallet) or, where neither is available, an encrypted file in the app data
+//! dir. Secrets never go through a command line.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const STORE_FILE: &str = "secrets.enc";
+const KEY_FILE: &str = "secrets.key";
+const STORE_VERSION: u32 = 1;
+/// Binds the ciphertext to this file format.
+const STORE_AAD: &[u8] = b"openusage-secrets-v1";
+
+/// Which backend holds plugin secrets (`secretStore` in the settings).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SecretStoreKind {
+    /// The Keychain on macOS, Secret Service on Linux when a provider is running, and
+    /// the encrypted file otherwise.
+    #[default]
+    Auto,
+    Keychain,
+    SecretService,
+    File,
+}
+
+impl std::str::FromStr for SecretStoreKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        serde_json::from_value(serde_json::Value::String(value.to_string())).map_err(|_| {
+            format!(
+                "unknown secret store: {} (expected auto, keychain, secret-service or file)",
+                value
+            )
+        })
+    }
+}
+
+/// A stored secret: a service name and, optionally, an account within it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SecretEntry<'a> {
+    pub service: &'a str,
+    /// `None` matches any account of the service.
+    pub account: Option<&'a str>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecretError {
+    NotFound,
+    /// The backend doesn't exist on this platform or isn't running.
+    Unavailable(String),
+    Failed(String),
+}
+
+impl std::fmt::Display for SecretError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound => f.write_str("secret not found"),
+            Self::Unavailable(message) | Self::Failed(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for SecretError {}
+
+pub trait SecretStore: Send + Sync {
+    /// Backend name for logs.
+    fn name(&self) -> &'static str;
+
+    /// The secret of `entry`; without an account, of the first entry of the service.
+    fn read(&self, entry: SecretEntry<'_>) -> Result<String, SecretError>;
+
+    /// Create or replace `entry`. Without an account, the service's existing entry is
+    /// updated, keeping its account, or a new one without an account is created.
+    fn write(&self, entry: SecretEntry<'_>, secret: &str) -> Result<(), SecretError>;
+
+    /// Remove `entry`, or every entry of the service without an account. Deleting an
+    /// entry that doesn't exist is not an error.
+    fn delete(&self, entry: SecretEntry<'_>) -> Result<(), SecretError>;
+}
+
+/// The store for `kind`, keeping file-backed secrets in `app_data_dir`.
+pub fn open(kind: SecretStoreKind, app_data_dir: &Path) -> Arc<dyn SecretStore> {
+    match kind {
+        SecretStoreKind::Auto if cfg!(target_os = "macos") => {
+            open(SecretStoreKind::Keychain, app_data_dir)
+        }
+        SecretStoreKind::Auto if secret_service_available() => {
+            open(SecretStoreKind::SecretService, app_data_dir)
+        }
+        SecretStoreKind::Auto | SecretStoreKind::File => Arc::new(FileStore::new(app_data_dir)),
+        #[cfg(target_os = "macos")]
+        SecretStoreKind::Keychain => Arc::new(keychain_store::KeychainStore),
+        #[cfg(not(target_os = "macos"))]
+        SecretStoreKind::Keychain => {
+            Arc::new(Unavailable("the Keychain is only available on macOS"))
+        }
+        #[cfg(target_os = "linux")]
+        SecretStoreKind::SecretService => Arc::new(secret_service_store::SecretServiceStore),
+        #[cfg(not(target_os = "linux"))]
+        SecretStoreKind::SecretService => {
+            Arc::new(Unavailable("Secret Service is only available on Linux"))
+        }
+    }
+}
+
+/// Whether a Secret Service provider answers on the session bus; checked once per run.
+#[cfg(target_os = "linux")]
+fn secret_service_available() -> bool {
+    static AVAILABLE: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+    *AVAILABLE.get_or_init(|| match secret_service_store::connect() {
+        Ok(_) => true,
+        Err(err) => {
+            log::info!(
+                "Secret Service unavailable ({}), keeping secrets in {}",
+                err,
+                STORE_FILE
+            );
+            false
+        }
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn secret_service_available() -> bool {
+    false
+}
+
+/// A backend that can't work here, e.g. the Keychain on Linux.
+struct Unavailable(&'static str);
+
+impl SecretStore for Unavailable {
+    fn name(&self) -> &'static str {
+        "unavailable"
+    }
+
+    fn read(&self, _entry: SecretEntry<'_>) -> Result<String, SecretError> {
+        Err(SecretError::Unavailable(self.0.to_string()))
+    }
+
+    fn write(&self, _entry: SecretEntry<'_>, _secret: &str) -> Result<(), SecretError> {
+        Err(SecretError::Unavailable(self.0.to_string()))
+    }
+
+    fn delete(&self, _entry: SecretEntry<'_>) -> Result<(), SecretError> {
+        Err(SecretError::Unavailable(self.0.to_string()))
+    }
+}
+
+/// Secrets in `secrets.enc`, sealed with XChaCha20-Poly1305 under a random key kept in
+/// `secrets.key` (mode 0600) next to it. Anything that can read the data dir, including
+/// its backups, can decrypt them; this only keeps them out of plain text. Prefer the
+/// Keychain or Secret Service.
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+/// Serializes read-modify-write cycles of probes running in parallel.
+static FILE_STORE_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct FileEntries {
+    entries: Vec<FileEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct FileEntry {
+    service: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    account: Option<String>,
+    secret: String,
+}
+
+impl FileEntry {
+    fn matches(&self, entry: SecretEntry<'_>) -> bool {
+        self.service == entry.service
+            && entry
+                .account
+                .is_none_or(|account| self.account.as_deref() == Some(account))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SealedStore {
+    version: u32,
+    nonce: String,
+    ciphertext: String,
+}
+
+impl FileStore {
+    pub fn new(app_data_dir: &Path) -> Self {
+        Self {
+            dir: app_data_dir.to_path_buf(),
+        }
+    }
+
+    fn load(&self) -> Result<FileEntries, SecretError> {
+        let sealed = match std::fs::read_to_string(self.dir.join(STORE_FILE)) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(FileEntries::default())
+            }
+            Err(err) => return Err(failed("read", err)),
+        };
+        let sealed: SealedStore =
+            serde_json::from_str(&sealed).map_err(|err| failed("parse", err))?;
+        if sealed.version != STORE_VERSION {
+            return Err(SecretError::Failed(format!(
+                "unsupported {} version {}",
+                STORE_FILE, sealed.version
+            )));
+        }
+        let key = self
+            .key(false)?
+            .ok_or_else(|| SecretError::Failed(format!("{} is missing", KEY_FILE)))?;
+        let nonce = STANDARD
+            .decode(&sealed.nonce)
+            .map_err(|err| failed("decode", err))?;
+        let ciphertext = STANDARD
+            .decode(&sealed.ciphertext)
+            .map_err(|err| failed("decode", err))?;
+        if nonce.len() != 24 {
+            return Err(SecretError::Failed(format!(
+                "{} has an invalid nonce",
+                STORE_FILE
+            )));
+        }
+        let plaintext = XChaCha20Poly1305::new(&key)
+            .decrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: &ciphertext,
+                    aad: STORE_AAD,
+                },
+            )
+            .map_err(|_| SecretError::Failed(format!("{} could not be decrypted", STORE_FILE)))?;
+        serde_json::from_slice(&plaintext).map_err(|err| failed("parse", err))
+    }
+
+    fn save(&self, entries: &FileEntries) -> Result<(), SecretError> {
+        let key = self
+            .key(true)?
+            .ok_or_else(|| SecretError::Failed(format!("{} could not be created", KEY_FILE)))?;
+        let plaintext = serde_json::to_vec(entries).map_err(|err| failed("encode", err))?;
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = XChaCha20Poly1305::new(&key)
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &plaintext,
+                    aad: STORE_AAD,
+                },
+            )
+            .map_err(|_| SecretError::Failed(format!("{} could not be encrypted", STORE_FILE)))?;
+        let sealed = SealedStore {
+            version: STORE_VERSION,
+            nonce: STANDARD.encode(nonce),
+            ciphertext: STANDARD.encode(ciphertext),
+        };
+        let text = serde_json::to_string(&sealed).map_err(|err| failed("encode", err))?;
+        // Write a sibling file and rename it over, so a crash never leaves half a store.
+        let temp = self.dir.join(format!("{}.tmp", STORE_FILE));
+        write_private(&temp, text.as_bytes()).map_err(|err| failed("write", err))?;
+        std::fs::rename(&temp, self.dir.join(STORE_FILE)).map_err(|err| failed("write", err))
+    }
+
+    /// The store key, generated on first use when `create` is set.
+    fn key(&self, create: bool) -> Result<Option<Key>, SecretError> {
+        let path = self.dir.join(KEY_FILE);
+        let existing = read_key(&path)?;
+        if existing.is_some() || !create {
+            return Ok(existing);
+        }
+        let key = XChaCha20Poly1305::generate_key(&mut OsRng);
+        std::fs::create_dir_all(&self.dir).map_err(|err| failed("write", err))?;
+        // Another process on this data dir may have created the key since the read;
+        // replacing it would leave the secrets sealed with it unreadable.
+        match create_private(&path, STANDARD.encode(key).as_bytes()) {
+            Ok(()) => Ok(Some(key)),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => read_key(&path),
+            Err(err) => Err(failed("write", err)),
+        }
+    }
+}
+
+fn read_key(path: &Path) -> Result<Option<Key>, SecretError> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(failed("read", err)),
+    };
+    let bytes = STANDARD
+        .decode(text.trim())
+        .map_err(|err| failed("decode", err))?;
+    if bytes.len() != 32 {
+        return Err(SecretError::Failed(format!(
+            "{} is not a valid key",
+            KEY_FILE
+        )));
+    }
+    Ok(Some(*Key::from_slice(&bytes)))
+}
+
+impl SecretStore for FileStore {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    fn read(&self, entry: SecretEntry<'_>) -> Result<String, SecretError> {
+        let _guard = FILE_STORE_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        self.load()?
+            .entries
+            .into_iter()
+            .find(|stored| stored.matches(entry))
+            .map(|stored| stored.secret)
+            .ok_or(SecretError::NotFound)
+    }
+
+    fn write(&self, entry: SecretEntry<'_>, secret: &str) -> Result<(), SecretError> {
+        let _guard = FILE_STORE_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut entries = self.load()?;
+        match entries
+            .entries
+            .iter_mut()
+            .find(|stored| stored.matches(entry))
+        {
+            Some(stored) => stored.secret = secret.to_string(),
+            None => entries.entries.push(FileEntry {
+                service: entry.service.to_string(),
+                account: entry.account.map(str::to_string),
+                secret: secret.to_string(),
+            }),
+        }
+        self.save(&entries)
+    }
+
+    fn delete(&self, entry: SecretEntry<'_>) -> Result<(), SecretError> {
+        let _guard = FILE_STORE_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut entries = self.load()?;
+        let before = entries.entries.len();
+        entries.entries.retain(|stored| !stored.matches(entry));
+        if entries.entries.len() == before {
+            return Ok(());
+        }
+        self.save(&entries)
+    }
+}
+
+fn failed(action: &str, err: impl std::fmt::Display) -> SecretError {
+    SecretError::Failed(format!("failed to {} secret store: {}", action, err))
+}
+
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut options = private_options();
+    options.create(true).truncate(true);
+    options.open(path)?.write_all(contents)
+}
+
+/// Like [`write_private`], but fails with `AlreadyExists` instead of replacing a file.
+fn create_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut options = private_options();
+    options.create_new(true);
+    options.open(path)?.write_all(contents)
+}
+
+fn private_options() -> std::fs::OpenOptions {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+}
+
+#[cfg(target_os = "macos")]
+mod keychain_store {
+    use super::{SecretEntry, SecretError, SecretStore};
+    use security_framework::item::{ItemClass, ItemSearchOptions, Limit};
+    use security_framework::passwords;
+
+    /// `errSecItemNotFound`.
+    const ITEM_NOT_FOUND: i32 = -25300;
+
+    /// Generic passwords in the login keychain.
+    pub struct KeychainStore;
+
+    /// Accounts of the generic passwords stored under `service`.
+    fn accounts(service: &str) -> Result<Vec<String>, SecretError> {
+        let results = ItemSearchOptions::new()
+            .class(ItemClass::generic_password())
+            .service(service)
+            .load_attributes(true)
+            .limit(Limit::All)
+            .search();
+        match results {
+            Ok(results) => Ok(results
+                .iter()
+                .filter_map(|result| result.simplify_dict())
+                .filter_map(|attributes| attributes.get("acct").cloned())
+                .collect()),
+            Err(err) if err.code() == ITEM_NOT_FOUND => Ok(Vec::new()),
+            Err(err) => Err(SecretError::Failed(format!(
+                "keychain search failed: {}",
+                err
+            ))),
+        }
+    }
+
+    impl SecretStore for KeychainStore {
+        fn name(&self) -> &'static str {
+            "keychain"
+        }
+
+        fn read(&self, entry: SecretEntry<'_>) -> Result<String, SecretError> {
+            let account = match entry.account {
+                Some(account) => account.to_string(),
+                None => accounts(entry.service)?
+                    .into_iter()
+                    .next()
+                    .ok_or(SecretError::NotFound)?,
+            };
+            match passwords::get_generic_password(entry.service, &account) {
+                Ok(secret) => String::from_utf8(secret).map_err(|_| {
+                    SecretError::Failed("keychain item is not valid UTF-8".to_string())
+                }),
+                Err(err) if err.code() == ITEM_NOT_FOUND => Err(SecretError::NotFound),
+                Err(err) => Err(SecretError::Failed(format!(
+                    "keychain read failed: {}",
+                    err
+                ))),
+            }
+        }
+
+        fn write(&self, entry: SecretEntry<'_>, secret: &str) -> Result<(), SecretError> {
+            let account = match entry.account {
+                Some(account) => account.to_string(),
+                None => accounts(entry.service)?
+                    .into_iter()
+                    .next()
+                    .unwrap_or_default(),
+            };
+            passwords::set_generic_password(entry.service, &account, secret.as_bytes())
+                .map_err(|err| SecretError::Failed(format!("keychain write failed: {}", err)))
+        }
+
+        fn delete(&self, entry: SecretEntry<'_>) -> Result<(), SecretError> {
+            let accounts = match entry.account {
+                Some(account) => vec![account.to_string()],
+                None => accounts(entry.service)?,
+            };
+            for account in accounts {
+                match passwords::delete_generic_password(entry.service, &account) {
+                    Ok(()) => {}
+                    Err(err) if err.code() == ITEM_NOT_FOUND => {}
+                    Err(err) => {
+                        return Err(SecretError::Failed(format!(
+                            "keychain delete failed: {}",
+                            err
+                        )))
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod secret_service_store {
+    use super::{SecretEntry, SecretError, SecretStore};
+    use secret_service::blocking::{Item, SecretService};
+    use secret_service::{EncryptionType, Error};
+    use std::collections::HashMap;
+
+    /// Items in the default Secret Service collection, with the `service` and `username`
+    /// attributes libsecret tools, go-keyring (the GitHub CLI) and keyring-rs use.
+    pub struct SecretServiceStore;
+
+    pub fn connect() -> Result<SecretService<'static>, SecretError> {
+        SecretService::connect(EncryptionType::Dh).map_err(map_error)
+    }
+
+    fn map_error(err: Error) -> SecretError {
+        match err {
+            Error::NoResult => SecretError::NotFound,
+            Error::Unavailable => {
+                SecretError::Unavailable("no Secret Service provider is running".to_string())
+            }
+            err => SecretError::Failed(format!("secret service: {}", err)),
+        }
+    }
+
+    fn attributes<'a>(entry: SecretEntry<'a>) -> HashMap<&'a str, &'a str> {
+        let mut attributes = HashMap::from([("service", entry.service)]);
+        if let Some(account) = entry.account {
+            attributes.insert("username", account);
+        }
+        attributes
+    }
+
+    /// Matching items, unlocked ones first.
+    fn find<'a>(
+        service: &'a SecretService<'a>,
+        entry: SecretEntry<'_>,
+    ) -> Result<Vec<Item<'a>>, SecretError> {
+        let found = service.search_items(attributes(entry)).map_err(map_error)?;
+        Ok(found.unlocked.into_iter().chain(found.locked).collect())
+    }
+
+    impl SecretStore for SecretServiceStore {
+        fn name(&self) -> &'static str {
+            "secret-service"
+        }
+
+        fn read(&self, entry: SecretEntry<'_>) -> Result<String, SecretError> {
+            let service = connect()?;
+            let item = find(&service, entry)?
+                .into_iter()
+                .next()
+                .ok_or(SecretError::NotFound)?;
+            item.ensure_unlocked().map_err(map_error)?;
+            let secret = item.get_secret().map_err(map_error)?;
+            String::from_utf8(secret)
+                .map_err(|_| SecretError::Failed("secret is not valid UTF-8".to_string()))
+        }
+
+        fn write(&self, entry: SecretEntry<'_>, secret: &str) -> Result<(), SecretError> {
+            let service = connect()?;
+            if let Some(item) = find(&service, entry)?.into_iter().next() {
+                item.ensure_unlocked().map_err(map_error)?;
+                return item
+                    .set_secret(secret.as_bytes(), "text/plain")
+                    .map_err(map_error);
+            }
+            let collection = service
+                .get_default_collection()
+                .or_else(|_| service.get_any_collection())
+                .map_err(map_error)?;
+            collection.ensure_unlocked().map_err(map_error)?;
+            let label = match entry.account {
+                Some(account) => format!("{} ({})", entry.service, account),
+                None => entry.service.to_string(),
+            };
+            collection
+                .create_item(
+                    &label,
+                    attributes(entry),
+                    secret.as_bytes(),
+                    true,
+                    "text/plain",
+                )
+                .map(|_| ())
+                .map_err(map_error)
+        }
+
+        fn delete(&self, entry: SecretEntry<'_>) -> Result<(), SecretError> {
+            let service = connect()?;
+            for item in find(&service, entry)? {
+                item.delete().map_err(map_error)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::temp_dir;
+
+    fn entry<'a>(service: &'a str, account: Option<&'a str>) -> SecretEntry<'a> {
+        SecretEntry { service, account }
+    }
+
+    #[test]
+    fn file_store_reads_writes_and_deletes_entries() {
+        let dir = temp_dir("crud");
+        let store = FileStore::new(&dir);
+        assert_eq!(store.read(entry("svc", None)), Err(SecretError::NotFound));
+        assert_eq!(store.delete(entry("svc", None)), Ok(()));
+
+        store.write(entry("svc", Some("alice")), "a1").unwrap();
+        store.write(entry("svc", Some("bob")), "b1").unwrap();
+        store.write(entry("other", None), "o1").unwrap();
+        assert_eq!(store.read(entry("svc", Some("bob"))).unwrap(), "b1");
+        assert_eq!(store.read(entry("svc", None)).unwrap(), "a1");
+        assert_eq!(
+            store.read(entry("svc", Some("carol"))),
+            Err(SecretError::NotFound)
+        );
+
+        // Without an account, a write updates the service's existing entry.
+        store.write(entry("svc", None), "a2").unwrap();
+        assert_eq!(store.read(entry("svc", Some("alice"))).unwrap(), "a2");
+        assert_eq!(store.read(entry("svc", Some("bob"))).unwrap(), "b1");
+
+        // A new store over the same dir sees the same entries.
+        let reopened = open(SecretStoreKind::File, &dir);
+        assert_eq!(reopened.name(), "file");
+        assert_eq!(reopened.read(entry("other", None)).unwrap(), "o1");
+
+        store.delete(entry("svc", Some("alice"))).unwrap();
+        assert_eq!(store.read(entry("svc", None)).unwrap(), "b1");
+        store.delete(entry("svc", None)).unwrap();
+        assert_eq!(store.read(entry("svc", None)), Err(SecretError::NotFound));
+        assert_eq!(store.read(entry("other", None)).unwrap(), "o1");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn file_store_never_replaces_an_existing_key() {
+        let dir = temp_dir("key");
+        let first = FileStore::new(&dir);
+        first.write(entry("svc", None), "s1").unwrap();
+        let key = std::fs::read(dir.join(KEY_FILE)).unwrap();
+
+        assert_eq!(
+            create_private(&dir.join(KEY_FILE), b"other").map_err(|err| err.kind()),
+            Err(std::io::ErrorKind::AlreadyExists)
+        );
+        assert_eq!(std::fs::read(dir.join(KEY_FILE)).unwrap(), key);
+        let second = FileStore::new(&dir);
+        second.write(entry("other", None), "o1").unwrap();
+        assert_eq!(std::fs::read(dir.join(KEY_FILE)).unwrap(), key);
+        assert_eq!(first.read(entry("svc", None)).unwrap(), "s1");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn file_store_is_encrypted_and_private() {
+        let dir = temp_dir("sealed");
+        let store = FileStore::new(&dir);
+        store
+            .write(entry("svc", Some("alice")), "hunter2-secret")
+            .unwrap();
+
+        let raw = std::fs::read_to_string(dir.join(STORE_FILE)).unwrap();
+        assert!(!raw.contains("hunter2-secret"));
+        assert!(!raw.contains("alice"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            for file in [STORE_FILE, KEY_FILE] {
+                let mode = std::fs::metadata(dir.join(file))
+                    .unwrap()
+                    .permissions()
+                    .mode();
+                assert_eq!(mode & 0o777, 0o600, "{} should be private", file);
+            }
+        }
+
+        // Tampering with the ciphertext is detected.
+        let mut sealed: SealedStore = serde_json::from_str(&raw).unwrap();
+        let mut ciphertext = STANDARD.decode(&sealed.ciphertext).unwrap();
+        ciphertext[0] ^= 1;
+        sealed.ciphertext = STANDARD.encode(ciphertext);
+        std::fs::write(
+            dir.join(STORE_FILE),
+            serde_json::to_string(&sealed).unwrap(),
+        )
+        .unwrap();
+        assert!(matches!(
+            store.read(entry("svc", None)),
+            Err(SecretError::Failed(_))
+        ));
+
+        // So is a store whose key is gone.
+        std::fs::write(dir.join(STORE_FILE), raw).unwrap();
+        std::fs::remove_file(dir.join(KEY_FILE)).unwrap();
+        assert!(matches!(
+            store.read(entry("svc", None)),
+            Err(SecretError::Failed(_))
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn parses_store_kinds() {
+        assert_eq!(
+            "secret-service".parse::<SecretStoreKind>(),
+            Ok(SecretStoreKind::SecretService)
+        );
+        assert_eq!("file".parse::<SecretStoreKind>(), Ok(SecretStoreKind::File));
+        assert!("vault".parse::<SecretStoreKind>().is_err());
+        let kind: SecretStoreKind = serde_json::from_str("\"keychain\"").unwrap();
+        assert_eq!(kind, SecretStoreKind::Keychain);
+    }
+}
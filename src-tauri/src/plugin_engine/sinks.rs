@@ -0,0 +1,529 @@
+//! Output sinks: destinations that receive every probe result, such as webhooks for a
+//! team chat bot. Each sink runs on its own thread behind a bounded queue, so a slow or
+//! unreachable sink never holds up probes or other sinks.
+
+use crate::plugin_engine::alerts::DEFAULT_HYSTERESIS;
+use crate::plugin_engine::runtime::{MetricLine, PluginOutput};
+use crate::plugin_engine::scheduler::ProbeResult;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashSet;
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Results waiting for one sink; more are dropped rather than piling up.
+const QUEUE_SIZE: usize = 64;
+pub const SIGNATURE_HEADER: &str = "X-OpenUsage-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-OpenUsage-Timestamp";
+
+/// Receives probe results on its own thread; may block, e.g. on network I/O.
+pub trait ResultSink: Send + Sync {
+    /// Short name for logs.
+    fn name(&self) -> String;
+    fn send(&self, result: &ProbeResult);
+}
+
+/// Sinks configured in settings (`sinks`) or the daemon config.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SinksConfig {
+    pub webhooks: Vec<WebhookConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Signs each body with HMAC-SHA256; see [`signature`].
+    pub secret: Option<String>,
+    /// Only send results of these plugins; empty sends all.
+    pub providers: Vec<String>,
+    /// Only send a result when one of its progress lines rises to this percentage.
+    pub min_percent: Option<f64>,
+    /// Points a line must fall below `min_percent` before it can send again.
+    pub hysteresis: f64,
+    /// Attempts after the first one for network errors, 429 and 5xx.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for every further one.
+    pub backoff_ms: u64,
+    pub timeout_ms: u64,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            secret: None,
+            providers: Vec::new(),
+            min_percent: None,
+            hysteresis: DEFAULT_HYSTERESIS,
+            max_retries: 3,
+            backoff_ms: 1_000,
+            timeout_ms: 10_000,
+        }
+    }
+}
+
+impl WebhookConfig {
+    /// Whether results of `provider_id` pass the provider filter.
+    pub fn matches_provider(&self, provider_id: &str) -> bool {
+        self.providers.is_empty() || self.providers.iter().any(|id| id == provider_id)
+    }
+}
+
+/// Tracks which progress lines are at or above a webhook's `min_percent`, so a line
+/// only sends when it crosses upwards rather than on every probe while it stays high.
+#[derive(Debug, Default)]
+struct ThresholdEdges {
+    /// (plugin id, label) of lines above the threshold that haven't re-armed yet.
+    above: HashSet<(String, String)>,
+}
+
+impl ThresholdEdges {
+    /// Update the line states from `output` and return whether any line rose to
+    /// `min_percent`. Lines re-arm once they drop `hysteresis` points below it.
+    fn rising(&mut self, output: &PluginOutput, min_percent: f64, hysteresis: f64) -> bool {
+        let mut rose = false;
+        for line in &output.lines {
+            let MetricLine::Progress {
+                label, used, limit, ..
+            } = line
+            else {
+                continue;
+            };
+            if !used.is_finite() || !limit.is_finite() || *limit <= 0.0 {
+                continue;
+            }
+            let percent = used / limit * 100.0;
+            let key = (output.provider_id.clone(), label.clone());
+            if percent >= min_percent {
+                rose |= self.above.insert(key);
+            } else if percent < min_percent - hysteresis.max(0.0) {
+                self.above.remove(&key);
+            }
+        }
+        rose
+    }
+}
+
+/// Fans probe results out to sink threads without blocking the caller.
+#[derive(Clone, Default)]
+pub struct SinkDispatcher {
+    queues: Vec<(String, SyncSender<ProbeResult>)>,
+}
+
+impl SinkDispatcher {
+    /// Start a thread for each sink.
+    pub fn new(sinks: Vec<Arc<dyn ResultSink>>) -> Self {
+        let mut queues = Vec::new();
+        for sink in sinks {
+            let name = sink.name();
+            let (sender, receiver) = mpsc::sync_channel(QUEUE_SIZE);
+            let spawned = thread::Builder::new()
+                .name("result-sink".to_string())
+                .spawn(move || run_sink(sink.as_ref(), receiver));
+            match spawned {
+                Ok(_) => queues.push((name, sender)),
+                Err(err) => log::error!("failed to start sink {}: {}", name, err),
+            }
+        }
+        Self { queues }
+    }
+
+    /// Sinks from the config; webhooks without an http(s) URL are skipped.
+    pub fn from_config(config: &SinksConfig) -> Self {
+        let sinks: Vec<Arc<dyn ResultSink>> = config
+            .webhooks
+            .iter()
+            .filter(|webhook| {
+                let valid =
+                    webhook.url.starts_with("http://") || webhook.url.starts_with("https://");
+                if !valid {
+                    log::warn!("ignoring webhook with invalid url: {:?}", webhook.url);
+                }
+                valid
+            })
+            .map(|webhook| Arc::new(WebhookSink::new(webhook.clone())) as Arc<dyn ResultSink>)
+            .collect();
+        Self::new(sinks)
+    }
+
+    pub fn dispatch(&self, result: &ProbeResult) {
+        for (name, queue) in &self.queues {
+            match queue.try_send(result.clone()) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => {
+                    log::warn!("sink {} is behind, dropping result", name)
+                }
+                Err(TrySendError::Disconnected(_)) => log::warn!("sink {} has stopped", name),
+            }
+        }
+    }
+}
+
+fn run_sink(sink: &dyn ResultSink, receiver: Receiver<ProbeResult>) {
+    for result in receiver {
+        // A panicking sink only loses this result.
+        let sent = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| sink.send(&result)));
+        if sent.is_err() {
+            log::error!("sink {} panicked", sink.name());
+        }
+    }
+}
+
+/// Body posted by [`WebhookSink`].
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookPayload<'a> {
+    pub event: &'static str,
+    pub batch_id: &'a str,
+    pub output: &'a PluginOutput,
+}
+
+/// POSTs each matching result as JSON, signed when a secret is configured.
+pub struct WebhookSink {
+    config: WebhookConfig,
+    /// Only consulted with `min_percent`; kept in memory, so a line that is still high
+    /// after a restart sends once more.
+    edges: Mutex<ThresholdEdges>,
+    runtime: Option<tokio::runtime::Runtime>,
+    client: Option<reqwest::Client>,
+}
+
+impl WebhookSink {
+    pub fn new(config: WebhookConfig) -> Self {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|err| log::error!("webhook runtime failed to start: {}", err))
+            .ok();
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms.max(1)))
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .map_err(|err| log::error!("webhook client failed to start: {}", err))
+            .ok();
+        Self {
+            config,
+            edges: Mutex::new(ThresholdEdges::default()),
+            runtime,
+            client,
+        }
+    }
+
+    /// Whether `output` should be posted. Stale results only repeat data that was
+    /// already sent, so they never are.
+    fn wants(&self, output: &PluginOutput) -> bool {
+        if output.stale || !self.config.matches_provider(&output.provider_id) {
+            return false;
+        }
+        let Some(min_percent) = self.config.min_percent else {
+            return true;
+        };
+        // Failed probes carry no lines; keep the line states until fresh data arrives.
+        if output.error.is_some() {
+            return false;
+        }
+        match self.edges.lock() {
+            Ok(mut edges) => edges.rising(output, min_percent, self.config.hysteresis),
+            Err(_) => false,
+        }
+    }
+
+    /// POST once; `Err(true)` means the failure is worth retrying.
+    fn post(&self, body: &[u8]) -> Result<(), (bool, String)> {
+        let (Some(runtime), Some(client)) = (&self.runtime, &self.client) else {
+            return Err((false, "webhook client unavailable".to_string()));
+        };
+        let mut request = client
+            .post(&self.config.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.to_vec());
+        if let Some(secret) = &self.config.secret {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            request = request
+                .header(TIMESTAMP_HEADER, timestamp.to_string())
+                .header(SIGNATURE_HEADER, signature(secret, timestamp, body));
+        }
+        let status = runtime
+            .block_on(async { request.send().await })
+            .map_err(|err| (true, err.without_url().to_string()))?
+            .status();
+        if status.is_success() {
+            Ok(())
+        } else {
+            let retry =
+                status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+            Err((retry, format!("status {}", status.as_u16())))
+        }
+    }
+}
+
+impl ResultSink for WebhookSink {
+    fn name(&self) -> String {
+        // Paths and query strings often carry the webhook's secret token.
+        let host = self
+            .config
+            .url
+            .split("://")
+            .nth(1)
+            .and_then(|rest| rest.split(['/', '?']).next())
+            .unwrap_or_default();
+        format!("webhook {}", host)
+    }
+
+    fn send(&self, result: &ProbeResult) {
+        if !self.wants(&result.output) {
+            return;
+        }
+        let payload = WebhookPayload {
+            event: "probe.result",
+            batch_id: &result.batch_id,
+            output: &result.output,
+        };
+        let body = match serde_json::to_vec(&payload) {
+            Ok(body) => body,
+            Err(err) => {
+                log::warn!("{}: failed to encode payload: {}", self.name(), err);
+                return;
+            }
+        };
+
+        let mut delay = Duration::from_millis(self.config.backoff_ms);
+        for attempt in 0..=self.config.max_retries {
+            match self.post(&body) {
+                Ok(()) => return,
+                Err((true, message)) if attempt < self.config.max_retries => {
+                    log::debug!(
+                        "{}: attempt {} failed ({}), retrying",
+                        self.name(),
+                        attempt + 1,
+                        message
+                    );
+                    thread::sleep(delay);
+                    delay = delay.saturating_mul(2);
+                }
+                Err((_, message)) => {
+                    log::warn!(
+                        "{}: giving up on {} after {} attempts: {}",
+                        self.name(),
+                        result.output.provider_id,
+                        attempt + 1,
+                        message
+                    );
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// `sha256=<hex>` HMAC-SHA256 of `<timestamp>.<body>` with `secret`. Receivers should
+/// recompute it and reject old timestamps to stop replays.
+pub fn signature(secret: &str, timestamp: u64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    let hex: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", hex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::runtime::ProgressFormat;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::Mutex;
+
+    fn result(provider_id: &str, used: f64) -> ProbeResult {
+        ProbeResult {
+            batch_id: "b1".to_string(),
+            output: PluginOutput {
+                provider_id: provider_id.to_string(),
+                display_name: provider_id.to_string(),
+                plan: None,
+                lines: vec![MetricLine::Progress {
+                    label: "Weekly".to_string(),
+                    used,
+                    limit: 200.0,
+                    format: ProgressFormat::Percent,
+                    resets_at: None,
+                    period_duration_ms: None,
+                    color: None,
+                    forecast: None,
+                }],
+                icon_url: String::new(),
+                error: None,
+                fetched_at: None,
+                stale: false,
+            },
+        }
+    }
+
+    /// Answers each request with the next status and returns (headers, body) of each.
+    fn spawn_receiver(statuses: Vec<u16>) -> (String, mpsc::Receiver<(String, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for (stream, status) in listener.incoming().zip(statuses) {
+                let mut stream = stream.expect("accept");
+                let mut buf = Vec::new();
+                let mut chunk = [0u8; 8192];
+                loop {
+                    let read = stream.read(&mut chunk).expect("read");
+                    buf.extend_from_slice(&chunk[..read]);
+                    let text = String::from_utf8_lossy(&buf).to_string();
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let length = head
+                            .lines()
+                            .find_map(|line| {
+                                line.to_lowercase()
+                                    .strip_prefix("content-length:")
+                                    .map(str::to_string)
+                            })
+                            .map(|value| value.trim().parse::<usize>().unwrap())
+                            .unwrap_or(0);
+                        if body.len() >= length {
+                            let _ = tx.send((head.to_string(), body.to_string()));
+                            break;
+                        }
+                    }
+                }
+                let response = format!(
+                    "HTTP/1.1 {} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                );
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        (format!("http://{}/hooks/secret-path", addr), rx)
+    }
+
+    #[test]
+    fn filters_on_provider_and_sends_threshold_crossings_once() {
+        let sink = WebhookSink::new(WebhookConfig {
+            providers: vec!["claude".to_string()],
+            min_percent: Some(90.0),
+            ..WebhookConfig::default()
+        });
+        assert!(!sink.wants(&result("codex", 200.0).output));
+        assert!(!sink.wants(&result("claude", 170.0).output));
+        assert!(sink.wants(&result("claude", 180.0).output), "rose to 90%");
+        assert!(!sink.wants(&result("claude", 190.0).output), "still above");
+        assert!(
+            !sink.wants(&result("claude", 172.0).output),
+            "within hysteresis"
+        );
+        assert!(!sink.wants(&result("claude", 182.0).output));
+        assert!(!sink.wants(&result("claude", 160.0).output), "re-armed");
+        assert!(sink.wants(&result("claude", 180.0).output), "rose again");
+
+        let all = WebhookSink::new(WebhookConfig::default());
+        assert!(all.wants(&result("codex", 0.0).output));
+        assert!(all.wants(&result("codex", 0.0).output));
+        let mut stale = result("codex", 0.0).output;
+        stale.stale = true;
+        assert!(!all.wants(&stale));
+    }
+
+    #[test]
+    fn signature_is_hmac_sha256_of_timestamp_and_body() {
+        // Reference value from `printf '1700000000.{}' | openssl dgst -sha256 -hmac key`.
+        assert_eq!(
+            signature("key", 1_700_000_000, b"{}"),
+            "sha256=9d713ed406bb7076d4123f0dc2c39d2df5c654ed4b0cd56b52c8b4c940bd63ae"
+        );
+    }
+
+    #[test]
+    fn webhook_retries_server_errors_and_signs_requests() {
+        let (url, received) = spawn_receiver(vec![503, 200]);
+        let sink = WebhookSink::new(WebhookConfig {
+            url,
+            secret: Some("shh".to_string()),
+            backoff_ms: 10,
+            ..WebhookConfig::default()
+        });
+        assert!(!sink.name().contains("secret-path"));
+        sink.send(&result("claude", 50.0));
+
+        let (_, first) = received.recv_timeout(Duration::from_secs(5)).unwrap();
+        let (head, body) = received.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(first, body, "retry sends the same body");
+        let payload: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(payload["event"], "probe.result");
+        assert_eq!(payload["output"]["providerId"], "claude");
+
+        let header = |name: &str| {
+            head.lines()
+                .find_map(|line| {
+                    let (key, value) = line.split_once(':')?;
+                    key.eq_ignore_ascii_case(name)
+                        .then(|| value.trim().to_string())
+                })
+                .unwrap()
+        };
+        let timestamp: u64 = header(TIMESTAMP_HEADER).parse().unwrap();
+        assert_eq!(
+            header(SIGNATURE_HEADER),
+            signature("shh", timestamp, body.as_bytes())
+        );
+    }
+
+    #[test]
+    fn webhook_does_not_retry_client_errors() {
+        let (url, received) = spawn_receiver(vec![400, 200]);
+        let sink = WebhookSink::new(WebhookConfig {
+            url,
+            backoff_ms: 10,
+            ..WebhookConfig::default()
+        });
+        sink.send(&result("claude", 50.0));
+        assert!(received.recv_timeout(Duration::from_secs(5)).is_ok());
+        assert!(received.recv_timeout(Duration::from_millis(200)).is_err());
+    }
+
+    struct SlowSink(Mutex<mpsc::Sender<String>>);
+
+    impl ResultSink for SlowSink {
+        fn name(&self) -> String {
+            "slow".to_string()
+        }
+
+        fn send(&self, result: &ProbeResult) {
+            thread::sleep(Duration::from_millis(100));
+            let _ = self
+                .0
+                .lock()
+                .unwrap()
+                .send(result.output.provider_id.clone());
+        }
+    }
+
+    #[test]
+    fn dispatch_does_not_wait_for_sinks() {
+        let (tx, rx) = mpsc::channel();
+        let dispatcher = SinkDispatcher::new(vec![Arc::new(SlowSink(Mutex::new(tx)))]);
+        let started = std::time::Instant::now();
+        dispatcher.dispatch(&result("claude", 1.0));
+        dispatcher.dispatch(&result("codex", 1.0));
+        assert!(started.elapsed() < Duration::from_millis(50));
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)).unwrap(), "claude");
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)).unwrap(), "codex");
+    }
+}
@@ -0,0 +1,379 @@
+//! Embedded SQLite behind `host.sqlite`. Statements run in-process against a bundled
+//! engine with bound parameters, so plugins never splice values into SQL and the
+//! `sqlite3` binary does not need to be installed.
+
+use crate::plugin_engine::limits::Deadline;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rusqlite::ffi::ErrorCode;
+use rusqlite::types::{Value as SqlValue, ValueRef};
+use rusqlite::{Connection, OpenFlags, Statement};
+use serde_json::{Map, Number, Value};
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+
+/// How long writes wait for a lock held by the database's owner (e.g. an editor
+/// mid-save) before failing. Clamped to the probe deadline.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+/// VM instructions between deadline checks while a statement runs.
+const PROGRESS_INTERVAL: i32 = 1_000;
+
+/// Values bound to a statement: positional (`?`, `?1`) from a JS array, or named
+/// (`:key`, `@key`, `$key`) from a JS object.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Params {
+    /// No params argument was passed.
+    None,
+    Positional(Vec<Value>),
+    Named(Map<String, Value>),
+}
+
+impl Params {
+    /// Accept the JSON form of the JS `params` argument.
+    pub fn from_json(value: Option<Value>) -> Result<Self, SqliteError> {
+        match value {
+            None | Some(Value::Null) => Ok(Self::None),
+            Some(Value::Array(values)) => Ok(Self::Positional(values)),
+            Some(Value::Object(values)) => Ok(Self::Named(values)),
+            Some(_) => Err(SqliteError::InvalidParams(
+                "params must be an array or an object".to_string(),
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SqliteError {
+    /// The params do not match the statement's placeholders.
+    InvalidParams(String),
+    /// The probe deadline passed before the statement finished.
+    Timeout,
+    /// SQLite could not open the database or rejected the statement.
+    Failed(String),
+}
+
+impl fmt::Display for SqliteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidParams(message) => write!(f, "sqlite params: {}", message),
+            Self::Timeout => write!(f, "sqlite statement exceeded the probe deadline"),
+            Self::Failed(message) => write!(f, "sqlite error: {}", message),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for SqliteError {
+    fn from(err: rusqlite::Error) -> Self {
+        match &err {
+            rusqlite::Error::SqliteFailure(failure, _)
+                if failure.code == ErrorCode::OperationInterrupted =>
+            {
+                Self::Timeout
+            }
+            rusqlite::Error::MultipleStatement => {
+                Self::Failed("expected a single statement".to_string())
+            }
+            _ => Self::Failed(err.to_string()),
+        }
+    }
+}
+
+/// Run one read-only statement and return its rows as a JSON array of objects keyed
+/// by column name. BLOBs come back base64-encoded.
+pub fn query(
+    db_path: &Path,
+    sql: &str,
+    params: &Params,
+    deadline: &Deadline,
+) -> Result<String, SqliteError> {
+    if deadline.expired() {
+        return Err(SqliteError::Timeout);
+    }
+    // immutable=1 skips locking and the WAL/SHM files, which can fail to open
+    // read-only when another process holds them (e.g. after macOS sleep).
+    let conn = Connection::open_with_flags(
+        immutable_uri(db_path),
+        OpenFlags::SQLITE_OPEN_READ_ONLY
+            | OpenFlags::SQLITE_OPEN_URI
+            | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    interrupt_at(&conn, *deadline);
+
+    let mut stmt = conn.prepare(sql)?;
+    bind(&mut stmt, params)?;
+    let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+    let mut rows = stmt.raw_query();
+    let mut out = Vec::new();
+    while let Some(row) = rows.next()? {
+        let mut object = Map::new();
+        for (index, column) in columns.iter().enumerate() {
+            object.insert(column.clone(), to_json(row.get_ref(index)?));
+        }
+        out.push(Value::Object(object));
+    }
+    Ok(Value::Array(out).to_string())
+}
+
+/// Run a write against the database, creating it if needed, and return the number
+/// of rows changed. Without params, `sql` may hold several `;`-separated statements.
+pub fn exec(
+    db_path: &Path,
+    sql: &str,
+    params: &Params,
+    deadline: &Deadline,
+) -> Result<u64, SqliteError> {
+    if deadline.expired() {
+        return Err(SqliteError::Timeout);
+    }
+    let conn = Connection::open_with_flags(
+        db_path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE
+            | OpenFlags::SQLITE_OPEN_CREATE
+            | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    conn.busy_timeout(deadline.clamp(BUSY_TIMEOUT))?;
+    interrupt_at(&conn, *deadline);
+
+    if *params == Params::None {
+        conn.execute_batch(sql)?;
+        return Ok(conn.changes());
+    }
+    let mut stmt = conn.prepare(sql)?;
+    bind(&mut stmt, params)?;
+    Ok(stmt.raw_execute()? as u64)
+}
+
+/// Percent-encode the path into a read-only `immutable=1` URI (`%` must go first).
+fn immutable_uri(db_path: &Path) -> String {
+    let encoded = db_path
+        .to_string_lossy()
+        .replace('%', "%25")
+        .replace(' ', "%20")
+        .replace('#', "%23")
+        .replace('?', "%3F");
+    format!("file:{}?immutable=1", encoded)
+}
+
+/// Abort the running statement once the probe deadline passes.
+fn interrupt_at(conn: &Connection, deadline: Deadline) {
+    conn.progress_handler(PROGRESS_INTERVAL, Some(move || deadline.expired()));
+}
+
+fn bind(stmt: &mut Statement<'_>, params: &Params) -> Result<(), SqliteError> {
+    let expected = stmt.parameter_count();
+    let given = match params {
+        Params::None => 0,
+        Params::Positional(values) => values.len(),
+        Params::Named(values) => values.len(),
+    };
+    // Unbound placeholders would silently read as NULL.
+    if given != expected {
+        return Err(SqliteError::InvalidParams(format!(
+            "statement expects {} parameters, got {}",
+            expected, given
+        )));
+    }
+    match params {
+        Params::None => {}
+        Params::Positional(values) => {
+            for (index, value) in values.iter().enumerate() {
+                stmt.raw_bind_parameter(index + 1, to_sql(value)?)?;
+            }
+        }
+        Params::Named(values) => {
+            for (name, value) in values {
+                let name = if name.starts_with([':', '@', '$']) {
+                    name.clone()
+                } else {
+                    format!(":{}", name)
+                };
+                let index = stmt.parameter_index(&name)?.ok_or_else(|| {
+                    SqliteError::InvalidParams(format!("statement has no parameter {}", name))
+                })?;
+                stmt.raw_bind_parameter(index, to_sql(value)?)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn to_sql(value: &Value) -> Result<SqlValue, SqliteError> {
+    Ok(match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(flag) => SqlValue::Integer(i64::from(*flag)),
+        Value::Number(number) => match number.as_i64() {
+            Some(integer) => SqlValue::Integer(integer),
+            None => SqlValue::Real(number.as_f64().unwrap_or_default()),
+        },
+        Value::String(text) => SqlValue::Text(text.clone()),
+        Value::Array(_) | Value::Object(_) => {
+            return Err(SqliteError::InvalidParams(
+                "values must be null, booleans, numbers or strings".to_string(),
+            ))
+        }
+    })
+}
+
+fn to_json(value: ValueRef<'_>) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(integer) => Value::from(integer),
+        ValueRef::Real(real) => Number::from_f64(real)
+            .map(Value::Number)
+            .unwrap_or(Value::Null),
+        ValueRef::Text(text) => Value::String(String::from_utf8_lossy(text).into_owned()),
+        ValueRef::Blob(blob) => Value::String(STANDARD.encode(blob)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin_engine::temp_dir;
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    fn temp_db(label: &str) -> PathBuf {
+        let dir = temp_dir(label);
+        std::fs::create_dir_all(&dir).expect("create temp dir");
+        dir.join("state test.db")
+    }
+
+    fn deadline() -> Deadline {
+        Deadline::start(Duration::from_secs(10))
+    }
+
+    fn params(value: Value) -> Params {
+        Params::from_json(Some(value)).expect("params")
+    }
+
+    #[test]
+    fn binds_values_instead_of_splicing_them() {
+        let db = temp_db("bind");
+        let deadline = deadline();
+        exec(
+            &db,
+            "CREATE TABLE t (key TEXT PRIMARY KEY, value)",
+            &Params::None,
+            &deadline,
+        )
+        .expect("create");
+
+        let hostile = "x'); DROP TABLE t; --";
+        let changed = exec(
+            &db,
+            "INSERT OR REPLACE INTO t (key, value) VALUES (?, ?)",
+            &params(json!([hostile, 42])),
+            &deadline,
+        )
+        .expect("insert");
+        assert_eq!(changed, 1);
+        exec(
+            &db,
+            "INSERT INTO t (key, value) VALUES (:key, @value)",
+            &params(json!({ "key": "real", "@value": 1.5 })),
+            &deadline,
+        )
+        .expect("named insert");
+
+        let rows = query(
+            &db,
+            "SELECT key, value FROM t WHERE key = ?1",
+            &params(json!([hostile])),
+            &deadline,
+        )
+        .expect("query");
+        assert_eq!(
+            serde_json::from_str::<Value>(&rows).unwrap(),
+            json!([{ "key": hostile, "value": 42 }])
+        );
+        let rows = query(
+            &db,
+            "SELECT value, NULL AS missing, x'0102' AS raw FROM t WHERE key = $key",
+            &params(json!({ "$key": "real" })),
+            &deadline,
+        )
+        .expect("query");
+        assert_eq!(
+            serde_json::from_str::<Value>(&rows).unwrap(),
+            json!([{ "value": 1.5, "missing": null, "raw": "AQI=" }])
+        );
+    }
+
+    #[test]
+    fn rejects_mismatched_params_and_writes_through_query() {
+        let db = temp_db("reject");
+        let deadline = deadline();
+        exec(
+            &db,
+            "CREATE TABLE t (key TEXT); INSERT INTO t VALUES ('a');",
+            &Params::None,
+            &deadline,
+        )
+        .expect("setup");
+
+        let err = query(
+            &db,
+            "SELECT * FROM t WHERE key = ?",
+            &Params::None,
+            &deadline,
+        )
+        .unwrap_err();
+        assert!(matches!(err, SqliteError::InvalidParams(_)), "{:?}", err);
+        let err = query(
+            &db,
+            "SELECT * FROM t WHERE key = :key",
+            &params(json!({ "other": 1 })),
+            &deadline,
+        )
+        .unwrap_err();
+        assert!(matches!(err, SqliteError::InvalidParams(_)), "{:?}", err);
+        let err = exec(
+            &db,
+            "INSERT INTO t VALUES (?)",
+            &params(json!([["nested"]])),
+            &deadline,
+        )
+        .unwrap_err();
+        assert!(matches!(err, SqliteError::InvalidParams(_)), "{:?}", err);
+        assert!(Params::from_json(Some(json!("a"))).is_err());
+
+        let err = query(&db, "DELETE FROM t", &Params::None, &deadline).unwrap_err();
+        assert!(matches!(err, SqliteError::Failed(_)), "{:?}", err);
+        let err = query(&db, "SELECT 1; DELETE FROM t", &Params::None, &deadline).unwrap_err();
+        assert_eq!(
+            err,
+            SqliteError::Failed("expected a single statement".to_string())
+        );
+        let err = query(&db, ".tables", &Params::None, &deadline).unwrap_err();
+        assert!(matches!(err, SqliteError::Failed(_)), "{:?}", err);
+
+        let rows =
+            query(&db, "SELECT count(*) AS n FROM t", &Params::None, &deadline).expect("count");
+        assert_eq!(rows, r#"[{"n":1}]"#);
+    }
+
+    #[test]
+    fn stops_statements_at_the_deadline() {
+        let db = temp_db("deadline");
+        exec(
+            &db,
+            "CREATE TABLE t (n INTEGER)",
+            &Params::None,
+            &deadline(),
+        )
+        .expect("create");
+
+        let short = Deadline::start(Duration::from_millis(50));
+        let err = query(
+            &db,
+            "WITH RECURSIVE c(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM c) SELECT count(*) FROM c",
+            &Params::None,
+            &short,
+        )
+        .unwrap_err();
+        assert_eq!(err, SqliteError::Timeout);
+        let err = exec(&db, "INSERT INTO t VALUES (1)", &Params::None, &short).unwrap_err();
+        assert_eq!(err, SqliteError::Timeout);
+    }
+}
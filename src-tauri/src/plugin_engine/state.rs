@@ -0,0 +1,203 @@
+use crate::plugin_engine::error::{ProbeError, ProbeErrorKind};
+use crate::plugin_engine::host_api::iso_now;
+use crate::plugin_engine::runtime::PluginOutput;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Where a plugin is in its probe lifecycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProbePhase {
+    /// Not probed yet, or its queued probe was cancelled.
+    Idle,
+    Queued,
+    Running,
+    Ok,
+    Error,
+    TimedOut,
+    Panicked,
+}
+
+impl ProbePhase {
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::Ok | Self::Error | Self::TimedOut | Self::Panicked)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProbeState {
+    pub plugin_id: String,
+    pub phase: ProbePhase,
+    /// Wall time of the last finished probe.
+    pub last_duration_ms: Option<u64>,
+    /// RFC 3339 time of the last probe that returned lines without an error.
+    pub last_success_at: Option<String>,
+    /// Error of the last finished probe, cleared by the next success.
+    pub last_error: Option<ProbeError>,
+}
+
+impl ProbeState {
+    fn idle(plugin_id: &str) -> Self {
+        Self {
+            plugin_id: plugin_id.to_string(),
+            phase: ProbePhase::Idle,
+            last_duration_ms: None,
+            last_success_at: None,
+            last_error: None,
+        }
+    }
+}
+
+/// Lifecycle state for every loaded plugin. Each transition returns the updated
+/// state so the caller can publish it.
+#[derive(Debug, Default)]
+pub struct ProbeStates {
+    states: BTreeMap<String, ProbeState>,
+}
+
+impl ProbeStates {
+    pub fn new<I: IntoIterator<Item = String>>(plugin_ids: I) -> Self {
+        Self {
+            states: plugin_ids
+                .into_iter()
+                .map(|id| {
+                    let state = ProbeState::idle(&id);
+                    (id, state)
+                })
+                .collect(),
+        }
+    }
+
+    pub fn get(&self, plugin_id: &str) -> Option<&ProbeState> {
+        self.states.get(plugin_id)
+    }
+
+    /// All states, ordered by plugin id.
+    pub fn snapshot(&self) -> Vec<ProbeState> {
+        self.states.values().cloned().collect()
+    }
+
+    pub fn queued(&mut self, plugin_id: &str) -> ProbeState {
+        self.set_phase(plugin_id, ProbePhase::Queued)
+    }
+
+    pub fn running(&mut self, plugin_id: &str) -> ProbeState {
+        self.set_phase(plugin_id, ProbePhase::Running)
+    }
+
+    /// A queued probe was dropped before it ran.
+    pub fn dropped(&mut self, plugin_id: &str) -> ProbeState {
+        self.set_phase(plugin_id, ProbePhase::Idle)
+    }
+
+    pub fn finished(
+        &mut self,
+        plugin_id: &str,
+        output: &PluginOutput,
+        duration: Duration,
+        panicked: bool,
+    ) -> ProbeState {
+        let state = self.entry(plugin_id);
+        state.phase = match &output.error {
+            _ if panicked => ProbePhase::Panicked,
+            Some(error) if error.kind == ProbeErrorKind::Timeout => ProbePhase::TimedOut,
+            Some(_) => ProbePhase::Error,
+            None => ProbePhase::Ok,
+        };
+        state.last_duration_ms = Some(duration.as_millis() as u64);
+        state.last_error = output.error.clone();
+        if state.phase == ProbePhase::Ok {
+            state.last_success_at = Some(iso_now());
+        }
+        state.clone()
+    }
+
+    fn set_phase(&mut self, plugin_id: &str, phase: ProbePhase) -> ProbeState {
+        let state = self.entry(plugin_id);
+        state.phase = phase;
+        state.clone()
+    }
+
+    fn entry(&mut self, plugin_id: &str) -> &mut ProbeState {
+        self.states
+            .entry(plugin_id.to_string())
+            .or_insert_with(|| ProbeState::idle(plugin_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(error: Option<ProbeError>) -> PluginOutput {
+        PluginOutput {
+            provider_id: "codex".to_string(),
+            display_name: "Codex".to_string(),
+            plan: None,
+            lines: vec![],
+            icon_url: String::new(),
+            error,
+            fetched_at: None,
+            stale: false,
+        }
+    }
+
+    #[test]
+    fn walks_through_lifecycle() {
+        let mut states = ProbeStates::new(vec!["codex".to_string()]);
+        assert_eq!(states.get("codex").unwrap().phase, ProbePhase::Idle);
+        assert_eq!(states.queued("codex").phase, ProbePhase::Queued);
+        assert_eq!(states.running("codex").phase, ProbePhase::Running);
+
+        let ok = states.finished("codex", &output(None), Duration::from_millis(120), false);
+        assert_eq!(ok.phase, ProbePhase::Ok);
+        assert_eq!(ok.last_duration_ms, Some(120));
+        assert!(ok.last_success_at.is_some());
+
+        let failed = states.finished(
+            "codex",
+            &output(Some(ProbeError::new(ProbeErrorKind::Network, "offline"))),
+            Duration::from_millis(5),
+            false,
+        );
+        assert_eq!(failed.phase, ProbePhase::Error);
+        assert_eq!(failed.last_success_at, ok.last_success_at, "success time is kept");
+        assert_eq!(failed.last_error.unwrap().kind, ProbeErrorKind::Network);
+    }
+
+    #[test]
+    fn classifies_timeouts_and_panics() {
+        let mut states = ProbeStates::default();
+        let timeout = output(Some(ProbeError::new(ProbeErrorKind::Timeout, "slow")));
+        assert_eq!(
+            states.finished("a", &timeout, Duration::ZERO, false).phase,
+            ProbePhase::TimedOut
+        );
+        let internal = output(Some(ProbeError::new(ProbeErrorKind::Internal, "crash")));
+        assert_eq!(
+            states.finished("a", &internal, Duration::ZERO, true).phase,
+            ProbePhase::Panicked
+        );
+        assert!(ProbePhase::Panicked.is_terminal());
+        assert!(!ProbePhase::Queued.is_terminal());
+    }
+
+    #[test]
+    fn serializes_snake_case_phase() {
+        let mut states = ProbeStates::new(vec!["b".to_string(), "a".to_string()]);
+        states.finished(
+            "a",
+            &output(Some(ProbeError::new(ProbeErrorKind::Timeout, "slow"))),
+            Duration::from_millis(30_000),
+            false,
+        );
+        let json = serde_json::to_value(states.snapshot()).unwrap();
+        assert_eq!(json[0]["pluginId"], "a");
+        assert_eq!(json[0]["phase"], "timed_out");
+        assert_eq!(json[0]["lastDurationMs"], 30_000);
+        assert_eq!(json[1]["phase"], "idle");
+        assert!(json[1]["lastSuccessAt"].is_null());
+    }
+}
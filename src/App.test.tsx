@@ -606,7 +606,8 @@ describe("App", () => {
       providerId: "a",
       displayName: "Alpha",
       iconUrl: "icon-a",
-      lines: [{ type: "badge", label: "Error", text: "Bad" }],
+      lines: [],
+      error: { kind: "plugin_bug", message: "Bad", retryable: false },
     })
     const retry = await screen.findByRole("button", { name: "Retry" })
     await userEvent.click(retry)
@@ -789,7 +790,8 @@ describe("App", () => {
       providerId: "a",
       displayName: "Alpha",
       iconUrl: "icon-a",
-      lines: [{ type: "badge", label: "Error", text: "Something failed" }],
+      lines: [],
+      error: { kind: "unknown", message: "Something failed", retryable: true },
     })
 
     // Make startBatch reject on next call (the retry)
@@ -915,7 +917,8 @@ describe("App", () => {
       providerId: "a",
       displayName: "Alpha",
       iconUrl: "icon-a",
-      lines: [{ type: "badge", label: "Error", text: "Network error" }],
+      lines: [],
+      error: { kind: "network", message: "Network error", retryable: true },
     })
 
     const retryButton = await screen.findByRole("button", { name: "Retry" })
@@ -952,7 +955,8 @@ describe("App", () => {
       providerId: "a",
       displayName: "Alpha",
       iconUrl: "icon-a",
-      lines: [{ type: "badge", label: "Error", text: "Network error" }],
+      lines: [],
+      error: { kind: "network", message: "Network error", retryable: true },
     })
 
     // Find and prepare to click retry
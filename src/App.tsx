@@ -380,12 +380,8 @@ function App() {
   }, [activeView, displayPlugins]);
 
   const getErrorMessage = useCallback((output: PluginOutput) => {
-    if (output.lines.length !== 1) return null
-    const line = output.lines[0]
-    if (line.type === "badge" && line.label === "Error") {
-      return line.text || "Couldn't update data. Try again?"
-    }
-    return null
+    if (!output.error) return null
+    return output.error.message || "Couldn't update data. Try again?"
   }, [])
 
   const setLoadingForPlugins = useCallback((ids: string[]) => {
@@ -393,7 +389,8 @@ function App() {
       const next = { ...prev }
       for (const id of ids) {
         const existing = prev[id]
-        next[id] = { data: null, loading: true, error: null, lastManualRefreshAt: existing?.lastManualRefreshAt ?? null }
+        // Keep showing the previous data while the probe runs.
+        next[id] = { data: existing?.data ?? null, loading: true, error: null, lastManualRefreshAt: existing?.lastManualRefreshAt ?? null }
       }
       return next
     })
@@ -423,7 +420,8 @@ function App() {
       setPluginStates((prev) => ({
         ...prev,
         [output.providerId]: {
-          data: errorMessage ? null : output,
+          // A stale output is the last good data plus the error of the failed probe.
+          data: errorMessage && !output.stale ? null : output,
           loading: false,
           error: errorMessage,
           // Only set cooldown timestamp for successful manual refreshes
@@ -500,6 +498,14 @@ function App() {
           console.error("Failed to load tray show percentage:", error)
         }
 
+        let cachedOutputs: PluginOutput[] = []
+        try {
+          const cached = await invoke<PluginOutput[] | null>("get_cached_outputs")
+          cachedOutputs = Array.isArray(cached) ? cached : []
+        } catch (error) {
+          console.error("Failed to load cached outputs:", error)
+        }
+
         if (isMounted) {
           setPluginSettings(normalized)
           setAutoUpdateInterval(storedInterval)
@@ -507,6 +513,15 @@ function App() {
           setDisplayMode(storedDisplayMode)
           setTrayIconStyle(storedTrayIconStyle)
           setTrayShowPercentage(storedTrayShowPercentage)
+          if (cachedOutputs.length > 0) {
+            setPluginStates((prev) => {
+              const next = { ...prev }
+              for (const output of cachedOutputs) {
+                next[output.providerId] = { data: output, loading: false, error: null, lastManualRefreshAt: null }
+              }
+              return next
+            })
+          }
           const enabledIds = getEnabledPluginIds(normalized)
           setLoadingForPlugins(enabledIds)
           try {
@@ -60,6 +60,33 @@ describe("ProviderCard", () => {
     expect(screen.getByText("Two")).toBeInTheDocument()
   })
 
+  it("keeps stale lines visible next to the error", () => {
+    render(
+      <ProviderCard
+        name="Test"
+        displayMode="used"
+        error="Offline"
+        lines={[{ type: "text", label: "Session", value: "42%" }]}
+      />
+    )
+    expect(screen.getByText("Offline")).toBeInTheDocument()
+    expect(screen.getByText("42%")).toBeInTheDocument()
+  })
+
+  it("keeps previous lines instead of the skeleton while refreshing", () => {
+    render(
+      <ProviderCard
+        name="Test"
+        displayMode="used"
+        loading
+        lines={[{ type: "text", label: "Session", value: "42%" }]}
+        skeletonLines={[{ type: "text", label: "Skeleton", scope: "overview" }]}
+      />
+    )
+    expect(screen.getByText("42%")).toBeInTheDocument()
+    expect(screen.queryByText("Skeleton")).not.toBeInTheDocument()
+  })
+
   it("shows loading spinner when retry is enabled", () => {
     const { container } = render(
       <ProviderCard
@@ -221,6 +248,39 @@ describe("ProviderCard", () => {
     vi.useRealTimers()
   })
 
+  it("prefers the host forecast over the local pace estimate", () => {
+    vi.useFakeTimers()
+    vi.setSystemTime(new Date("2026-02-02T12:00:00.000Z"))
+    render(
+      <ProviderCard
+        name="Forecast"
+        displayMode="used"
+        lines={[
+          {
+            type: "progress",
+            label: "Weekly",
+            used: 30,
+            limit: 100,
+            format: { kind: "percent" },
+            resetsAt: "2026-02-03T00:00:00.000Z",
+            periodDurationMs: 24 * 60 * 60 * 1000,
+            forecast: {
+              status: "over-pace",
+              burnRatePerHour: 10,
+              projectedUsage: 150,
+              exhaustsAt: "2026-02-02T19:00:00Z",
+              confidence: 0.8,
+              sampleCount: 12,
+            },
+          },
+        ]}
+      />
+    )
+    expect(screen.getByLabelText("Using fast")).toBeInTheDocument()
+    expect(screen.queryByLabelText("Ahead of pace")).not.toBeInTheDocument()
+    vi.useRealTimers()
+  })
+
   it("fires retry from header button", () => {
     const onRetry = vi.fn()
     const { container } = render(
@@ -11,7 +11,7 @@ import { useNowTicker } from "@/hooks/use-now-ticker"
 import { REFRESH_COOLDOWN_MS, type DisplayMode } from "@/lib/settings"
 import type { ManifestLine, MetricLine } from "@/lib/plugin-types"
 import { clamp01 } from "@/lib/utils"
-import { calculatePaceStatus, type PaceStatus } from "@/lib/pace-status"
+import { calculatePaceStatus, paceStatusFromForecast, type PaceStatus } from "@/lib/pace-status"
 
 interface ProviderCardProps {
   name: string
@@ -223,11 +223,12 @@ export function ProviderCard({
         </div>
         {error && <PluginError message={error} />}
 
-        {loading && !error && (
+        {loading && !error && filteredLines.length === 0 && (
           <SkeletonLines lines={filteredSkeletonLines} />
         )}
 
-        {!loading && !error && (
+        {/* Previous or stale lines stay visible while refreshing or after a failed probe. */}
+        {(!loading || filteredLines.length > 0) && (!error || filteredLines.length > 0) && (
           <div className="space-y-4">
             {filteredLines.map((line, index) => (
               <MetricLineRenderer
@@ -323,10 +324,12 @@ function MetricLineRenderer({
             ? `$${formatNumber(line.limit)} limit`
             : `${formatCount(line.limit)} ${line.format.suffix}`
 
-    // Calculate pace status if we have reset time and period duration
+    // Prefer the host's forecast; otherwise estimate pace from reset time and period duration
     // If used === 0, always show "ahead" (no usage = definitionally ahead of pace)
     const paceResult =
-      line.resetsAt && line.periodDurationMs
+      line.forecast
+        ? { status: paceStatusFromForecast(line.forecast.status) }
+        : line.resetsAt && line.periodDurationMs
         ? calculatePaceStatus(
             line.used,
             line.limit,
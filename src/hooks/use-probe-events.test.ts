@@ -155,6 +155,39 @@ describe("useProbeEvents", () => {
     expect(onBatchComplete).not.toHaveBeenCalled()
   })
 
+  it("shows results of refreshes started through the HTTP API", async () => {
+    const onResult = vi.fn()
+    const onBatchComplete = vi.fn()
+    renderHook(() => useProbeEvents({ onResult, onBatchComplete }))
+    await act(async () => {})
+
+    const output = { providerId: "a", displayName: "A", lines: [], iconUrl: "" } satisfies PluginOutput
+    listeners.get("probe:result")?.({ payload: { batchId: "api-123", output } })
+    listeners.get("probe:batch-complete")?.({ payload: { batchId: "api-123" } })
+
+    expect(onResult).toHaveBeenCalledWith(output)
+    expect(onBatchComplete).not.toHaveBeenCalled()
+  })
+
+  it("cancels active batches", async () => {
+    let lastArgs: any = null
+    invokeMock.mockImplementation(async (cmd: string, args: any) => {
+      if (cmd === "cancel_probe_batch") return true
+      lastArgs = args
+      return { batchId: args.batchId, pluginIds: args.pluginIds ?? [] }
+    })
+    const onBatchComplete = vi.fn()
+    const { result } = renderHook(() => useProbeEvents({ onResult: vi.fn(), onBatchComplete }))
+
+    await act(() => result.current.startBatch(["a"]))
+    const batchId = lastArgs.batchId
+    await act(() => result.current.cancelBatches())
+    expect(invokeMock).toHaveBeenCalledWith("cancel_probe_batch", { batchId })
+
+    listeners.get("probe:batch-complete")?.({ payload: { batchId, cancelled: true } })
+    expect(onBatchComplete).toHaveBeenCalledTimes(1)
+  })
+
   it("rejects when invoke fails", async () => {
     invokeMock.mockRejectedValueOnce(new Error("boom"))
     const { result } = renderHook(() =>
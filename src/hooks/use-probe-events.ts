@@ -10,6 +10,7 @@ type ProbeResult = {
 
 type ProbeBatchComplete = {
   batchId: string
+  cancelled?: boolean
 }
 
 type ProbeBatchStarted = {
@@ -17,6 +18,10 @@ type ProbeBatchStarted = {
   pluginIds: string[]
 }
 
+// Batches started by `POST /v1/refresh` on the local HTTP API. Their results are
+// shown like our own, but they never count toward our batch completion.
+const API_BATCH_PREFIX = "api-"
+
 type UseProbeEventsOptions = {
   onResult: (output: PluginOutput) => void
   onBatchComplete: () => void
@@ -38,7 +43,8 @@ export function useProbeEvents({ onResult, onBatchComplete }: UseProbeEventsOpti
 
     const setup = async () => {
       const resultUnlisten = await listen<ProbeResult>("probe:result", (event) => {
-        if (activeBatchIds.current.has(event.payload.batchId)) {
+        const { batchId } = event.payload
+        if (activeBatchIds.current.has(batchId) || batchId.startsWith(API_BATCH_PREFIX)) {
           onResult(event.payload.output)
         }
       })
@@ -104,5 +110,13 @@ export function useProbeEvents({ onResult, onBatchComplete }: UseProbeEventsOpti
     }
   }, [])
 
-  return { startBatch }
+  // Cancelled batches still get a `probe:batch-complete`, which clears them above.
+  const cancelBatches = useCallback(async () => {
+    const batchIds = Array.from(activeBatchIds.current)
+    await Promise.all(
+      batchIds.map((batchId) => invoke<boolean>("cancel_probe_batch", { batchId }))
+    )
+  }, [])
+
+  return { startBatch, cancelBatches }
 }
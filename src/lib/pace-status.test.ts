@@ -1,6 +1,6 @@
 import { describe, expect, it } from "vitest"
 
-import { calculatePaceStatus, getPaceStatusColor } from "@/lib/pace-status"
+import { calculatePaceStatus, getPaceStatusColor, paceStatusFromForecast } from "@/lib/pace-status"
 
 const ONE_DAY_MS = 24 * 60 * 60 * 1000
 
@@ -63,6 +63,12 @@ describe("pace-status", () => {
     expect(result).toEqual({ status: "behind", projectedUsage: 120 })
   })
 
+  it("maps forecast status to pace status", () => {
+    expect(paceStatusFromForecast("ahead")).toBe("ahead")
+    expect(paceStatusFromForecast("on-track")).toBe("on-track")
+    expect(paceStatusFromForecast("over-pace")).toBe("behind")
+  })
+
   it("maps pace status to color classes", () => {
     expect(getPaceStatusColor("ahead")).toBe("text-green-500")
     expect(getPaceStatusColor("on-track")).toBe("text-yellow-500")
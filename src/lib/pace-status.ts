@@ -1,3 +1,5 @@
+import type { ForecastStatus } from "@/lib/plugin-types"
+
 export type PaceStatus = "ahead" | "on-track" | "behind"
 
 export type PaceResult = {
@@ -71,6 +73,13 @@ export function calculatePaceStatus(
   return { status, projectedUsage }
 }
 
+/**
+ * Map the host's forecast status onto the pace dot's status.
+ */
+export function paceStatusFromForecast(status: ForecastStatus): PaceStatus {
+  return status === "over-pace" ? "behind" : status
+}
+
 /**
  * Get the CSS color class for a pace status.
  */
@@ -3,6 +3,20 @@ export type ProgressFormat =
   | { kind: "dollars" }
   | { kind: "count"; suffix: string }
 
+export type ForecastStatus = "ahead" | "on-track" | "over-pace"
+
+/** Host-computed projection of a progress line until its reset. */
+export type Forecast = {
+  status: ForecastStatus
+  burnRatePerHour: number
+  projectedUsage: number
+  /** RFC 3339 time the limit is hit, if before the reset. */
+  exhaustsAt?: string
+  /** 0..1 */
+  confidence: number
+  sampleCount: number
+}
+
 export type MetricLine =
   | { type: "text"; label: string; value: string; color?: string; subtitle?: string }
   | {
@@ -14,6 +28,7 @@ export type MetricLine =
       resetsAt?: string
       periodDurationMs?: number
       color?: string
+      forecast?: Forecast
     }
   | { type: "badge"; label: string; text: string; color?: string; subtitle?: string }
 
@@ -23,12 +38,104 @@ export type ManifestLine = {
   scope: "overview" | "detail"
 }
 
+export type ProbeErrorKind =
+  | "auth"
+  | "network"
+  | "timeout"
+  | "parse"
+  | "provider"
+  | "plugin_bug"
+  | "unsupported_platform"
+  | "permission"
+  | "internal"
+  | "unknown"
+
+export type ProbeError = {
+  kind: ProbeErrorKind
+  message: string
+  /** Whether retrying without user action is likely to help. */
+  retryable: boolean
+  /** JS stack trace (frames point at the plugin's entry file). */
+  stack?: string
+}
+
 export type PluginOutput = {
   providerId: string
   displayName: string
   plan?: string
   lines: MetricLine[]
   iconUrl: string
+  /** Set when the probe failed as a whole; `lines` is empty in that case unless `stale`. */
+  error?: ProbeError | null
+  /** RFC 3339 time `lines` were fetched. */
+  fetchedAt?: string
+  /** The probe failed and `lines` are the last good ones from an earlier probe. */
+  stale?: boolean
+}
+
+export type ProbePhase =
+  | "idle"
+  | "queued"
+  | "running"
+  | "ok"
+  | "error"
+  | "timed_out"
+  | "panicked"
+
+/** Lifecycle of one plugin's probes, from `get_probe_states` and `probe:state`. */
+export type ProbeState = {
+  pluginId: string
+  phase: ProbePhase
+  lastDurationMs: number | null
+  /** RFC 3339 time of the last successful probe. */
+  lastSuccessAt: string | null
+  lastError: ProbeError | null
+}
+
+/** One recorded value of a progress line, from `query_usage_history`. */
+export type UsageSample = {
+  timestampMs: number
+  used: number
+  limit: number
+  resetsAt?: string
+}
+
+/** One host API call of a probe, from `query_host_audit`. URLs and SQL are redacted. */
+export type HostCall = {
+  startedAt: string
+  durationMs: number
+  /** Redacted message when the call failed, including permission denials. */
+  error?: string
+} & (
+  | {
+      api: "http"
+      method: string
+      url: string
+      /** Null when no response arrived. */
+      status: number | null
+      requestBodySize: number | null
+      responseBodySize: number | null
+      contentType: string | null
+    }
+  | { api: "fs"; operation: string; path: string }
+  | { api: "keychain"; operation: string; service: string; account: string | null }
+  | { api: "sqlite"; operation: string; database: string; statement: string }
+)
+
+/** The host calls of one probe, from `query_host_audit`. */
+export type ProbeAudit = {
+  id: number
+  pluginId: string
+  startedAt: string
+  durationMs: number
+  error: ProbeError | null
+  calls: HostCall[]
+}
+
+/** Inclusive bounds in Unix milliseconds; omit either side for an open range. */
+export type HistoryRange = {
+  fromMs?: number
+  toMs?: number
 }
 
 export type PluginMeta = {